@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use clap::{Parser, Subcommand};
+use trading_data::loader::keystore::{
+    create_credential, rotate_credential, verify_credential, KdfParams, PASSPHRASE_ENV,
+};
+use trading_data::{Credential, EncryptedCredentialProvider};
+
+/// 管理加密的客户端凭证文件
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// 凭证文件，默认为 `config_dir()/credential.enc`
+    #[arg(long, global = true)]
+    path: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 创建凭证文件，secret key 和口令从终端输入
+    Create {
+        #[arg(long)]
+        access_key: String,
+    },
+    /// 更换口令，指定 access key 时同时更换凭证
+    Rotate {
+        #[arg(long)]
+        access_key: Option<String>,
+    },
+    /// 校验口令并输出 access key
+    Verify,
+}
+
+/// 优先读取环境变量，否则从终端输入
+fn passphrase(prompt: &str) -> anyhow::Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    Ok(rpassword::prompt_password(prompt)?)
+}
+
+fn new_passphrase() -> anyhow::Result<String> {
+    let passphrase = rpassword::prompt_password("new passphrase: ")?;
+    if passphrase != rpassword::prompt_password("confirm passphrase: ")? {
+        bail!("passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
+fn credential(access_key: String) -> anyhow::Result<Credential> {
+    let secret_key = rpassword::prompt_password("secret key: ")?;
+    if access_key.is_empty() || secret_key.is_empty() {
+        bail!("access key and secret key must not be empty");
+    }
+    Ok(Credential { access_key, secret_key })
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let path = match args.path {
+        Some(path) => path,
+        None => EncryptedCredentialProvider::default_path()?,
+    };
+    match args.command {
+        Command::Create { access_key } => {
+            let credential = credential(access_key)?;
+            create_credential(&path, &credential, &new_passphrase()?, KdfParams::default())?;
+            println!("created {}", path.display());
+        }
+        Command::Rotate { access_key } => {
+            let current = passphrase("current passphrase: ")?;
+            let credential = access_key.map(credential).transpose()?;
+            rotate_credential(&path, &current, &new_passphrase()?, credential)?;
+            println!("rotated {}", path.display());
+        }
+        Command::Verify => {
+            let credential = verify_credential(&path, &passphrase("passphrase: ")?)?;
+            println!("ok, access key: {}", credential.access_key);
+        }
+    }
+    Ok(())
+}
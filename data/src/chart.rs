@@ -5,7 +5,7 @@ use std::fmt::{Display, Formatter};
 use serde::{Deserialize, Serialize};
 
 use crate::stock::GetSymbolCode;
-use crate::{deref, Percent, Period};
+use crate::{deref, DataError, Percent, Period};
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Bar {
@@ -18,6 +18,8 @@ pub struct Bar {
     #[serde(alias = "price")]
     pub close: f64,
     pub volume: f64,
+    #[serde(default)]
+    pub amount: f64,
     pub yesterday: f64,
 }
 
@@ -97,6 +99,7 @@ impl Bar {
 impl Bar {
     pub fn merge(&mut self, bar: Bar) {
         self.volume += bar.volume;
+        self.amount += bar.amount;
         self.high = self.high.max(bar.high);
         self.low = self.low.min(bar.low);
         self.close = bar.close;
@@ -170,13 +173,15 @@ impl Chart {
 pub struct ChartParamter {
     pub period: Period,
     pub symbol: String,
+    /// 目前只有 `LocalLoader` 的分钟线和远程加载器支持
+    pub start: Option<String>,
     pub end: Option<String>,
     pub limit: Option<usize>,
 }
 
 impl ChartParamter {
     pub fn new(symbol: impl GetSymbolCode, period: Period) -> Self {
-        Self { period, symbol: symbol.symbol().to_string(), limit: None, end: None }
+        Self { period, symbol: symbol.symbol().to_string(), limit: None, start: None, end: None }
     }
 
     pub fn day(symbol: impl GetSymbolCode) -> Self {
@@ -188,10 +193,31 @@ impl ChartParamter {
         self
     }
 
+    pub fn start(mut self, start: impl ToString) -> Self {
+        self.start = Some(start.to_string());
+        self
+    }
+
     pub fn end(mut self, end: impl ToString) -> Self {
         self.end = Some(end.to_string());
         self
     }
+
+    /// `start` 和 `end` 只支持 `YYYY-MM-DD`、`YYYY-MM-DD HH:MM`、`YYYY-MM-DD HH:MM:SS`
+    pub fn validate(&self) -> Result<(), DataError> {
+        for date in [&self.start, &self.end].into_iter().flatten() {
+            let valid = match date.len() {
+                10 => chrono::NaiveDate::parse_from_str(date, crate::FORMAT).is_ok(),
+                16 => chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M").is_ok(),
+                19 => chrono::NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").is_ok(),
+                _ => false,
+            };
+            if !valid {
+                return Err(DataError::Unsupported(format!("invalid date {:?}", date)));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<T> From<T> for ChartParamter
@@ -206,15 +232,15 @@ where
 
 #[async_trait::async_trait]
 pub trait ChartLoader {
-    async fn chart(&self, param: impl Into<ChartParamter> + Send) -> anyhow::Result<Chart>;
+    async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError>;
 }
 
 #[async_trait::async_trait]
 pub trait BarLoader {
-    async fn current(&self, symbol: impl GetSymbolCode + Send) -> anyhow::Result<Bar>;
+    async fn current(&self, symbol: impl GetSymbolCode + Send) -> Result<Bar, DataError>;
 }
 
 #[async_trait::async_trait]
 pub trait MarketCurrentLoader {
-    async fn market(&self) -> anyhow::Result<HashMap<String, Bar>>;
+    async fn market(&self) -> Result<HashMap<String, Bar>, DataError>;
 }
@@ -0,0 +1,492 @@
+//! 应用配置
+//!
+//! 配置文件为 `config_dir()/config.toml`，可以通过 `TRADING_CONFIG` 指定其它路径。每个 profile 独立设置
+//! 数据源、服务地址、超时、数据目录、交易费用和界面偏好，未设置的项使用默认值：
+//!
+//! ```toml
+//! profile = "default"
+//!
+//! [profiles.default]
+//! source = "local"
+//!
+//! [profiles.server]
+//! source = "remote"
+//! host = "http://192.168.1.10:18686/api/data"
+//! timeout = 5
+//! hmac_sign = true
+//! fees = { commission = 0.0001, min_commission = 0.0 }
+//! ui = { theme = "light" }
+//! ```
+//!
+//! `TRADING_PROFILE` 选择 profile，`TRADING_<KEY>` 覆盖当前 profile 中的任意一项，嵌套的项用下划线连接，
+//! 例如 `TRADING_HOST`、`TRADING_DATA_DIR`、`TRADING_FEES_COMMISSION`、`TRADING_UI_THEME`。
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config_dir, data_dir, CommandCredentialProvider, DataSource, EnvCredentialProvider, FileCredentialProvider,
+    LocalLoader, MockSource, MultiCredentialProvider, Period, RemoteLoader, SignVersion,
+};
+
+pub const ENV_PREFIX: &str = "TRADING_";
+pub const ENV_CONFIG: &str = "TRADING_CONFIG";
+pub const ENV_PROFILE: &str = "TRADING_PROFILE";
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    #[default]
+    Local,
+    Remote,
+    Sqlite,
+    Mock,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    #[default]
+    Dark,
+}
+
+/// 交易费用，费率均为成交金额的比例
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fees {
+    /// 佣金，买卖双向收取
+    pub commission: f64,
+    /// 单笔最低佣金
+    pub min_commission: f64,
+    /// 印花税，仅卖出收取
+    pub stamp_duty: f64,
+    /// 过户费，买卖双向收取
+    pub transfer: f64,
+}
+
+impl Default for Fees {
+    fn default() -> Self {
+        Self { commission: 0.00025, min_commission: 5.0, stamp_duty: 0.0005, transfer: 0.00001 }
+    }
+}
+
+impl Fees {
+    const KEYS: &'static [&'static str] = &["commission", "min_commission", "stamp_duty", "transfer"];
+
+    /// 成交金额为 `amount` 时的总费用
+    pub fn cost(&self, amount: f64, sell: bool) -> f64 {
+        let commission = (amount * self.commission).max(self.min_commission);
+        let stamp_duty = if sell { amount * self.stamp_duty } else { 0.0 };
+        commission + stamp_duty + amount * self.transfer
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Ui {
+    pub theme: Theme,
+    /// 默认K线周期，与 `Period` 的字符串格式相同
+    pub period: String,
+    /// 默认显示的K线数量
+    pub limit: usize,
+    /// 红涨绿跌
+    pub red_up: bool,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self {
+            theme: Theme::default(),
+            period: Period::Day.to_string(),
+            limit: 120,
+            red_up: true,
+        }
+    }
+}
+
+impl Ui {
+    const KEYS: &'static [&'static str] = &["theme", "period", "limit", "red_up"];
+
+    pub fn period(&self) -> anyhow::Result<Period> {
+        Period::from_str(&self.period)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub source: SourceKind,
+    /// 远程数据服务地址
+    pub host: String,
+    /// 请求超时（秒）
+    pub timeout: u64,
+    /// 连接超时（秒）
+    pub connect_timeout: u64,
+    /// 数据目录，默认使用 `data_dir()`
+    pub data_dir: Option<PathBuf>,
+    /// 凭证文件，默认使用 `config_dir()/credential.json`
+    pub credential_file: Option<PathBuf>,
+    /// 获取凭证的外部命令，见 [`CommandCredentialProvider`]
+    pub credential_command: Option<String>,
+    /// 使用 HMAC-SHA256 签名，服务端需要支持，默认使用旧版签名
+    pub hmac_sign: bool,
+    pub fees: Fees,
+    pub ui: Ui,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        let remote = RemoteLoader::default();
+        Self {
+            source: SourceKind::default(),
+            host: remote.host,
+            timeout: remote.timeout.map(|v| v.as_secs()).unwrap_or(3),
+            connect_timeout: 3,
+            data_dir: None,
+            credential_file: None,
+            credential_command: None,
+            hmac_sign: false,
+            fees: Fees::default(),
+            ui: Ui::default(),
+        }
+    }
+}
+
+impl Profile {
+    const KEYS: &'static [&'static str] = &[
+        "source",
+        "host",
+        "timeout",
+        "connect_timeout",
+        "data_dir",
+        "credential_file",
+        "credential_command",
+        "hmac_sign",
+    ];
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let url = url::Url::parse(&self.host).context(format!("host: invalid url `{}`", self.host))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("host: unsupported scheme `{}`, expected http or https", url.scheme());
+        }
+        if self.timeout == 0 {
+            bail!("timeout: must be greater than 0");
+        }
+        if self.connect_timeout == 0 {
+            bail!("connect_timeout: must be greater than 0");
+        }
+        if let Some(dir) = &self.data_dir {
+            if !dir.is_absolute() {
+                bail!("data_dir: must be an absolute path, got {}", dir.display());
+            }
+        }
+        let rates = [
+            ("commission", self.fees.commission),
+            ("stamp_duty", self.fees.stamp_duty),
+            ("transfer", self.fees.transfer),
+        ];
+        for (name, rate) in rates {
+            if !(0.0..1.0).contains(&rate) {
+                bail!("fees.{}: must be in [0, 1), got {}", name, rate);
+            }
+        }
+        if !(0.0..).contains(&self.fees.min_commission) {
+            bail!("fees.min_commission: must not be negative, got {}", self.fees.min_commission);
+        }
+        self.ui
+            .period()
+            .context(format!("ui.period: invalid period `{}`", self.ui.period))?;
+        if self.ui.limit == 0 {
+            bail!("ui.limit: must be greater than 0");
+        }
+        Ok(())
+    }
+
+    pub fn data_dir(&self) -> anyhow::Result<PathBuf> {
+        match &self.data_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => data_dir(),
+        }
+    }
+
+    pub fn local_loader(&self) -> anyhow::Result<LocalLoader> {
+        LocalLoader::new(self.data_dir()?)
+    }
+
+    /// 设置了外部命令时只使用命令返回的凭证，否则依次从环境变量和凭证文件读取，都没有时不签名
+    pub fn remote_loader(&self) -> anyhow::Result<RemoteLoader> {
+        let timeout = Some(Duration::from_secs(self.timeout));
+        let loader = match (&self.credential_command, &self.credential_file) {
+            (Some(command), _) => RemoteLoader::new(&self.host, timeout, CommandCredentialProvider::shell(command))?,
+            (None, Some(path)) => {
+                let provider = MultiCredentialProvider::new(
+                    vec![
+                        Box::new(EnvCredentialProvider::default()),
+                        Box::new(FileCredentialProvider::new(path)),
+                    ],
+                    false,
+                );
+                RemoteLoader::new(&self.host, timeout, provider)?
+            }
+            (None, None) => RemoteLoader::new(&self.host, timeout, MultiCredentialProvider::default())?,
+        };
+        let sign_version = if self.hmac_sign { SignVersion::HmacSha256 } else { SignVersion::Legacy };
+        Ok(loader
+            .with_connect_timeout(Duration::from_secs(self.connect_timeout))
+            .with_sign_version(sign_version))
+    }
+
+    /// 按 `source` 创建数据源
+    pub fn source(&self) -> anyhow::Result<Arc<dyn DataSource>> {
+        Ok(match self.source {
+            SourceKind::Local => Arc::new(self.local_loader()?),
+            SourceKind::Remote => Arc::new(self.remote_loader()?),
+            #[cfg(feature = "sqlite")]
+            SourceKind::Sqlite => {
+                Arc::new(crate::loader::sqlite::SqliteLoader::open(self.data_dir()?.join("trading.db"))?)
+            }
+            #[cfg(not(feature = "sqlite"))]
+            SourceKind::Sqlite => bail!("source sqlite requires the `sqlite` feature"),
+            SourceKind::Mock => Arc::new(MockSource::new()),
+        })
+    }
+}
+
+/// 环境变量对应的配置项，不是配置项的变量返回 `None`
+fn env_key(name: &str) -> Option<(Option<&'static str>, String)> {
+    let key = name.strip_prefix(ENV_PREFIX)?.to_lowercase();
+    let sections = [("fees", Fees::KEYS), ("ui", Ui::KEYS)];
+    for (section, keys) in sections {
+        if let Some(field) = key.strip_prefix(section).and_then(|v| v.strip_prefix('_')) {
+            return keys.contains(&field).then(|| (Some(section), field.to_string()));
+        }
+    }
+    Profile::KEYS.contains(&key.as_str()).then_some((None, key))
+}
+
+/// 按配置项原来的类型解析环境变量，字符串和未设置的项保持原样
+fn env_value(current: Option<&toml::Value>, raw: &str) -> anyhow::Result<toml::Value> {
+    match current {
+        None | Some(toml::Value::String(_)) => Ok(toml::Value::String(raw.to_string())),
+        Some(current) => {
+            let value = toml::from_str::<toml::value::Table>(&format!("v = {}", raw))
+                .ok()
+                .and_then(|mut v| v.remove("v"))
+                .context(format!("invalid value `{}`", raw))?;
+            let value = match (current, value) {
+                (toml::Value::Float(_), toml::Value::Integer(v)) => toml::Value::Float(v as f64),
+                (_, value) => value,
+            };
+            if value.type_str() != current.type_str() {
+                bail!("expected {}, got `{}`", current.type_str(), raw);
+            }
+            Ok(value)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// 默认使用的 profile
+    pub profile: String,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            profile: "default".to_string(),
+            profiles: BTreeMap::from([("default".to_string(), Profile::default())]),
+        }
+    }
+}
+
+impl FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let config: Config = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Config {
+    /// `TRADING_CONFIG` 或 `config_dir()/config.toml`
+    pub fn path() -> anyhow::Result<PathBuf> {
+        match std::env::var_os(ENV_CONFIG) {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => Ok(config_dir()?.join("config.toml")),
+        }
+    }
+
+    /// 读取默认路径的配置，文件不存在时使用默认配置
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            tracing::debug!("config {} not found, use default", path.display());
+            return Ok(Self::default());
+        }
+        Self::from_file(path)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).context(format!("read config: {}", path.display()))?;
+        Self::from_str(&content).context(format!("load config: {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(path, content).context(format!("write config: {}", path.display()))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.profiles.contains_key(&self.profile) {
+            bail!("profile `{}` not found, available: {}", self.profile, self.names());
+        }
+        for (name, profile) in &self.profiles {
+            profile.validate().context(format!("profile `{}`", name))?;
+        }
+        Ok(())
+    }
+
+    fn names(&self) -> String {
+        self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+    }
+
+    /// 当前使用的 profile，已应用环境变量
+    pub fn active(&self) -> anyhow::Result<Profile> {
+        let name = std::env::var(ENV_PROFILE).unwrap_or_else(|_| self.profile.clone());
+        self.resolve(&name, std::env::vars())
+    }
+
+    /// 用 `vars` 中 `TRADING_` 开头的变量覆盖指定的 profile 并校验
+    pub fn resolve(&self, name: &str, vars: impl IntoIterator<Item = (String, String)>) -> anyhow::Result<Profile> {
+        let Some(profile) = self.profiles.get(name) else {
+            bail!("profile `{}` not found, available: {}", name, self.names());
+        };
+        let mut value = toml::Value::try_from(profile)?;
+        for (var, raw) in vars {
+            let Some((section, key)) = env_key(&var) else {
+                continue;
+            };
+            let table = match section {
+                Some(section) => value.get_mut(section).and_then(toml::Value::as_table_mut),
+                None => value.as_table_mut(),
+            };
+            let table = table.context("invalid profile")?;
+            let value = env_value(table.get(&key), &raw).context(var.clone())?;
+            table.insert(key, value);
+        }
+        let profile: Profile = value.try_into().context(format!("profile `{}`", name))?;
+        profile.validate().context(format!("profile `{}`", name))?;
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    const CONFIG: &str = r#"
+profile = "server"
+
+[profiles.default]
+
+[profiles.server]
+source = "remote"
+host = "http://192.168.1.10:18686/api/data"
+timeout = 5
+fees = { commission = 0.0001, min_commission = 0.0 }
+ui = { theme = "light", period = "5m" }
+"#;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn profiles() {
+        let config = Config::from_str(CONFIG).unwrap();
+        assert_eq!(config.resolve("default", vec![]).unwrap(), Profile::default());
+
+        let server = config.resolve("server", vec![]).unwrap();
+        assert_eq!(
+            (server.source, server.timeout, server.connect_timeout),
+            (SourceKind::Remote, 5, 3)
+        );
+        assert_eq!((server.fees.commission, server.fees.stamp_duty), (0.0001, 0.0005));
+        assert_eq!(
+            (server.ui.theme, server.ui.period().unwrap()),
+            (Theme::Light, Period::Minute(5))
+        );
+        assert!((server.fees.cost(10000.0, true) - 6.1).abs() < 1e-9);
+        let loader = server.remote_loader().unwrap();
+        assert_eq!(loader.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(loader.sign_version, SignVersion::Legacy);
+        let hmac = Profile { hmac_sign: true, ..server.clone() };
+        assert_eq!(hmac.remote_loader().unwrap().sign_version, SignVersion::HmacSha256);
+
+        let dir = TempDir::new("config");
+        let path = dir.join("config.toml");
+        config.save(&path).unwrap();
+        assert_eq!(Config::from_file(&path).unwrap(), config);
+    }
+
+    #[test]
+    fn env_override() {
+        let config = Config::from_str(CONFIG).unwrap();
+        let env = vars(&[
+            ("TRADING_HOST", "https://example.com/api/data"),
+            ("TRADING_TIMEOUT", "10"),
+            ("TRADING_DATA_DIR", "/srv/trading"),
+            ("TRADING_FEES_STAMP_DUTY", "0"),
+            ("TRADING_UI_RED_UP", "false"),
+            ("TRADING_ACCESS_KEY", "ignored"),
+            ("HOME", "/root"),
+        ]);
+        let profile = config.resolve("server", env).unwrap();
+        assert_eq!(profile.host, "https://example.com/api/data");
+        assert_eq!(profile.timeout, 10);
+        assert_eq!(profile.data_dir, Some(PathBuf::from("/srv/trading")));
+        assert_eq!(profile.fees.stamp_duty, 0.0);
+        assert!(!profile.ui.red_up);
+
+        let err = config.resolve("server", vars(&[("TRADING_TIMEOUT", "abc")])).unwrap_err();
+        assert!(format!("{:#}", err).contains("TRADING_TIMEOUT"));
+        let err = config.resolve("server", vars(&[("TRADING_SOURCE", "ftp")])).unwrap_err();
+        assert!(format!("{:#}", err).contains("profile `server`"));
+    }
+
+    #[test]
+    fn validate() {
+        let errors = [
+            ("profile = \"missing\"", "profile `missing` not found"),
+            ("[profiles.default]\nhost = \"ftp://127.0.0.1\"", "host: unsupported scheme"),
+            ("[profiles.default]\ntimeout = 0", "timeout: must be greater than 0"),
+            ("[profiles.default]\ndata_dir = \"data\"", "data_dir: must be an absolute path"),
+            (
+                "[profiles.default.fees]\ncommission = 1.5",
+                "fees.commission: must be in [0, 1)",
+            ),
+            ("[profiles.default.ui]\nperiod = \"month\"", "ui.period: invalid period"),
+            ("[profiles.default]\nhots = \"x\"", "unknown field `hots`"),
+        ];
+        for (content, message) in errors {
+            let err = format!("{:#}", Config::from_str(content).unwrap_err());
+            assert!(err.contains(message), "{}: {}", content, err);
+        }
+        assert!(Config::from_str("").is_ok());
+    }
+}
@@ -0,0 +1,529 @@
+//! K线 csv 编解码
+//!
+//! 按表头名称映射列，列顺序不限，未识别的列会被忽略；
+//! 表头无法识别时按默认列顺序解析，兼容旧的数据文件。
+
+use std::fmt::{Display, Formatter};
+
+use crate::Bar;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Column {
+    Symbol,
+    Date,
+    /// 与日期分开存放的时间，读取时拼接到日期后面
+    Time,
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+    Amount,
+    Yesterday,
+}
+
+/// K线文件默认列
+pub const BAR_COLUMNS: &[Column] = &[
+    Column::Date,
+    Column::Open,
+    Column::High,
+    Column::Low,
+    Column::Close,
+    Column::Volume,
+];
+
+/// 行情快照默认列，首列为股票代码
+pub const RECORD_COLUMNS: &[Column] = &[
+    Column::Symbol,
+    Column::Date,
+    Column::Open,
+    Column::High,
+    Column::Low,
+    Column::Close,
+    Column::Volume,
+];
+
+const REQUIRED: &[Column] = &[
+    Column::Date,
+    Column::Open,
+    Column::High,
+    Column::Low,
+    Column::Close,
+    Column::Volume,
+];
+
+impl Column {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Symbol => "symbol",
+            Column::Date => "date",
+            Column::Time => "time",
+            Column::Open => "open",
+            Column::High => "high",
+            Column::Low => "low",
+            Column::Close => "close",
+            Column::Volume => "volume",
+            Column::Amount => "amount",
+            Column::Yesterday => "yesterday",
+        }
+    }
+
+    /// 识别表头名称，支持常见的英文和中文列名
+    pub fn parse(name: &str) -> Option<Column> {
+        let name = name.trim().trim_start_matches('\u{feff}').to_ascii_lowercase();
+        let column = match name.as_str() {
+            "symbol" | "code" | "代码" | "股票代码" => Column::Symbol,
+            "date" | "datetime" | "日期" => Column::Date,
+            "time" | "时间" => Column::Time,
+            "open" | "开盘" | "开盘价" => Column::Open,
+            "high" | "最高" | "最高价" => Column::High,
+            "low" | "最低" | "最低价" => Column::Low,
+            "close" | "price" | "收盘" | "收盘价" | "现价" => Column::Close,
+            "volume" | "vol" | "成交量" => Column::Volume,
+            "amount" | "成交额" => Column::Amount,
+            "yesterday" | "pre_close" | "preclose" | "昨收" | "前收盘" => Column::Yesterday,
+            _ => return None,
+        };
+        Some(column)
+    }
+}
+
+/// 解析错误，行号和列号均从 1 开始，列号为 0 表示整行错误
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CsvError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl CsvError {
+    pub(crate) fn new(line: usize, column: usize, message: impl ToString) -> Self {
+        Self { line, column, message: message.to_string() }
+    }
+}
+
+impl Display for CsvError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// 一行数据，`symbol` 仅在包含代码列时存在
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub symbol: Option<String>,
+    pub bar: Bar,
+}
+
+#[derive(Debug, Clone)]
+pub struct Header {
+    delimiter: char,
+    columns: Vec<Option<Column>>,
+}
+
+impl Header {
+    /// 解析表头，没有任何可识别的列时使用 `default` 作为列顺序，多个列对应同一个字段时返回错误
+    ///
+    /// 只有时间列时把它当作日期列
+    pub fn parse(line: &str, default: &[Column]) -> Result<Self, CsvError> {
+        let delimiter = if !line.contains(',') && line.contains('\t') { '\t' } else { ',' };
+        let mut columns: Vec<Option<Column>> = line.split(delimiter).map(Column::parse).collect();
+        if columns.iter().all(Option::is_none) {
+            columns = default.iter().map(|v| Some(*v)).collect();
+        }
+        if !columns.contains(&Some(Column::Date)) {
+            if let Some(time) = columns.iter().position(|v| *v == Some(Column::Time)) {
+                columns[time] = Some(Column::Date);
+            }
+        }
+        for (index, column) in columns.iter().enumerate() {
+            if let Some(column) = column {
+                if columns[..index].contains(&Some(*column)) {
+                    return Err(CsvError::new(1, index + 1, format!("duplicate column {}", column.name())));
+                }
+            }
+        }
+        let header = Self { delimiter, columns };
+        for column in REQUIRED {
+            if header.position(*column).is_none() {
+                return Err(CsvError::new(1, 0, format!("missing column {}", column.name())));
+            }
+        }
+        Ok(header)
+    }
+
+    pub fn position(&self, column: Column) -> Option<usize> {
+        self.columns.iter().position(|v| *v == Some(column))
+    }
+
+    pub fn contains(&self, column: Column) -> bool {
+        self.position(column).is_some()
+    }
+
+    fn record(&self, number: usize, line: &str) -> Result<Record, CsvError> {
+        let fields: Vec<&str> = line.split(self.delimiter).collect();
+        let mut record = Record { symbol: None, bar: Bar::default() };
+        let mut time = None;
+        for (index, column) in self.columns.iter().enumerate() {
+            let Some(column) = column else {
+                continue;
+            };
+            let Some(field) = fields.get(index).map(|v| v.trim()) else {
+                if REQUIRED.contains(column) {
+                    return Err(CsvError::new(number, index + 1, format!("missing field {}", column.name())));
+                }
+                continue;
+            };
+            let parse = |field: &str| -> Result<f64, CsvError> {
+                if field.is_empty() && !REQUIRED.contains(column) {
+                    return Ok(0.0);
+                }
+                field
+                    .parse::<f64>()
+                    .map_err(|e| CsvError::new(number, index + 1, format!("parse {}: {}", column.name(), e)))
+            };
+            let bar = &mut record.bar;
+            match column {
+                Column::Symbol => record.symbol = Some(field.to_string()),
+                Column::Date => bar.date = field.to_string(),
+                Column::Time => time = Some(field),
+                Column::Open => bar.open = parse(field)?,
+                Column::High => bar.high = parse(field)?,
+                Column::Low => bar.low = parse(field)?,
+                Column::Close => bar.close = parse(field)?,
+                Column::Volume => bar.volume = parse(field)?,
+                Column::Amount => bar.amount = parse(field)?,
+                Column::Yesterday => bar.yesterday = parse(field)?,
+            }
+        }
+        if let Some(time) = time.filter(|v| !v.is_empty()) {
+            record.bar.date = format!("{} {}", record.bar.date, time);
+        }
+        Ok(record)
+    }
+}
+
+/// 解析 csv 内容，首行为表头，跳过空行
+pub fn read(content: &str, default: &[Column]) -> Result<Vec<Record>, CsvError> {
+    let Some(header) = content.lines().next() else {
+        return Ok(vec![]);
+    };
+    read_with(Header::parse(header, default)?, content)
+}
+
+fn read_with(header: Header, content: &str) -> Result<Vec<Record>, CsvError> {
+    content
+        .lines()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| header.record(index + 1, line))
+        .collect()
+}
+
+/// 解析K线文件
+pub fn read_bars(content: &str) -> Result<Vec<Bar>, CsvError> {
+    Ok(read(content, BAR_COLUMNS)?.into_iter().map(|v| v.bar).collect())
+}
+
+/// 解析行情快照，每一行都必须包含股票代码
+///
+/// 没有代码列时兼容旧服务端的表头，首列 `name` 存放的是股票代码
+pub fn read_records(content: &str) -> Result<Vec<(String, Bar)>, CsvError> {
+    let line = content.lines().next().unwrap_or_default();
+    let mut header = Header::parse(line, RECORD_COLUMNS)?;
+    if !header.contains(Column::Symbol) {
+        let first = line.split(header.delimiter).next().unwrap_or_default();
+        if first.trim().trim_start_matches('\u{feff}').eq_ignore_ascii_case("name") && header.columns[0].is_none() {
+            header.columns[0] = Some(Column::Symbol);
+        } else {
+            return Err(CsvError::new(1, 0, "missing column symbol"));
+        }
+    }
+    Ok(read_with(header, content)?
+        .into_iter()
+        .map(|v| (v.symbol.unwrap_or_default(), v.bar))
+        .collect())
+}
+
+/// 按列名读取的通用表格，用于K线以外的数据文件
+///
+/// 分隔符按表头自动识别，列的顺序不限，未列出的列会被忽略，表头前的空行和数据中的空行会被跳过
+#[derive(Debug, Clone)]
+pub struct Table<'a> {
+    columns: Vec<&'a str>,
+    positions: Vec<Option<usize>>,
+    rows: Vec<(usize, Vec<&'a str>)>,
+}
+
+impl<'a> Table<'a> {
+    /// `columns` 中的前 `required` 列必须出现在表头中
+    pub fn parse(content: &'a str, columns: &[&'a str], required: usize) -> Result<Self, CsvError> {
+        let mut lines = content.lines().enumerate().filter(|(_, v)| !v.trim().is_empty());
+        let Some((_, header)) = lines.next() else {
+            return Ok(Self { columns: columns.to_vec(), positions: vec![None; columns.len()], rows: vec![] });
+        };
+        let delimiter = if !header.contains(',') && header.contains('\t') { '\t' } else { ',' };
+        let header: Vec<_> = header
+            .split(delimiter)
+            .map(|v| v.trim().trim_start_matches('\u{feff}'))
+            .collect();
+        let positions: Vec<_> = columns.iter().map(|v| header.iter().position(|h| h == v)).collect();
+        for (column, position) in columns.iter().zip(&positions).take(required) {
+            if position.is_none() {
+                return Err(CsvError::new(1, 0, format!("missing column {}", column)));
+            }
+        }
+        let rows = lines
+            .map(|(index, line)| (index + 1, line.split(delimiter).map(str::trim).collect()))
+            .collect();
+        Ok(Self { columns: columns.to_vec(), positions, rows })
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<'_, 'a>> {
+        self.rows.iter().map(|(line, fields)| Row { table: self, line: *line, fields })
+    }
+}
+
+/// 表格中的一行，按 [`Table::parse`] 的列序号取值
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'t, 'a> {
+    table: &'t Table<'a>,
+    line: usize,
+    fields: &'t [&'a str],
+}
+
+impl<'a> Row<'_, 'a> {
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// 列不存在或者字段缺失时为空字符串
+    pub fn text(&self, column: usize) -> &'a str {
+        self.table.positions[column]
+            .and_then(|i| self.fields.get(i))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn required_text(&self, column: usize) -> Result<&'a str, CsvError> {
+        match self.text(column) {
+            "" => Err(self.error(column, format!("missing field {}", self.table.columns[column]))),
+            value => Ok(value),
+        }
+    }
+
+    /// 空字段为 `None`
+    pub fn number(&self, column: usize) -> Result<Option<f64>, CsvError> {
+        let value = self.text(column);
+        if value.is_empty() {
+            return Ok(None);
+        }
+        value
+            .parse()
+            .map(Some)
+            .map_err(|e| self.error(column, format!("parse {}: {}", self.table.columns[column], e)))
+    }
+
+    pub fn required(&self, column: usize) -> Result<f64, CsvError> {
+        self.number(column)?
+            .ok_or_else(|| self.error(column, format!("missing field {}", self.table.columns[column])))
+    }
+
+    /// 当前行指定列的错误，列不存在时列号为 0
+    pub fn error(&self, column: usize, message: impl ToString) -> CsvError {
+        let position = self.table.positions[column].map(|i| i + 1).unwrap_or_default();
+        CsvError::new(self.line, position, message)
+    }
+}
+
+/// 按指定的列输出 csv
+#[derive(Debug, Clone)]
+pub struct Writer {
+    columns: Vec<Column>,
+}
+
+impl Default for Writer {
+    fn default() -> Self {
+        Self::new(BAR_COLUMNS)
+    }
+}
+
+impl Writer {
+    pub fn new(columns: &[Column]) -> Self {
+        Self { columns: columns.to_vec() }
+    }
+
+    pub fn header(&self) -> String {
+        self.columns.iter().map(Column::name).collect::<Vec<_>>().join(",")
+    }
+
+    pub fn line(&self, symbol: &str, bar: &Bar) -> String {
+        self.columns
+            .iter()
+            .map(|column| match column {
+                Column::Symbol => symbol.to_string(),
+                Column::Date => bar.date.clone(),
+                Column::Time => bar.date.split_once(' ').map(|v| v.1).unwrap_or_default().to_string(),
+                Column::Open => bar.open.to_string(),
+                Column::High => bar.high.to_string(),
+                Column::Low => bar.low.to_string(),
+                Column::Close => bar.close.to_string(),
+                Column::Volume => bar.volume.to_string(),
+                Column::Amount => bar.amount.to_string(),
+                Column::Yesterday => bar.yesterday.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn write_bars<'a>(&self, bars: impl IntoIterator<Item = &'a Bar>) -> String {
+        self.write_records(bars.into_iter().map(|bar| ("", bar)))
+    }
+
+    pub fn write_records<'a, S: AsRef<str>>(&self, records: impl IntoIterator<Item = (S, &'a Bar)>) -> String {
+        records.into_iter().fold(self.header(), |mut acc, (symbol, bar)| {
+            acc.push('\n');
+            acc.push_str(&self.line(symbol.as_ref(), bar));
+            acc
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_mapping() {
+        let content = "close,date,extra,low,high,open,volume,amount\n\
+                       10.2,2023-07-10,x,9.8,10.5,10.0,1000,10200\n\
+                       \n\
+                       10.6,2023-07-11,y,10.1,10.8,10.2,1200,";
+        let bars = read_bars(content).unwrap();
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].date, "2023-07-10");
+        assert_eq!(
+            (bars[0].open, bars[0].high, bars[0].low, bars[0].close),
+            (10.0, 10.5, 9.8, 10.2)
+        );
+        assert_eq!(bars[0].amount, 10200.0);
+        assert_eq!(bars[1].amount, 0.0);
+
+        let content = "日期,开盘,最高,最低,收盘,成交量\n2023-07-10,10.0,10.5,9.8,10.2,1000";
+        assert_eq!(read_bars(content).unwrap()[0].volume, 1000.0);
+    }
+
+    #[test]
+    fn legacy_layout() {
+        let content = "a,b,c,d,e,f\n2023-07-10,10.0,10.5,9.8,10.2,1000";
+        assert_eq!(read_bars(content).unwrap()[0].close, 10.2);
+
+        let content = "x,y,z,o,h,l,c\n601888,2023-07-10,10.0,10.5,9.8,10.2,1000";
+        let records = read_records(content).unwrap();
+        assert_eq!(records[0].0, "601888");
+        assert_eq!(records[0].1.low, 9.8);
+    }
+
+    #[test]
+    fn errors() {
+        let err = read_bars("date,open,high,low,close\n2023-07-10,1,1,1,1").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 0));
+
+        let err = read_bars("date,open,high,low,close,volume\n2023-07-10,1,1,1,1,1\n2023-07-11,1,x,1,1,1").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 3));
+        assert_eq!(err.to_string(), "line 3, column 3: parse high: invalid float literal");
+
+        let err = read_bars("date,open,high,low,close,volume\n2023-07-10,1,1").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 4));
+
+        let err = read_records("date,open,high,low,close,volume\n2023-07-10,1,1,1,1,1").unwrap_err();
+        assert_eq!(err.message, "missing column symbol");
+
+        let err = read_bars("date,datetime,open,high,low,close,volume\n2023-07-10,09:31,1,1,1,1,1").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 2));
+        assert_eq!(err.message, "duplicate column date");
+        let records = read_records("code,name,date,open,high,low,close,volume\n601888,x,2023-07-10,1,1,1,1,1");
+        assert_eq!(records.unwrap()[0].0, "601888");
+    }
+
+    #[test]
+    fn date_and_time() {
+        let content = "time,date,open,high,low,close,volume\n09:31,2023-07-10,1,1,1,1,1\n,2023-07-11,1,1,1,1,1";
+        let bars = read_bars(content).unwrap();
+        assert_eq!(bars[0].date, "2023-07-10 09:31");
+        assert_eq!(bars[1].date, "2023-07-11");
+
+        let content = "时间,开盘,最高,最低,收盘,成交量\n2023-07-10 09:31,1,1,1,1,1";
+        assert_eq!(read_bars(content).unwrap()[0].date, "2023-07-10 09:31");
+    }
+
+    #[test]
+    fn name_as_symbol() {
+        let content = "name,date,open,high,low,close,volume\n601888,2023-07-10,10.0,10.5,9.8,10.2,1000";
+        let records = read_records(content).unwrap();
+        assert_eq!(records[0].0, "601888");
+        assert_eq!(records[0].1.close, 10.2);
+
+        let err = read_records("date,name,open,high,low,close,volume\n2023-07-10,601888,1,1,1,1,1").unwrap_err();
+        assert_eq!(err.message, "missing column symbol");
+    }
+
+    #[test]
+    fn table() {
+        let content = "\nname\tcode\tvalue\textra\n a \t600000\t1.5\tx\n\nb\t600001\t\n";
+        let table = Table::parse(content, &["code", "name", "value", "missing"], 2).unwrap();
+        let rows: Vec<_> = table.rows().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].line(), rows[0].text(0), rows[0].text(1)), (3, "600000", "a"));
+        assert_eq!(rows[0].number(2).unwrap(), Some(1.5));
+        assert_eq!(rows[1].number(2).unwrap(), None);
+        assert_eq!(rows[1].text(3), "");
+
+        let err = rows[1].required(2).unwrap_err();
+        assert_eq!((err.line, err.column, err.message.as_str()), (5, 3, "missing field value"));
+        assert_eq!(rows[1].required_text(3).unwrap_err().column, 0);
+
+        let err = Table::parse("code,value\n600000,x", &["code", "value"], 1)
+            .unwrap()
+            .rows()
+            .next()
+            .unwrap()
+            .number(1)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "line 2, column 2: parse value: invalid float literal");
+
+        let err = Table::parse("code,value", &["code", "name"], 2).unwrap_err();
+        assert_eq!((err.line, err.column, err.message.as_str()), (1, 0, "missing column name"));
+        assert_eq!(Table::parse("", &["code"], 1).unwrap().rows().count(), 0);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bar = Bar::random("2023-07-10", 10.0, 20.0);
+        bar.volume = 1234.0;
+        bar.amount = 5678.5;
+
+        let writer = Writer::new(&[
+            Column::Date,
+            Column::Open,
+            Column::High,
+            Column::Low,
+            Column::Close,
+            Column::Volume,
+            Column::Amount,
+        ]);
+        let content = writer.write_bars([&bar]);
+        assert!(content.starts_with("date,open,high,low,close,volume,amount\n"));
+        let bars = read_bars(&content).unwrap();
+        assert_eq!(bars[0].date, bar.date);
+        assert_eq!((bars[0].open, bars[0].close, bars[0].amount), (bar.open, bar.close, bar.amount));
+
+        let content = Writer::new(RECORD_COLUMNS).write_records([("601888", &bar)]);
+        let records = read_records(&content).unwrap();
+        assert_eq!(records[0].0, "601888");
+        assert_eq!(records[0].1.high, bar.high);
+    }
+}
@@ -174,7 +174,7 @@ pub mod holidays {
     }
 }
 
-#[derive(Debug, Clone, Default, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Default, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum Period {
     Minute(usize),
     #[default]
@@ -0,0 +1,172 @@
+//! 加载器错误
+//!
+//! 所有加载器 trait 都返回 `DataError`，调用方可以按错误原因处理，例如休市时不重试、限流时等待。
+//! `DataError` 实现了 `std::error::Error`，可以直接用 `?` 转换为 `anyhow::Error`；
+//! 内部仍然使用 `anyhow` 的代码返回时，按错误链中的 io、csv 和 http 错误归类。
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::csv::CsvError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DataError {
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+    #[error("market closed")]
+    MarketClosed,
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("rate limited")]
+    RateLimited,
+    #[error("timeout")]
+    Timeout,
+    /// 行号和列号均从 1 开始，列号为 0 表示整行错误
+    #[error("parse {}line {line}, column {column}: {message}", FilePrefix(.file))]
+    Parse { file: Option<PathBuf>, line: usize, column: usize, message: String },
+    #[error("io: {0}")]
+    Io(#[source] std::io::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+struct FilePrefix<'a>(&'a Option<PathBuf>);
+
+impl Display for FilePrefix<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(file) => write!(f, "{}: ", file.display()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl DataError {
+    /// 为解析错误补充文件路径，其它错误保持不变
+    pub fn with_file(self, path: impl AsRef<Path>) -> Self {
+        match self {
+            DataError::Parse { file: None, line, column, message } => {
+                DataError::Parse { file: Some(path.as_ref().to_path_buf()), line, column, message }
+            }
+            err => err,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, DataError::NotFound(_))
+    }
+
+    /// 错误链中间的 `DataError` 无法取出所有权，`Io` 和 `Other` 以外的变体可以复制
+    fn try_clone(&self) -> Option<DataError> {
+        Some(match self {
+            DataError::NotFound(v) => DataError::NotFound(v.clone()),
+            DataError::Unsupported(v) => DataError::Unsupported(v.clone()),
+            DataError::MarketClosed => DataError::MarketClosed,
+            DataError::Unauthorized(v) => DataError::Unauthorized(v.clone()),
+            DataError::RateLimited => DataError::RateLimited,
+            DataError::Timeout => DataError::Timeout,
+            DataError::Parse { file, line, column, message } => {
+                DataError::Parse { file: file.clone(), line: *line, column: *column, message: message.clone() }
+            }
+            DataError::Io(_) | DataError::Other(_) => return None,
+        })
+    }
+}
+
+impl From<CsvError> for DataError {
+    fn from(err: CsvError) -> Self {
+        DataError::Parse { file: None, line: err.line, column: err.column, message: err.message }
+    }
+}
+
+impl From<std::io::Error> for DataError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => DataError::NotFound(err.to_string()),
+            std::io::ErrorKind::TimedOut => DataError::Timeout,
+            _ => DataError::Io(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for DataError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return DataError::Timeout;
+        }
+        DataError::Other(err.into())
+    }
+}
+
+impl From<tokio::task::JoinError> for DataError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        DataError::Other(err.into())
+    }
+}
+
+impl From<anyhow::Error> for DataError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<DataError>() {
+            Ok(err) => return err,
+            Err(err) => err,
+        };
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<DataError>().and_then(DataError::try_clone) {
+                return e;
+            }
+            if let Some(e) = cause.downcast_ref::<CsvError>() {
+                return e.clone().into();
+            }
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                if e.is_timeout() {
+                    return DataError::Timeout;
+                }
+            }
+            if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+                // 保留上下文信息
+                let message = format!("{:#}", err);
+                return match e.kind() {
+                    std::io::ErrorKind::NotFound => DataError::NotFound(message),
+                    std::io::ErrorKind::TimedOut => DataError::Timeout,
+                    kind => DataError::Io(std::io::Error::new(kind, message)),
+                };
+            }
+        }
+        DataError::Other(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn convert() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let err = DataError::from(anyhow::Error::new(io).context("read 601888.csv"));
+        assert!(matches!(&err, DataError::NotFound(message) if message.contains("601888.csv")));
+
+        let err: DataError = crate::csv::read_bars("date\topen").unwrap_err().into();
+        let err = err.with_file("601888.csv");
+        assert!(matches!(&err, DataError::Parse { line: 1, file: Some(_), .. }));
+        assert!(err.to_string().starts_with("parse 601888.csv: line 1"));
+
+        let err = DataError::from(anyhow::Error::new(DataError::MarketClosed));
+        assert!(matches!(err, DataError::MarketClosed));
+        let err = DataError::from(anyhow::Error::new(DataError::Unsupported("15m".into())).context("load chart"));
+        assert!(matches!(err, DataError::Unsupported(_)));
+        let err: anyhow::Result<()> = Err(DataError::RateLimited).context("load market");
+        assert!(matches!(
+            err.unwrap_err().downcast_ref::<DataError>(),
+            Some(DataError::RateLimited)
+        ));
+
+        let err = DataError::from(anyhow::anyhow!("unknown"));
+        assert!(matches!(&err, DataError::Other(_)));
+        assert_eq!(err.to_string(), "unknown");
+    }
+}
@@ -0,0 +1,218 @@
+//! 股本和估值数据
+//!
+//! 每条 [`Fundamental`] 从 `date` 开始生效，直到下一条记录为止，一般为股本变动日或财报公告日。
+//! 按K线日期对齐后计算市值、市盈率（TTM）、市净率和换手率，股本和成交量的单位需要一致，均为股。
+//!
+//! csv 文件的表头为 `date,total_shares,float_shares,eps_ttm,bps`，`eps_ttm` 和 `bps` 可以为空。
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::csv::{CsvError, Table};
+use crate::stock::GetSymbolCode;
+use crate::{deref, Bar, Chart, DataError, LocalLoader};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Fundamental {
+    pub date: String,
+    /// 总股本
+    pub total_shares: f64,
+    /// 流通股本
+    pub float_shares: f64,
+    /// 最近四个季度的每股收益
+    #[serde(default)]
+    pub eps_ttm: Option<f64>,
+    /// 每股净资产
+    #[serde(default)]
+    pub bps: Option<f64>,
+}
+
+/// 某根K线的估值
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Valuation {
+    pub date: String,
+    pub total_shares: f64,
+    pub float_shares: f64,
+    /// 总市值 = 收盘价 × 总股本
+    pub market_cap: f64,
+    /// 流通市值 = 收盘价 × 流通股本
+    pub float_market_cap: f64,
+    /// 每股收益为 0 或未知时为空，亏损时为负数
+    pub pe_ttm: Option<f64>,
+    /// 每股净资产不大于 0 或未知时为空
+    pub pb: Option<f64>,
+    /// 换手率（%）= 成交量 / 流通股本 × 100
+    pub turnover: f64,
+}
+
+impl Valuation {
+    pub fn new(bar: &Bar, fundamental: &Fundamental) -> Self {
+        let ratio = |v: Option<f64>, positive: bool| {
+            v.filter(|v| if positive { *v > 0.0 } else { *v != 0.0 }).map(|v| bar.close / v)
+        };
+        Self {
+            date: bar.date.clone(),
+            total_shares: fundamental.total_shares,
+            float_shares: fundamental.float_shares,
+            market_cap: bar.close * fundamental.total_shares,
+            float_market_cap: bar.close * fundamental.float_shares,
+            pe_ttm: ratio(fundamental.eps_ttm, false),
+            pb: ratio(fundamental.bps, true),
+            turnover: if fundamental.float_shares > 0.0 {
+                bar.volume / fundamental.float_shares * 100.0
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+deref! {
+    /// 按日期升序
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct Fundamentals(Vec<Fundamental>);
+}
+
+const COLUMNS: [&str; 5] = ["date", "total_shares", "float_shares", "eps_ttm", "bps"];
+
+impl Fundamentals {
+    /// `date` 当天生效的记录，日期可以包含时间
+    pub fn at(&self, date: &str) -> Option<&Fundamental> {
+        let day = date.get(..10).unwrap_or(date);
+        let index = self.0.partition_point(|v| v.date.as_str() <= day);
+        index.checked_sub(1).map(|i| &self.0[i])
+    }
+
+    pub fn valuation(&self, bar: &Bar) -> Option<Valuation> {
+        Some(Valuation::new(bar, self.at(&bar.date)?))
+    }
+
+    /// 与K线一一对应，早于第一条记录的K线为空
+    pub fn align(&self, chart: &Chart) -> Vec<Option<Valuation>> {
+        chart.iter().map(|bar| self.valuation(bar)).collect()
+    }
+
+    pub fn parse_csv(content: &str) -> Result<Self, CsvError> {
+        let mut items = vec![];
+        for row in Table::parse(content, &COLUMNS, 3)?.rows() {
+            items.push(Fundamental {
+                date: row.text(0).to_string(),
+                total_shares: row.required(1)?,
+                float_shares: row.required(2)?,
+                eps_ttm: row.number(3)?,
+                bps: row.number(4)?,
+            });
+        }
+        items.sort_by(|a, b| a.date.cmp(&b.date));
+        Ok(Self(items))
+    }
+
+    pub fn to_csv(&self) -> String {
+        let optional = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+        self.0.iter().fold(COLUMNS.join(","), |mut acc, v| {
+            acc.push_str(&format!(
+                "\n{},{},{},{},{}",
+                v.date,
+                v.total_shares,
+                v.float_shares,
+                optional(v.eps_ttm),
+                optional(v.bps)
+            ));
+            acc
+        })
+    }
+}
+
+#[async_trait::async_trait]
+pub trait FundamentalsLoader {
+    async fn fundamentals(&self, symbol: impl GetSymbolCode + Send) -> Result<Fundamentals, DataError>;
+}
+
+#[async_trait::async_trait]
+impl<T: FundamentalsLoader + Sync> FundamentalsLoader for &T {
+    async fn fundamentals(&self, symbol: impl GetSymbolCode + Send) -> Result<Fundamentals, DataError> {
+        (*self).fundamentals(symbol).await
+    }
+}
+
+impl LocalLoader {
+    /// `fundamentals/{s1}/{s2}/{symbol}.csv`，同名的 `.json` 文件为 [`Fundamental`] 数组
+    pub fn fundamentals_path(&self, symbol: impl GetSymbolCode) -> anyhow::Result<PathBuf> {
+        let (s1, s2, symbol) = crate::loader::local::symbol_dirs(symbol.symbol())?;
+        self.storage(format!("fundamentals/{}/{}/{}.csv", s1, s2, symbol))
+    }
+}
+
+#[async_trait::async_trait]
+impl FundamentalsLoader for LocalLoader {
+    async fn fundamentals(&self, symbol: impl GetSymbolCode + Send) -> Result<Fundamentals, DataError> {
+        let path = self.fundamentals_path(&symbol)?;
+        if path.exists() {
+            let content = tokio::fs::read_to_string(&path).await?;
+            return Fundamentals::parse_csv(&content).map_err(|e| DataError::from(e).with_file(&path));
+        }
+        let path = path.with_extension("json");
+        if path.exists() {
+            let content = tokio::fs::read_to_string(&path).await?;
+            let mut items: Vec<Fundamental> = serde_json::from_str(&content).map_err(|e| DataError::Parse {
+                file: Some(path.clone()),
+                line: e.line(),
+                column: e.column(),
+                message: e.to_string(),
+            })?;
+            items.sort_by(|a, b| a.date.cmp(&b.date));
+            return Ok(Fundamentals::new(items));
+        }
+        Err(DataError::NotFound(format!("fundamentals of {}", symbol.symbol())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[tokio::test]
+    async fn align_chart() {
+        let dir = TempDir::new("fundamentals");
+        let loader = LocalLoader::new(&dir).unwrap();
+        assert!(loader.fundamentals("600000").await.unwrap_err().is_not_found());
+
+        let path = loader.fundamentals_path("600000").unwrap();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            "date,total_shares,float_shares,eps_ttm,bps\n\
+             2023-07-11,2000,1000,0.5,\n\
+             2023-04-28,2000,800,-0.2,4",
+        )
+        .unwrap();
+        let fundamentals = loader.fundamentals("600000").await.unwrap();
+        assert_eq!(fundamentals[0].date, "2023-04-28");
+        assert_eq!(Fundamentals::parse_csv(&fundamentals.to_csv()).unwrap(), fundamentals);
+
+        let mut bars = vec![Bar::new("2023-04-27"), Bar::new("2023-07-10"), Bar::new("2023-07-11")];
+        for bar in bars.iter_mut() {
+            (bar.close, bar.volume) = (10.0, 40.0);
+        }
+        let valuations = fundamentals.align(&Chart::new(bars));
+        assert_eq!(valuations.len(), 3);
+        assert!(valuations[0].is_none());
+        let v = valuations[1].as_ref().unwrap();
+        assert_eq!((v.market_cap, v.float_market_cap, v.turnover), (20000.0, 8000.0, 5.0));
+        assert_eq!((v.pe_ttm, v.pb), (Some(-50.0), Some(2.5)));
+        let v = valuations[2].as_ref().unwrap();
+        assert_eq!((v.pe_ttm, v.pb, v.turnover), (Some(20.0), None, 4.0));
+
+        std::fs::write(&path, "date,total_shares,float_shares\n2023-07-11,abc,1").unwrap();
+        let err = loader.fundamentals("600000").await.unwrap_err();
+        assert!(matches!(err, DataError::Parse { line: 2, column: 2, .. }), "{}", err);
+
+        std::fs::remove_file(&path).unwrap();
+        let json = serde_json::json!([{"date": "2023-07-11", "total_shares": 10.0, "float_shares": 5.0}]);
+        std::fs::write(path.with_extension("json"), json.to_string()).unwrap();
+        let fundamentals = loader.fundamentals("600000").await.unwrap();
+        assert_eq!(fundamentals.at("2023-07-12 10:00").unwrap().eps_ttm, None);
+    }
+}
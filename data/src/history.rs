@@ -0,0 +1,330 @@
+//! 股票列表历史
+//!
+//! `stocks.csv` 只是某一天的快照，直接用来选股会漏掉已经退市的股票，也可能选到当时还没有上市的股票。
+//! `StockHistory` 按日期记录每次快照的变化：上市、退市、改名和 ST 状态，可以还原任意一天的股票列表。
+//! 第一次快照之前的上市日期未知，视为一直在上市。退市后重新上市的股票保留每一段上市区间，退市期间不在列表中。
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::loader::writer::FileLock;
+use crate::{LocalLoader, Stock, Stocks};
+
+/// 名称中包含 `ST` 的股票，包括 `*ST` 和 `S*ST`
+pub fn is_st(name: &str) -> bool {
+    name.to_uppercase().contains("ST")
+}
+
+/// 从 `date` 开始使用的名称
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NameChange {
+    pub date: String,
+    pub name: String,
+    pub st: bool,
+}
+
+/// 一段上市区间 `listed..delisted`
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Listing {
+    /// 为空表示在第一次快照之前上市
+    pub listed: Option<String>,
+    /// 退市后第一次没有出现在快照中的日期，为空表示仍在上市
+    pub delisted: Option<String>,
+}
+
+impl Listing {
+    pub fn contains(&self, date: &str) -> bool {
+        self.listed.as_deref().is_none_or(|v| v <= date) && self.delisted.as_deref().is_none_or(|v| date < v)
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StockRecord {
+    pub symbol: String,
+    /// 按日期升序，重新上市时增加一段
+    pub listings: Vec<Listing>,
+    /// 按日期升序
+    pub names: Vec<NameChange>,
+}
+
+impl StockRecord {
+    pub fn is_listed(&self, date: &str) -> bool {
+        self.listings.iter().any(|v| v.contains(date))
+    }
+
+    /// 第一次上市的日期，为空表示在第一次快照之前上市
+    pub fn listed(&self) -> Option<&str> {
+        self.listings.first().and_then(|v| v.listed.as_deref())
+    }
+
+    /// 最后一次退市的日期，仍在上市时为空
+    pub fn delisted(&self) -> Option<&str> {
+        self.listings.last().and_then(|v| v.delisted.as_deref())
+    }
+
+    /// 当天使用的名称，早于第一条记录时返回第一个名称
+    pub fn name_at(&self, date: &str) -> Option<&NameChange> {
+        self.names.iter().rev().find(|v| v.date.as_str() <= date).or(self.names.first())
+    }
+
+    pub fn is_st(&self, date: &str) -> bool {
+        self.name_at(date).map(|v| v.st).unwrap_or_default()
+    }
+
+    fn rename(&mut self, date: &str, name: &str) {
+        if self.names.last().map(|v| v.name != name).unwrap_or(true) {
+            let change = NameChange { date: date.to_string(), name: name.to_string(), st: is_st(name) };
+            self.names.push(change);
+        }
+    }
+}
+
+/// 两次快照之间的变化
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SnapshotDiff {
+    pub listed: Vec<Stock>,
+    pub delisted: Vec<Stock>,
+    /// 改名前后的股票
+    pub renamed: Vec<(Stock, Stock)>,
+}
+
+impl SnapshotDiff {
+    pub fn between(old: &Stocks, new: &Stocks) -> Self {
+        let old: BTreeMap<_, _> = old.iter().map(|v| (v.symbol.as_str(), v)).collect();
+        let new: BTreeMap<_, _> = new.iter().map(|v| (v.symbol.as_str(), v)).collect();
+        let mut diff = SnapshotDiff::default();
+        for (symbol, stock) in &new {
+            match old.get(symbol) {
+                None => diff.listed.push((*stock).clone()),
+                Some(before) if before.name != stock.name => diff.renamed.push(((*before).clone(), (*stock).clone())),
+                _ => {}
+            }
+        }
+        diff.delisted = old
+            .iter()
+            .filter(|(k, _)| !new.contains_key(*k))
+            .map(|(_, v)| (*v).clone())
+            .collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listed.is_empty() && self.delisted.is_empty() && self.renamed.is_empty()
+    }
+
+    /// 改名前后 ST 状态发生变化的股票
+    pub fn st_changed(&self) -> impl Iterator<Item = &(Stock, Stock)> {
+        self.renamed.iter().filter(|(a, b)| is_st(&a.name) != is_st(&b.name))
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StockHistory {
+    /// 最后一次快照的日期
+    updated: Option<String>,
+    records: BTreeMap<String, StockRecord>,
+}
+
+impl StockHistory {
+    pub fn updated(&self) -> Option<&str> {
+        self.updated.as_deref()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&StockRecord> {
+        self.records.get(symbol)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &StockRecord> {
+        self.records.values()
+    }
+
+    /// 当天在上市的股票，名称为当天使用的名称
+    pub fn as_of(&self, date: &str) -> Stocks {
+        let stocks = self
+            .records
+            .values()
+            .filter(|v| v.is_listed(date))
+            .filter_map(|v| Some(Stock::new(v.name_at(date)?.name.as_str(), v.symbol.as_str())))
+            .collect();
+        Stocks::new(stocks)
+    }
+
+    /// 记录 `date` 的快照，快照必须按日期顺序记录，返回与上一次快照的差异
+    pub fn record(&mut self, date: &str, stocks: &Stocks) -> anyhow::Result<SnapshotDiff> {
+        if let Some(updated) = &self.updated {
+            if date < updated.as_str() {
+                bail!("snapshot {} is older than last snapshot {}", date, updated);
+            }
+        }
+        let diff = match &self.updated {
+            Some(updated) => SnapshotDiff::between(&self.as_of(updated), stocks),
+            None => SnapshotDiff { listed: stocks.to_vec(), ..Default::default() },
+        };
+        let first = self.updated.is_none();
+
+        for stock in &diff.listed {
+            let record = self
+                .records
+                .entry(stock.symbol.clone())
+                .or_insert_with(|| StockRecord { symbol: stock.symbol.clone(), ..Default::default() });
+            let listed = (!first).then(|| date.to_string());
+            record.listings.push(Listing { listed, delisted: None });
+            record.rename(date, &stock.name);
+        }
+        for (_, stock) in &diff.renamed {
+            if let Some(record) = self.records.get_mut(&stock.symbol) {
+                record.rename(date, &stock.name);
+            }
+        }
+        for stock in &diff.delisted {
+            if let Some(listing) = self.records.get_mut(&stock.symbol).and_then(|v| v.listings.last_mut()) {
+                listing.delisted = Some(date.to_string());
+            }
+        }
+        self.updated = Some(date.to_string());
+        Ok(diff)
+    }
+
+    /// 已知准确的上市日期时覆盖快照推断的第一次上市日期
+    pub fn set_listed(&mut self, symbol: &str, date: &str) {
+        if let Some(record) = self.records.get_mut(symbol) {
+            if let Some(listing) = record.listings.first_mut() {
+                listing.listed = Some(date.to_string());
+            }
+            if let Some(first) = record.names.first_mut() {
+                if date < first.date.as_str() {
+                    first.date = date.to_string();
+                }
+            }
+        }
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path).context(format!("read {}", path.display()))?;
+        serde_json::from_str(&content).context(format!("parse {}", path.display()))
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        crate::loader::writer::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl Stocks {
+    /// 当天在上市的股票，用于回测和训练时避免幸存者偏差
+    pub fn as_of(history: &StockHistory, date: &str) -> Stocks {
+        history.as_of(date)
+    }
+}
+
+impl LocalLoader {
+    pub fn stock_history_path(&self) -> anyhow::Result<PathBuf> {
+        self.storage("stocks_history.json")
+    }
+
+    pub fn stock_history(&self) -> anyhow::Result<StockHistory> {
+        StockHistory::read(self.stock_history_path()?)
+    }
+
+    /// 保存 `date` 的股票列表快照，同时更新 `stocks.csv` 和历史记录
+    pub fn update_stocks(&self, date: &str, stocks: &Stocks) -> anyhow::Result<SnapshotDiff> {
+        crate::loader::layout::init(self.base_dir())?;
+        let path = self.stock_history_path()?;
+        let _lock = FileLock::exclusive(&path)?;
+        let mut history = StockHistory::read(&path)?;
+        let diff = history.record(date, stocks)?;
+        crate::loader::writer::replace(&path, serde_json::to_string_pretty(&history)?)?;
+        self.save_stocks(stocks)?;
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn stocks(items: &[(&str, &str)]) -> Stocks {
+        Stocks::new(items.iter().map(|(symbol, name)| Stock::new(*name, *symbol)).collect())
+    }
+
+    fn symbols(history: &StockHistory, date: &str) -> Vec<String> {
+        history.as_of(date).iter().map(|v| v.symbol.clone()).collect()
+    }
+
+    #[test]
+    fn snapshot_history() {
+        let mut history = StockHistory::default();
+        history
+            .record("2023-01-03", &stocks(&[("600000", "浦发银行"), ("600001", "邯郸钢铁")]))
+            .unwrap();
+        let diff = history
+            .record(
+                "2023-03-01",
+                &stocks(&[("600000", "浦发银行"), ("600001", "*ST邯钢"), ("600002", "齐鲁石化")]),
+            )
+            .unwrap();
+        assert_eq!(diff.listed, vec![Stock::new("齐鲁石化", "600002")]);
+        assert_eq!(diff.st_changed().count(), 1);
+        let diff = history
+            .record("2023-06-01", &stocks(&[("600000", "浦发银行"), ("600002", "齐鲁石化")]))
+            .unwrap();
+        assert_eq!(diff.delisted, vec![Stock::new("*ST邯钢", "600001")]);
+        assert!(history.record("2023-05-01", &Stocks::new(vec![])).is_err());
+
+        assert_eq!(symbols(&history, "2022-12-01"), ["600000", "600001"]);
+        assert_eq!(symbols(&history, "2023-03-15"), ["600000", "600001", "600002"]);
+        assert_eq!(symbols(&history, "2023-07-01"), ["600000", "600002"]);
+
+        let record = history.get("600001").unwrap();
+        assert_eq!(record.name_at("2023-02-01").unwrap().name, "邯郸钢铁");
+        assert!(!record.is_st("2023-02-01"));
+        assert!(record.is_st("2023-04-01"));
+        assert_eq!(history.as_of("2023-04-01")[1].name, "*ST邯钢");
+        let listed = Stocks::as_of(&history, "2023-07-01");
+        assert_eq!(
+            listed.iter().map(|v| v.symbol.as_str()).collect::<Vec<_>>(),
+            ["600000", "600002"]
+        );
+
+        history.set_listed("600002", "2023-02-20");
+        assert_eq!(symbols(&history, "2023-02-21"), ["600000", "600001", "600002"]);
+        let diff = SnapshotDiff::between(&history.as_of("2023-06-01"), &history.as_of("2023-06-01"));
+        assert!(diff.is_empty());
+
+        history
+            .record(
+                "2023-09-01",
+                &stocks(&[("600000", "浦发银行"), ("600001", "邯郸钢铁"), ("600002", "齐鲁石化")]),
+            )
+            .unwrap();
+        let record = history.get("600001").unwrap();
+        assert_eq!(record.listings.len(), 2);
+        assert_eq!((record.listed(), record.delisted()), (None, None));
+        assert!(record.is_listed("2023-05-31") && record.is_listed("2023-09-01"));
+        assert!(!record.is_listed("2023-06-01") && !record.is_listed("2023-08-31"));
+        assert_eq!(symbols(&history, "2023-07-01"), ["600000", "600002"]);
+    }
+
+    #[test]
+    fn update_stocks() {
+        let dir = TempDir::new("history");
+        let loader = LocalLoader::new(&dir).unwrap();
+        loader.update_stocks("2023-01-03", &stocks(&[("600000", "浦发银行")])).unwrap();
+        let diff = loader.update_stocks("2023-01-04", &stocks(&[("600001", "邯郸钢铁")])).unwrap();
+        assert_eq!((diff.listed.len(), diff.delisted.len()), (1, 1));
+
+        let history = loader.stock_history().unwrap();
+        assert_eq!(history.updated(), Some("2023-01-04"));
+        assert_eq!(history.as_of("2023-01-03").len(), 1);
+        assert_eq!(history.as_of("2023-01-03")[0].symbol, "600000");
+        let content = std::fs::read_to_string(loader.stocks_path().unwrap()).unwrap();
+        assert_eq!(content, "股票代码\t股票名称\n600001\t邯郸钢铁");
+    }
+}
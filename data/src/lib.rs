@@ -1,12 +1,29 @@
 pub use calculate::*;
 pub use chart::*;
+pub use config::{Config, Profile};
 pub use days::{holidays::*, *};
-pub use loader::{local::*, remote::*};
+pub use error::*;
+pub use fundamentals::*;
+pub use history::*;
+pub use loader::{command::CommandCredentialProvider, keystore::EncryptedCredentialProvider, local::*, remote::*};
+pub use quote::*;
+pub use sector::*;
+pub use source::*;
 pub use stock::*;
 
 mod calculate;
 mod chart;
+pub mod config;
+pub mod csv;
 mod days;
+mod error;
+mod fundamentals;
+mod history;
 pub mod loader;
 mod macros;
+pub mod quote;
+mod sector;
+mod source;
 mod stock;
+#[cfg(test)]
+pub(crate) mod testing;
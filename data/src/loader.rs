@@ -4,18 +4,18 @@
 pub mod local {
     use std::collections::HashMap;
     use std::fmt::Write;
-    use std::ops::Add;
+    use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
     use std::path::{Path, PathBuf};
     use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use std::time::SystemTime;
 
-    use anyhow::{bail, Context};
-    use tracing::log::debug;
-    use tracing::trace;
+    use anyhow::Context;
 
     use crate::stock::GetSymbolCode;
     use crate::{
-        Bar, BarLoader, Chart, ChartLoader, ChartParamter, MarketCurrentLoader, Period, Stock, Stocks, StocksLoader,
-        TradingDay,
+        Bar, BarLoader, Chart, ChartLoader, ChartParamter, DataError, MarketCurrentLoader, Period, Stock, Stocks,
+        StocksLoader, TradingDay,
     };
 
     pub fn data_dir() -> anyhow::Result<PathBuf> {
@@ -47,18 +47,60 @@ pub mod local {
         Ok(Stocks::new(stocks).sorted())
     }
 
+    /// 与 [`parse_stocks_data`] 的格式一致，以制表符分隔，原子写入
     pub fn write_stocks_data<P: AsRef<Path>>(path: P, stocks: &Stocks) -> anyhow::Result<()> {
-        let mut content = String::from("股票代码,股票名称");
+        let mut content = String::from("股票代码\t股票名称");
         for stock in stocks.iter() {
-            content.write_str(format!("\n{},{}", stock.symbol, stock.name).as_str())?;
+            content.write_str(format!("\n{}\t{}", stock.symbol, stock.name).as_str())?;
         }
-        std::fs::write(path, content).context("write file")?;
-        Ok(())
+        super::writer::write(path, content)
+    }
+
+    /// 日线按周合并，日期为每周的第一天
+    pub(crate) fn merge_week(bars: Vec<Bar>) -> Vec<Bar> {
+        bars.into_iter()
+            .map(|mut v| {
+                v.date = TradingDay::from_str(&v.date).unwrap().week_start_day().to_string();
+                v
+            })
+            .fold(Vec::default(), |mut acc: Vec<Bar>, bar| {
+                match acc.last_mut() {
+                    Some(last) if last.date.eq(&bar.date) => {
+                        last.merge(bar);
+                    }
+                    _ => acc.push(bar),
+                }
+                acc
+            })
+    }
+
+    /// K线存储格式
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+    pub enum StorageFormat {
+        /// 每只股票一个日线 csv，分钟线每天一个 csv
+        #[default]
+        Csv,
+        /// 每只股票每个周期一个二进制文件，见 [`super::binary`]
+        Binary,
+    }
+
+    /// 读取 csv 最新K线时从文件末尾读取的字节数
+    const TAIL_SIZE: u64 = 8 * 1024;
+
+    /// 快照索引，文件修改时间或截止日期变化后失效
+    #[derive(Debug, Clone)]
+    struct IndexEntry {
+        modified: SystemTime,
+        as_of: Option<String>,
+        bar: Option<Bar>,
     }
 
     #[derive(Debug, Clone)]
     pub struct LocalLoader {
         base_dir: PathBuf,
+        format: StorageFormat,
+        as_of: Option<String>,
+        index: Arc<Mutex<HashMap<PathBuf, IndexEntry>>>,
     }
 
     impl LocalLoader {
@@ -66,8 +108,55 @@ pub mod local {
             data_dir().and_then(|path| LocalLoader::new(path))
         }
 
+        /// 目录版本比当前支持的版本新时报错，见 [`super::layout`]
         pub fn new(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
-            Ok(Self { base_dir: path.into() })
+            let base_dir = path.into();
+            super::layout::check(&base_dir)?;
+            Ok(Self {
+                base_dir,
+                format: StorageFormat::default(),
+                as_of: None,
+                index: Default::default(),
+            })
+        }
+
+        pub fn with_format(mut self, format: StorageFormat) -> Self {
+            self.format = format;
+            self
+        }
+
+        pub fn format(&self) -> StorageFormat {
+            self.format
+        }
+
+        /// `market` 和 `current` 返回不晚于该日期的最后一根K线，默认为最新数据
+        pub fn with_as_of(mut self, date: impl ToString) -> Self {
+            self.as_of = Some(date.to_string());
+            self
+        }
+
+        pub fn as_of(&self) -> Option<&str> {
+            self.as_of.as_deref()
+        }
+
+        /// 去掉截止日期，读取最新的数据
+        pub(crate) fn latest(&self) -> Self {
+            Self { as_of: None, ..self.clone() }
+        }
+
+        pub fn base_dir(&self) -> &Path {
+            &self.base_dir
+        }
+
+        pub fn layout_version(&self) -> anyhow::Result<u32> {
+            super::layout::version(&self.base_dir)
+        }
+
+        pub fn migrate(
+            &self,
+            options: super::layout::MigrateOptions,
+        ) -> anyhow::Result<super::layout::MigrationReport> {
+            super::layout::migrate(&self.base_dir, options)
         }
 
         pub fn test(&self) -> anyhow::Result<()> {
@@ -77,66 +166,69 @@ pub mod local {
         }
 
         pub fn day_chart_path(&self, symbol: impl GetSymbolCode) -> anyhow::Result<PathBuf> {
-            let symbol = symbol.symbol();
-            let (s1, s2) = (&symbol[..2], &symbol[2..4]);
+            let (s1, s2, symbol) = symbol_dirs(symbol.symbol())?;
             self.storage(format!("stocks/day/{}/{}/{}.csv", s1, s2, symbol))
         }
 
         pub fn minutes_chart_dir(&self, symbol: impl GetSymbolCode) -> anyhow::Result<PathBuf> {
-            let symbol = symbol.symbol();
-            let (s1, s2) = (&symbol[..2], &symbol[2..4]);
+            let (s1, s2, symbol) = symbol_dirs(symbol.symbol())?;
             self.storage(format!("stocks/minutes/{}/{}/{}", s1, s2, symbol))
         }
 
+        pub fn day_binary_path(&self, symbol: impl GetSymbolCode) -> anyhow::Result<PathBuf> {
+            Ok(self.day_chart_path(symbol)?.with_extension("bin"))
+        }
+
+        pub fn minutes_binary_path(&self, symbol: impl GetSymbolCode) -> anyhow::Result<PathBuf> {
+            Ok(self.minutes_chart_dir(symbol)?.with_extension("bin"))
+        }
+
         pub fn stocks_path(&self) -> anyhow::Result<PathBuf> {
             self.storage("stocks.csv")
         }
 
+        /// 行情快照索引，保存每只股票最新的日线，由写入方维护，见 [`LocalLoader::rebuild_index`]
+        pub fn index_path(&self) -> anyhow::Result<PathBuf> {
+            let extension = match self.format {
+                StorageFormat::Csv => "csv",
+                StorageFormat::Binary => "bin",
+            };
+            self.storage(format!("stocks/day/snapshot.{}.json", extension))
+        }
+
         pub fn storage(&self, append: impl AsRef<str>) -> anyhow::Result<PathBuf> {
             Ok(self.base_dir.join(append.as_ref()))
         }
     }
 
+    /// 按代码前四位分成两级目录，代码只能包含字母和数字，避免路径越过数据目录
+    pub(crate) fn symbol_dirs(symbol: &str) -> anyhow::Result<(&str, &str, &str)> {
+        if symbol.len() < 4 || !symbol.chars().all(|v| v.is_ascii_alphanumeric()) {
+            anyhow::bail!("invalid symbol {:?}", symbol);
+        }
+        Ok((&symbol[..2], &symbol[2..4], symbol))
+    }
+
     #[async_trait::async_trait]
     impl StocksLoader for LocalLoader {
-        async fn stocks(&self) -> anyhow::Result<Stocks> {
+        async fn stocks(&self) -> Result<Stocks, DataError> {
             let path = self.stocks_path()?;
             let content = tokio::fs::read_to_string(path).await.context("Failed to read stock file")?;
-            parse_stocks_data(content)
+            Ok(parse_stocks_data(content)?)
         }
     }
 
     impl LocalLoader {
-        fn parse_chart(&self, content: String) -> anyhow::Result<Chart> {
-            let lines = content.lines().skip(1);
+        pub(crate) fn parse_chart(&self, content: String) -> anyhow::Result<Chart> {
             let mut chart = Chart::default();
             let mut yesterday = 0.0;
-            for line in lines.into_iter() {
-                if line.is_empty() {
-                    continue;
-                }
-                let mut fields = line.split(',');
-
-                let date = fields.next().context("not found date")?;
-                let mut bar = Bar::new(date);
-
-                bar.open = fields.next().context("not found open")?.parse::<f64>().context("parse open")?;
-                bar.high = fields.next().context("not found high")?.parse::<f64>().context("parse high")?;
-                bar.low = fields.next().context("not found low")?.parse::<f64>().context("parse low")?;
-                bar.close = fields
-                    .next()
-                    .context("not found close")?
-                    .parse::<f64>()
-                    .context("parse close")?;
-                bar.volume = fields
-                    .next()
-                    .context("not found volume")?
-                    .parse::<f64>()
-                    .context("parse volume")?;
+            for mut bar in crate::csv::read_bars(&content)? {
                 if !bar.is_ok() {
                     continue;
                 }
-                bar.yesterday = yesterday;
+                if bar.yesterday == 0.0 {
+                    bar.yesterday = yesterday;
+                }
                 yesterday = bar.close;
                 chart.push(bar);
             }
@@ -144,13 +236,22 @@ pub mod local {
         }
 
         async fn day_chart(&self, param: ChartParamter) -> anyhow::Result<Chart> {
+            if self.format == StorageFormat::Binary {
+                let path = self.day_binary_path(&param.symbol)?;
+                let err = format!("[{}] read stock chart file: {}", param.symbol, path.display());
+                return tokio::task::spawn_blocking(move || {
+                    super::binary::load(path, None, param.end.as_deref(), param.limit).context(err)
+                })
+                .await?;
+            }
+
             let path = self.day_chart_path(&param.symbol)?;
             let length = param.limit.unwrap_or(usize::MAX);
 
             let err = format!("[{}] read stock chart file: {}", param.symbol, path.display());
             let content = tokio::fs::read_to_string(&path).await.context(err)?;
 
-            let mut chart = self.parse_chart(content)?;
+            let mut chart = self.parse_chart(content).map_err(|e| DataError::from(e).with_file(&path))?;
 
             chart.length(length);
 
@@ -163,22 +264,7 @@ pub mod local {
             }
             let limit = std::mem::replace(&mut param.limit, None);
 
-            let output = self.day_chart(param).await?.value();
-            let output = output
-                .into_iter()
-                .map(|mut v| {
-                    v.date = TradingDay::from_str(&v.date).unwrap().week_start_day().to_string();
-                    v
-                })
-                .fold(Vec::default(), |mut acc: Vec<Bar>, bar| {
-                    match acc.last_mut() {
-                        Some(last) if last.date.eq(&bar.date) => {
-                            last.merge(bar);
-                        }
-                        _ => acc.push(bar),
-                    }
-                    acc
-                });
+            let output = merge_week(self.day_chart(param).await?.value());
             let mut chart = Chart::new(output);
             if let Some(limit) = limit {
                 chart.length(limit);
@@ -186,75 +272,167 @@ pub mod local {
             Ok(chart)
         }
 
+        /// 缺失和无法读取的交易日只记录警告，完整的结果见 [`LocalLoader::minutes`]
         async fn minutes_chart(&self, param: ChartParamter) -> anyhow::Result<Chart> {
-            let path = self.minutes_chart_dir(&param.symbol)?;
-            if !path.exists() {
-                return Ok(Chart::default());
+            let loader = self.clone();
+            let symbol = param.symbol.clone();
+            let output = tokio::task::spawn_blocking(move || loader.minutes(&param)).await??;
+            if !output.report.is_complete() {
+                tracing::warn!("[{}] {}", symbol, output.report);
             }
+            Ok(output.chart)
+        }
+    }
 
-            let Period::Minute(minutes) = param.period.clone() else {
-                unreachable!("!!");
+    #[async_trait::async_trait]
+    impl ChartLoader for LocalLoader {
+        async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError> {
+            let param = param.into();
+            let chart = match &param.period {
+                Period::Day => self.day_chart(param).await?,
+                Period::Week => self.week_chart(param).await?,
+                Period::Minute(_) => self.minutes_chart(param).await?,
             };
+            Ok(chart)
+        }
+    }
 
-            let end = TradingDay::trading(param.end.clone())?;
-            let limit = param.limit.unwrap_or((60 / minutes) * 4 * 5);
-            let limit = (limit as f64 / ((60 / minutes) * 4) as f64).ceil() as usize;
-            let mut start = end.clone() - (limit - 1);
-            debug!("start: {}, end: {}", start, end);
+    /// csv 文件的表头和末尾的若干行，足够解析出最后两根K线
+    fn read_tail(path: &Path) -> anyhow::Result<String> {
+        let mut file = std::fs::File::open(path)?;
+        let length = file.metadata()?.len();
+        if length <= TAIL_SIZE {
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            return Ok(content);
+        }
+        let mut header = String::new();
+        BufReader::new(&file).read_line(&mut header)?;
+        file.seek(SeekFrom::Start(length - TAIL_SIZE))?;
+        let mut tail = vec![];
+        file.read_to_end(&mut tail)?;
+        let tail = String::from_utf8_lossy(&tail);
+        // 第一行可能不完整
+        let tail = tail.split_once('\n').map(|(_, v)| v).unwrap_or_default();
+        Ok(format!("{}\n{}", header.trim_end(), tail))
+    }
 
-            let mut items = vec![];
+    impl LocalLoader {
+        fn snapshot_path(&self, symbol: impl GetSymbolCode) -> anyhow::Result<PathBuf> {
+            match self.format {
+                StorageFormat::Csv => self.day_chart_path(symbol),
+                StorageFormat::Binary => self.day_binary_path(symbol),
+            }
+        }
 
-            while start.le(&end) {
-                let file = path.join(format!("{}.csv", start.to_string()));
-                start = start.add(1);
+        /// 不晚于 `as_of` 的最后一根日线，结果按文件修改时间缓存
+        pub(crate) fn snapshot(&self, path: &Path) -> anyhow::Result<Option<Bar>> {
+            let modified = std::fs::metadata(path)?.modified()?;
+            if let Some(entry) = self.index.lock().unwrap().get(path) {
+                if entry.modified == modified && entry.as_of == self.as_of {
+                    return Ok(entry.bar.clone());
+                }
+            }
 
-                if !file.exists() {
-                    if items.len() > 0 {
-                        bail!("invalid minutes day");
+            let bar = match self.format {
+                StorageFormat::Binary => super::binary::load(path, None, self.as_of.as_deref(), Some(1))?.pop(),
+                StorageFormat::Csv => {
+                    let content = match &self.as_of {
+                        Some(_) => std::fs::read_to_string(path)?,
+                        None => read_tail(path)?,
+                    };
+                    let mut chart = self.parse_chart(content)?;
+                    if let Some(as_of) = &self.as_of {
+                        let end = super::end_bound(as_of);
+                        let index = chart.partition_point(|v| v.date <= end);
+                        chart.truncate(index);
                     }
-                    continue;
+                    chart.pop()
                 }
-                trace!("load {:?}", file);
+            };
+            let entry = IndexEntry { modified, as_of: self.as_of.clone(), bar: bar.clone() };
+            self.index.lock().unwrap().insert(path.to_path_buf(), entry);
+            Ok(bar)
+        }
 
-                if let Ok(content) = tokio::fs::read_to_string(file).await {
-                    let mut chart = self.parse_chart(content)?.value();
-                    items.append(&mut chart);
-                }
+        /// 读取快照索引，索引不存在时返回 `None`
+        pub(crate) fn read_index(&self) -> anyhow::Result<Option<HashMap<String, Bar>>> {
+            let path = self.index_path()?;
+            if !path.exists() {
+                return Ok(None);
             }
+            let content = std::fs::read(&path).context(format!("read {}", path.display()))?;
+            Ok(Some(
+                serde_json::from_slice(&content).context(format!("parse {}", path.display()))?,
+            ))
+        }
 
-            Ok(Chart::with_period(items, Period::Minute(minutes)))
+        /// 没有指定截止日期并且存在索引时直接读取索引，否则逐个读取日线文件
+        fn load_market(&self) -> anyhow::Result<HashMap<String, Bar>> {
+            if self.as_of.is_none() {
+                match self.read_index() {
+                    Ok(Some(market)) => return Ok(market),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("load snapshot index: {:?}", e),
+                }
+            }
+            self.scan_market()
         }
-    }
 
-    #[async_trait::async_trait]
-    impl ChartLoader for LocalLoader {
-        async fn chart(&self, param: impl Into<ChartParamter> + Send) -> anyhow::Result<Chart> {
-            let param = param.into();
-            match &param.period {
-                Period::Day => self.day_chart(param).await,
-                Period::Week => self.week_chart(param).await,
-                Period::Minute(_) => self.minutes_chart(param).await,
+        pub(crate) fn scan_market(&self) -> anyhow::Result<HashMap<String, Bar>> {
+            let extension = match self.format {
+                StorageFormat::Csv => "csv",
+                StorageFormat::Binary => "bin",
+            };
+            let mut market = HashMap::new();
+            for path in super::binary::nested_entries(&self.storage("stocks/day")?)? {
+                if path.extension().map(|v| v != extension).unwrap_or(true) {
+                    continue;
+                }
+                let Some(symbol) = path.file_stem().and_then(|v| v.to_str()) else {
+                    continue;
+                };
+                match self.snapshot(&path) {
+                    Ok(Some(bar)) => {
+                        market.insert(symbol.to_string(), bar);
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("[{}] load snapshot {}: {:?}", symbol, path.display(), e),
+                }
             }
+            Ok(market)
         }
     }
 
     #[async_trait::async_trait]
     impl MarketCurrentLoader for LocalLoader {
-        async fn market(&self) -> anyhow::Result<HashMap<String, Bar>> {
-            anyhow::bail!("not support")
+        /// 每只股票不晚于 `as_of` 的最后一根日线，无法读取的文件会被跳过
+        async fn market(&self) -> Result<HashMap<String, Bar>, DataError> {
+            let loader = self.clone();
+            Ok(tokio::task::spawn_blocking(move || loader.load_market()).await??)
         }
     }
 
     #[async_trait::async_trait]
     impl BarLoader for LocalLoader {
-        async fn current(&self, _symbol: impl GetSymbolCode + Send) -> anyhow::Result<Bar> {
-            anyhow::bail!("not support")
+        async fn current(&self, symbol: impl GetSymbolCode + Send) -> Result<Bar, DataError> {
+            let symbol = symbol.symbol().to_string();
+            let path = self.snapshot_path(&symbol)?;
+            if !path.exists() {
+                return Err(DataError::NotFound(symbol));
+            }
+            let loader = self.clone();
+            let bar = tokio::task::spawn_blocking(move || loader.snapshot(&path)).await??;
+            bar.ok_or(DataError::NotFound(symbol))
         }
     }
 
     #[cfg(test)]
     mod tests {
+        use std::ops::Add;
+
         use super::*;
+        use crate::testing::TempDir;
 
         #[test]
         #[ignore]
@@ -270,6 +448,73 @@ pub mod local {
             println!("stocks: {:?}", path);
         }
 
+        #[test]
+        fn invalid_symbol() {
+            let loader = LocalLoader::new(std::env::temp_dir()).unwrap();
+            for symbol in ["1", "60é444", "../../600444", "60/0444"] {
+                assert!(loader.day_chart_path(symbol).is_err(), "{}", symbol);
+                assert!(loader.minutes_chart_dir(symbol).is_err(), "{}", symbol);
+            }
+            assert!(loader
+                .day_chart_path("600444")
+                .unwrap()
+                .ends_with("stocks/day/60/04/600444.csv"));
+        }
+
+        #[tokio::test]
+        async fn market_snapshot() {
+            let dir = TempDir::new("market");
+            std::fs::create_dir_all(dir.join("stocks/day/60/18")).unwrap();
+            std::fs::create_dir_all(dir.join("stocks/day/60/04")).unwrap();
+            let mut start = TradingDay::from_str("2020-01-02").unwrap();
+            let bars: Vec<Bar> = (0..400)
+                .map(|_| {
+                    let bar = Bar::random(&start.to_string(), 10.0, 20.0);
+                    start = start.clone().add(1);
+                    bar
+                })
+                .collect();
+            let path = dir.join("stocks/day/60/18/601888.csv");
+            std::fs::write(&path, crate::csv::Writer::default().write_bars(&bars)).unwrap();
+            std::fs::write(
+                dir.join("stocks/day/60/04/600444.csv"),
+                "date,open,high,low,close,volume\n2020-01-02,10.0,10.5,9.8,10.2,1000",
+            )
+            .unwrap();
+
+            let loader = LocalLoader::new(&dir).unwrap();
+            let market = loader.market().await.unwrap();
+            assert_eq!(market.len(), 2);
+            let last = &market["601888"];
+            assert_eq!(last.date, bars[399].date);
+            assert_eq!(last.yesterday, bars[398].close);
+            assert_eq!(market["600444"].close, 10.2);
+            assert_eq!(loader.current("601888").await.unwrap().date, bars[399].date);
+            assert!(loader.current("000001").await.is_err());
+
+            let history = loader.clone().with_as_of(&bars[100].date);
+            let market = history.market().await.unwrap();
+            assert_eq!(market["601888"].date, bars[100].date);
+            assert_eq!(market["601888"].yesterday, bars[99].close);
+            let history = loader.clone().with_as_of("2018-01-01");
+            assert!(history.market().await.unwrap().is_empty());
+
+            // 文件更新后索引失效
+            std::fs::write(&path, crate::csv::Writer::default().write_bars(&bars[..10])).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10))
+                .unwrap();
+            assert_eq!(loader.current("601888").await.unwrap().date, bars[9].date);
+
+            super::super::binary::convert(&dir).unwrap();
+            let binary = LocalLoader::new(&dir)
+                .unwrap()
+                .with_format(StorageFormat::Binary)
+                .with_as_of(&bars[5].date);
+            assert_eq!(binary.market().await.unwrap()["601888"].date, bars[5].date);
+            assert_eq!(binary.current("601888").await.unwrap().yesterday, bars[4].close);
+        }
+
         #[tokio::test]
         #[ignore]
         async fn load_stocks() {
@@ -330,11 +575,11 @@ pub mod remote {
     use std::path::PathBuf;
 
     use anyhow::Context;
-    use reqwest::{header, Method, RequestBuilder, Response};
+    use reqwest::{header, Method, RequestBuilder, Response, StatusCode};
     use serde::{Deserialize, Serialize};
 
     use crate::stock::GetSymbolCode;
-    use crate::{Bar, BarLoader, Chart, ChartLoader, ChartParamter, MarketCurrentLoader, Stock, Stocks};
+    use crate::{Bar, BarLoader, Chart, ChartLoader, ChartParamter, DataError, MarketCurrentLoader, Stock, Stocks};
 
     pub mod headers {
         pub const NAME: &str = "x-trading-name";
@@ -343,6 +588,7 @@ pub mod remote {
         pub const AK: &str = "x-trading-access-key";
         pub const SIGN: &str = "x-trading-sign";
         pub const TIMESTAMP: &str = "x-trading-timestamp";
+        pub const NONCE: &str = "x-trading-nonce";
     }
 
     #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -360,6 +606,8 @@ pub mod remote {
         pub host: String,
         pub credential: Option<Credential>,
         pub timeout: Option<std::time::Duration>,
+        pub connect_timeout: Option<std::time::Duration>,
+        pub sign_version: SignVersion,
     }
 
     impl Default for RemoteLoader {
@@ -368,23 +616,256 @@ pub mod remote {
                 host: "http://127.0.0.1:18686/api/data".to_string(),
                 credential: None,
                 timeout: Some(std::time::Duration::from_secs(3)),
+                connect_timeout: None,
+                sign_version: SignVersion::default(),
             }
         }
     }
 
+    /// 旧版签名：`md5(md5(secret:path:timestamp))`，不包含请求参数，仅为兼容保留
     pub fn sign(_version: &str, secret_key: &str, data: &str, timestamp: &str) -> String {
         let data = md5::compute(format!("{}:{}:{}", secret_key, data, timestamp));
         let data = md5::compute(format!("{:x}", data));
         format!("{:x}", data)
     }
 
+    /// 签名版本，通过 `x-trading-version` 请求头传递
+    ///
+    /// 默认使用旧版签名，已部署的服务只支持旧版，服务端升级后通过 [`RemoteLoader::with_sign_version`] 或配置
+    /// `hmac_sign = true` 启用 HMAC-SHA256
+    #[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+    pub enum SignVersion {
+        /// 旧版 md5 签名，请求头中为客户端版本号
+        #[default]
+        Legacy,
+        /// HMAC-SHA256 签名，覆盖请求方法、路径、参数和请求体
+        HmacSha256,
+    }
+
+    impl SignVersion {
+        pub const HMAC_SHA256: &'static str = "hmac-sha256";
+
+        /// 非 `hmac-sha256` 的版本号都按旧版签名处理
+        pub fn from_header(value: &str) -> Self {
+            if value.eq_ignore_ascii_case(Self::HMAC_SHA256) {
+                SignVersion::HmacSha256
+            } else {
+                SignVersion::Legacy
+            }
+        }
+
+        pub fn header(&self) -> &'static str {
+            match self {
+                SignVersion::Legacy => env!("CARGO_PKG_VERSION"),
+                SignVersion::HmacSha256 => Self::HMAC_SHA256,
+            }
+        }
+    }
+
+    /// 参与签名的请求内容
+    #[derive(Debug, Clone, Default)]
+    pub struct SignContent<'a> {
+        pub method: &'a str,
+        pub path: &'a str,
+        pub query: Option<&'a str>,
+        pub body: &'a [u8],
+        pub timestamp: &'a str,
+        pub nonce: &'a str,
+    }
+
+    impl SignContent<'_> {
+        /// 规范化请求：`METHOD\nPATH\nQUERY\nSHA256(BODY)\nTIMESTAMP\nNONCE`
+        pub fn canonical(&self) -> String {
+            use sha2::Digest;
+            format!(
+                "{}\n{}\n{}\n{:x}\n{}\n{}",
+                self.method.to_ascii_uppercase(),
+                self.path,
+                canonical_query(self.query.unwrap_or_default()),
+                sha2::Sha256::digest(self.body),
+                self.timestamp,
+                self.nonce
+            )
+        }
+    }
+
+    /// 参数按名称和值排序后重新编码，与参数顺序和编码方式无关
+    pub fn canonical_query(query: &str) -> String {
+        let mut pairs: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+        pairs.sort();
+        url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(pairs)
+            .finish()
+    }
+
+    pub fn sign_hmac(secret_key: &str, content: &SignContent) -> String {
+        use hmac::Mac;
+        let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret_key.as_bytes()).expect("hmac accepts any key");
+        mac.update(content.canonical().as_bytes());
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+
+    /// 按签名版本计算签名
+    pub fn signature(version: SignVersion, secret_key: &str, content: &SignContent) -> String {
+        match version {
+            SignVersion::Legacy => sign(version.header(), secret_key, content.path, content.timestamp),
+            SignVersion::HmacSha256 => sign_hmac(secret_key, content),
+        }
+    }
+
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub enum VerifyError {
+        InvalidTimestamp,
+        Expired,
+        MissingNonce,
+        Replayed,
+        LegacyDisabled,
+        InvalidSign,
+    }
+
+    impl std::fmt::Display for VerifyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                VerifyError::InvalidTimestamp => write!(f, "invalid timestamp"),
+                VerifyError::Expired => write!(f, "request expired"),
+                VerifyError::MissingNonce => write!(f, "missing nonce"),
+                VerifyError::Replayed => write!(f, "request replayed"),
+                VerifyError::LegacyDisabled => write!(f, "legacy sign disabled"),
+                VerifyError::InvalidSign => write!(f, "invalid sign"),
+            }
+        }
+    }
+
+    impl std::error::Error for VerifyError {}
+
+    /// 时间窗口内出现过的 nonce，按出现顺序淘汰
+    #[derive(Debug, Default)]
+    struct NonceCache {
+        seen: std::collections::HashSet<String>,
+        order: std::collections::VecDeque<(i64, String)>,
+    }
+
+    impl NonceCache {
+        /// nonce 重复时返回 `false`，超过 `capacity` 时淘汰最早的 nonce
+        fn insert(&mut self, nonce: &str, now: i64, window: i64, capacity: usize) -> bool {
+            while let Some((seen, _)) = self.order.front() {
+                if now - *seen <= window && self.order.len() < capacity {
+                    break;
+                }
+                if let Some((_, nonce)) = self.order.pop_front() {
+                    self.seen.remove(&nonce);
+                }
+            }
+            if !self.seen.insert(nonce.to_string()) {
+                return false;
+            }
+            self.order.push_back((now, nonce.to_string()));
+            true
+        }
+    }
+
+    /// 服务端签名校验：检查时间窗口、nonce 是否重复以及签名是否一致
+    #[derive(Debug)]
+    pub struct Verifier {
+        window: std::time::Duration,
+        allow_legacy: bool,
+        /// 最多保存的 nonce 个数，避免大量请求占满内存
+        max_nonces: usize,
+        nonces: std::sync::Mutex<NonceCache>,
+    }
+
+    impl Default for Verifier {
+        fn default() -> Self {
+            Self::new(std::time::Duration::from_secs(300))
+        }
+    }
+
+    impl Verifier {
+        pub fn new(window: std::time::Duration) -> Self {
+            Self {
+                window,
+                allow_legacy: true,
+                max_nonces: Self::MAX_NONCES,
+                nonces: Default::default(),
+            }
+        }
+
+        pub const MAX_NONCES: usize = 100_000;
+
+        pub fn with_legacy(mut self, allow: bool) -> Self {
+            self.allow_legacy = allow;
+            self
+        }
+
+        /// 超过个数时淘汰最早的 nonce，被淘汰的 nonce 只能依靠时间窗口防止重放
+        pub fn with_max_nonces(mut self, max: usize) -> Self {
+            self.max_nonces = max.max(1);
+            self
+        }
+
+        pub fn verify(
+            &self,
+            version: SignVersion,
+            secret_key: &str,
+            content: &SignContent,
+            sign: &str,
+        ) -> Result<(), VerifyError> {
+            self.verify_at(chrono::Local::now().timestamp_millis(), version, secret_key, content, sign)
+        }
+
+        fn verify_at(
+            &self,
+            now: i64,
+            version: SignVersion,
+            secret_key: &str,
+            content: &SignContent,
+            sign: &str,
+        ) -> Result<(), VerifyError> {
+            let timestamp = content.timestamp.parse::<i64>().map_err(|_| VerifyError::InvalidTimestamp)?;
+            let window = self.window.as_millis() as i64;
+            if (now - timestamp).abs() > window {
+                return Err(VerifyError::Expired);
+            }
+            if version == SignVersion::Legacy && !self.allow_legacy {
+                return Err(VerifyError::LegacyDisabled);
+            }
+            if version == SignVersion::HmacSha256 && content.nonce.is_empty() {
+                return Err(VerifyError::MissingNonce);
+            }
+
+            let expected = signature(version, secret_key, content);
+            if !constant_time_eq(expected.as_bytes(), sign.as_bytes()) {
+                return Err(VerifyError::InvalidSign);
+            }
+
+            // 旧版签名没有 nonce，只能依靠时间窗口
+            if version == SignVersion::HmacSha256 {
+                let mut nonces = self.nonces.lock().unwrap();
+                if !nonces.insert(content.nonce, now, window, self.max_nonces) {
+                    return Err(VerifyError::Replayed);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+    }
+
     impl RemoteLoader {
         pub fn new<P: CredentialProvider>(
             host: &str,
             timeout: Option<std::time::Duration>,
             provider: P,
         ) -> anyhow::Result<Self> {
-            Ok(Self { host: host.to_string(), credential: provider.credential()?, timeout })
+            Ok(Self {
+                host: host.to_string(),
+                credential: provider.credential()?,
+                timeout,
+                connect_timeout: None,
+                sign_version: SignVersion::default(),
+            })
         }
 
         pub fn with_host<T: AsRef<str>>(mut self, host: T) -> Self {
@@ -396,6 +877,11 @@ pub mod remote {
             self
         }
 
+        pub fn with_connect_timeout(mut self, timeout: std::time::Duration) -> Self {
+            self.connect_timeout = Some(timeout);
+            self
+        }
+
         #[allow(dead_code)]
         pub fn with_provider<T: CredentialProvider>(mut self, provider: T) -> anyhow::Result<Self> {
             self.credential = provider.credential()?;
@@ -407,14 +893,24 @@ pub mod remote {
             self
         }
 
+        pub fn with_sign_version(mut self, version: SignVersion) -> Self {
+            self.sign_version = version;
+            self
+        }
+
         fn request(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
             let url = format!("{}{}", self.host, path);
-            let mut req = reqwest::Client::new().request(method, url);
+            let mut client = reqwest::Client::builder();
+            if let Some(timeout) = self.connect_timeout {
+                client = client.connect_timeout(timeout);
+            }
+            let client = client.build().unwrap_or_default();
+            let mut req = client.request(method, url);
             if let Some(timeout) = &self.timeout {
                 req = req.timeout(*timeout);
             }
             req = req.header(headers::NAME, "la.renzhen.trading");
-            req = req.header(headers::VERSION, env!("CARGO_PKG_VERSION"));
+            req = req.header(headers::VERSION, self.sign_version.header());
 
             req = if cfg!(target_os = "windows") {
                 req.header(headers::PLATFORM, "windows")
@@ -437,23 +933,50 @@ pub mod remote {
             false
         }
 
-        fn is_ok(&self, resp: &Response) -> anyhow::Result<()> {
-            if resp.status().is_success() {
+        /// 按状态码归类错误，休市对应服务端返回的 409
+        fn is_ok(&self, resp: &Response) -> Result<(), DataError> {
+            let status = resp.status();
+            if status.is_success() {
                 return Ok(());
             }
-            anyhow::bail!("http error: {}", resp.status().to_string())
+            let path = resp.url().path().to_string();
+            Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => DataError::Unauthorized(status.to_string()),
+                StatusCode::NOT_FOUND => DataError::NotFound(path),
+                StatusCode::TOO_MANY_REQUESTS => DataError::RateLimited,
+                StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => DataError::Timeout,
+                StatusCode::NOT_IMPLEMENTED => DataError::Unsupported(path),
+                StatusCode::CONFLICT => DataError::MarketClosed,
+                _ => DataError::Other(anyhow::anyhow!("http error: {}", status)),
+            })
         }
 
-        /// 添加验证信息
-        fn sign(&self, mut req: RequestBuilder, data: &str) -> RequestBuilder {
-            let timestamp = chrono::Local::now().timestamp_millis();
-            req = req.header(headers::TIMESTAMP, timestamp);
+        /// 添加验证信息并发送请求，`path` 为不包含 host 的接口路径
+        async fn send(&self, req: RequestBuilder, path: &str) -> anyhow::Result<Response> {
+            let (client, request) = req.build_split();
+            let mut request = request?;
+
+            let timestamp = chrono::Local::now().timestamp_millis().to_string();
+            let nonce = format!("{:032x}", fastrand::u128(..));
+            let headers = request.headers_mut();
+            headers.insert(headers::TIMESTAMP, header::HeaderValue::from_str(&timestamp)?);
+            headers.insert(headers::NONCE, header::HeaderValue::from_str(&nonce)?);
+
             if let Some(credential) = &self.credential {
-                let sign = sign(env!("CARGO_PKG_VERSION"), &credential.secret_key, data, &timestamp.to_string());
-                req = req.header(headers::AK, credential.access_key.as_str());
-                req = req.header(headers::SIGN, sign);
+                let content = SignContent {
+                    method: request.method().as_str(),
+                    path,
+                    query: request.url().query(),
+                    body: request.body().and_then(|body| body.as_bytes()).unwrap_or_default(),
+                    timestamp: &timestamp,
+                    nonce: &nonce,
+                };
+                let sign = signature(self.sign_version, &credential.secret_key, &content);
+                let headers = request.headers_mut();
+                headers.insert(headers::AK, header::HeaderValue::from_str(&credential.access_key)?);
+                headers.insert(headers::SIGN, header::HeaderValue::from_str(&sign)?);
             }
-            req
+            Ok(client.execute(request).await?)
         }
     }
 
@@ -533,6 +1056,7 @@ pub mod remote {
             Self {
                 providers: vec![
                     Box::new(EnvCredentialProvider::default()),
+                    Box::new(super::keystore::EncryptedCredentialProvider::default()),
                     Box::new(FileCredentialProvider::default()),
                 ],
                 must_found: false,
@@ -568,117 +1092,122 @@ pub mod remote {
 
     #[async_trait::async_trait]
     impl crate::StocksLoader for RemoteLoader {
-        async fn stocks(&self) -> anyhow::Result<crate::stock::Stocks> {
+        async fn stocks(&self) -> Result<crate::stock::Stocks, DataError> {
             let req = self.request(Method::GET, "/stocks");
-            let req = self.sign(req, "/stocks");
-
-            let resp = req.send().await?;
+            let resp = self.send(req, "/stocks").await?;
             self.is_ok(&resp)?;
             if self.is_json_response(&resp) {
                 let items = resp.json::<Vec<Stock>>().await?;
                 return Ok(Stocks::new(items));
             } else {
                 let content = resp.text().await?;
-                super::local::parse_stocks_data(content)
+                Ok(super::local::parse_stocks_data(content)?)
             }
         }
     }
 
     #[async_trait::async_trait]
     impl MarketCurrentLoader for RemoteLoader {
-        async fn market(&self) -> anyhow::Result<HashMap<String, Bar>> {
+        async fn market(&self) -> Result<HashMap<String, Bar>, DataError> {
             let req = self.request(Method::GET, "/market");
-            let req = self.sign(req, "/market");
-
-            let resp = req.send().await?;
+            let resp = self.send(req, "/market").await?;
             self.is_ok(&resp)?;
             if self.is_json_response(&resp) {
                 let output = resp.json::<HashMap<String, Bar>>().await?;
                 return Ok(output);
             }
-            let mut outputs = HashMap::new();
-
             let content = resp.text().await?;
-
-            let lines = content.lines().skip(1);
-            for line in lines.into_iter() {
-                let line = line.trim();
-                if line.is_empty() {
-                    continue;
-                }
-                let mut fields = line.split(',');
-                let name = fields.next().context("not found name")?;
-
-                let mut bar = Bar::new(fields.next().context("not found date")?);
-                bar.open = fields.next().context("not found open")?.parse::<f64>().context("parse open")?;
-                bar.high = fields.next().context("not found high")?.parse::<f64>().context("parse high")?;
-                bar.low = fields.next().context("not found low")?.parse::<f64>().context("parse low")?;
-                bar.close = fields
-                    .next()
-                    .context("not found close")?
-                    .parse::<f64>()
-                    .context("parse close")?;
-                bar.volume = fields
-                    .next()
-                    .context("not found volume")?
-                    .parse::<f64>()
-                    .context("parse volume")?;
-                if !bar.is_ok() {
-                    continue;
-                }
-                outputs.insert(name.to_string(), bar);
-            }
+            let outputs = crate::csv::read_records(&content)?
+                .into_iter()
+                .filter(|(_, bar)| bar.is_ok())
+                .collect();
             Ok(outputs)
         }
     }
 
     #[async_trait::async_trait]
     impl BarLoader for RemoteLoader {
-        async fn current(&self, symbol: impl GetSymbolCode + Send) -> anyhow::Result<Bar> {
+        async fn current(&self, symbol: impl GetSymbolCode + Send) -> Result<Bar, DataError> {
             let uri = format!("/current/{}", symbol.symbol());
             let req = self.request(Method::GET, &uri);
-            let req = self.sign(req, &uri);
-            let resp = req.send().await?;
+            let resp = self.send(req, &uri).await?;
             self.is_ok(&resp)?;
             if self.is_json_response(&resp) {
                 let output = resp.json::<Bar>().await?;
                 return Ok(output);
             }
             let content = resp.text().await?;
-            let Some(line) = content.lines().skip(1).next() else {
-                anyhow::bail!("not found");
-            };
+            parse_current(symbol.symbol(), &content)
+        }
+    }
+
+    /// 解析 csv 格式的实时行情，第二行为空表示已收盘
+    fn parse_current(symbol: &str, content: &str) -> Result<Bar, DataError> {
+        let Some(line) = content.lines().nth(1) else {
+            return Err(DataError::NotFound(symbol.to_string()));
+        };
 
-            if line.is_empty() {
-                anyhow::bail!("closed");
-            }
-
-            let mut fields = line.split(',');
-            let _name = fields.next().context("not found name")?;
-            let mut bar = Bar::new(fields.next().context("not found date")?);
-            bar.open = fields.next().context("not found open")?.parse::<f64>().context("parse open")?;
-            bar.high = fields.next().context("not found high")?.parse::<f64>().context("parse high")?;
-            bar.low = fields.next().context("not found low")?.parse::<f64>().context("parse low")?;
-            bar.close = fields
-                .next()
-                .context("not found close")?
-                .parse::<f64>()
-                .context("parse close")?;
-            bar.volume = fields
-                .next()
-                .context("not found volume")?
-                .parse::<f64>()
-                .context("parse volume")?;
-            if !bar.is_ok() {
-                anyhow::bail!("invailed data");
+        if line.is_empty() {
+            return Err(DataError::MarketClosed);
+        }
+
+        let Some((_, bar)) = crate::csv::read_records(content)?.into_iter().next() else {
+            return Err(DataError::NotFound(symbol.to_string()));
+        };
+        if !bar.is_ok() {
+            let message = format!("invalid bar: {}", line);
+            return Err(DataError::Parse { file: None, line: 2, column: 0, message });
+        }
+        Ok(bar)
+    }
+
+    #[async_trait::async_trait]
+    impl crate::FundamentalsLoader for RemoteLoader {
+        async fn fundamentals(&self, symbol: impl GetSymbolCode + Send) -> Result<crate::Fundamentals, DataError> {
+            let uri = format!("/fundamentals/{}", symbol.symbol());
+            let req = self.request(Method::GET, &uri);
+            let resp = self.send(req, &uri).await?;
+            self.is_ok(&resp)?;
+            if self.is_json_response(&resp) {
+                let items = resp.json::<Vec<crate::Fundamental>>().await?;
+                return Ok(crate::Fundamentals::new(items));
             }
-            Ok(bar)
+            let content = resp.text().await?;
+            Ok(crate::Fundamentals::parse_csv(&content)?)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::SectorLoader for RemoteLoader {
+        async fn sectors(&self) -> Result<crate::Sectors, DataError> {
+            let req = self.request(Method::GET, "/sectors");
+            let resp = self.send(req, "/sectors").await?;
+            self.is_ok(&resp)?;
+            if self.is_json_response(&resp) {
+                let items = resp.json::<Vec<crate::Sector>>().await?;
+                return Ok(crate::Sectors::new(items));
+            }
+            let content = resp.text().await?;
+            Ok(crate::Sectors::parse_csv(&content)?)
+        }
+
+        async fn members(&self, code: &str) -> Result<crate::Members, DataError> {
+            let uri = format!("/sectors/{}", code);
+            let req = self.request(Method::GET, &uri);
+            let resp = self.send(req, &uri).await?;
+            self.is_ok(&resp)?;
+            if self.is_json_response(&resp) {
+                let items = resp.json::<Vec<crate::Membership>>().await?;
+                return Ok(crate::Members::new(items));
+            }
+            let content = resp.text().await?;
+            Ok(crate::Members::parse_csv(&content)?)
         }
     }
 
     #[async_trait::async_trait]
     impl ChartLoader for RemoteLoader {
-        async fn chart(&self, param: impl Into<ChartParamter> + Send) -> anyhow::Result<Chart> {
+        async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError> {
             let param = param.into();
             let uri = format!("/chart/{}/{}", param.period.to_string(), &param.symbol);
 
@@ -686,47 +1215,22 @@ pub mod remote {
             if let Some(limit) = &param.limit {
                 params.insert("limit", limit.to_string());
             }
+            if let Some(start) = &param.start {
+                params.insert("start", start.to_string());
+            }
             if let Some(end) = &param.end {
                 params.insert("end", end.to_string());
             }
 
             let req = self.request(Method::GET, &uri).query(&params);
-            let req = self.sign(req, &uri);
-
-            let resp = req.send().await?;
+            let resp = self.send(req, &uri).await?;
             self.is_ok(&resp)?;
             if self.is_json_response(&resp) {
                 let output = resp.json::<Vec<Bar>>().await?;
                 return Ok(Chart::new(output));
             }
-            let mut items = vec![];
             let content = resp.text().await?;
-            let lines = content.lines().skip(1);
-            for line in lines.into_iter() {
-                let line = line.trim();
-                if line.is_empty() {
-                    continue;
-                }
-                let mut fields = line.split(',');
-                let mut bar = Bar::new(fields.next().context("not found date")?);
-                bar.open = fields.next().context("not found open")?.parse::<f64>().context("parse open")?;
-                bar.high = fields.next().context("not found high")?.parse::<f64>().context("parse high")?;
-                bar.low = fields.next().context("not found low")?.parse::<f64>().context("parse low")?;
-                bar.close = fields
-                    .next()
-                    .context("not found close")?
-                    .parse::<f64>()
-                    .context("parse close")?;
-                bar.volume = fields
-                    .next()
-                    .context("not found volume")?
-                    .parse::<f64>()
-                    .context("parse volume")?;
-                if !bar.is_ok() {
-                    continue;
-                }
-                items.push(bar);
-            }
+            let items = crate::csv::read_bars(&content)?.into_iter().filter(Bar::is_ok).collect();
             Ok(Chart::new(items))
         }
     }
@@ -737,6 +1241,119 @@ pub mod remote {
 
         use super::*;
 
+        #[test]
+        fn current_csv() {
+            let header = "symbol,date,open,high,low,close,volume";
+            let bar = parse_current("601888", &format!("{}\n601888,2023-07-12,10,11,9,10.5,1000", header)).unwrap();
+            assert_eq!(bar.close, 10.5);
+            assert!(matches!(
+                parse_current("601888", &format!("{}\n", header)),
+                Err(DataError::NotFound(_))
+            ));
+
+            let line = "601888,2023-07-12,0,0,0,0,0";
+            let err = parse_current("601888", &format!("{}\n{}", header, line)).unwrap_err();
+            assert!(matches!(&err, DataError::Parse { line: 2, column: 0, .. }), "{:?}", err);
+            assert!(err.to_string().contains(line), "{}", err);
+        }
+
+        #[test]
+        fn hmac_sign() {
+            let content = SignContent {
+                method: "get",
+                path: "/chart/day/601888",
+                query: Some("limit=10&end=2023-07-12"),
+                body: b"",
+                timestamp: "1689129600000",
+                nonce: "abc",
+            };
+            assert_eq!(
+                content.canonical(),
+                "GET\n/chart/day/601888\nend=2023-07-12&limit=10\n\
+                 e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\n1689129600000\nabc"
+            );
+            assert_eq!(canonical_query("b=2&a=1&a=0"), "a=0&a=1&b=2");
+            assert_eq!(canonical_query("end=2023%2D07%2D12"), canonical_query("end=2023-07-12"));
+
+            let sign = sign_hmac("secret", &content);
+            assert_eq!(sign.len(), 64);
+            let tampered = SignContent { query: Some("limit=1000&end=2023-07-12"), ..content.clone() };
+            assert_ne!(sign, sign_hmac("secret", &tampered));
+            assert_eq!(sign, signature(SignVersion::HmacSha256, "secret", &content));
+            assert_eq!(
+                signature(SignVersion::Legacy, "secret", &content),
+                super::sign("", "secret", content.path, content.timestamp)
+            );
+            assert_eq!(
+                SignVersion::from_header(SignVersion::HmacSha256.header()),
+                SignVersion::HmacSha256
+            );
+            assert_eq!(SignVersion::from_header(env!("CARGO_PKG_VERSION")), SignVersion::Legacy);
+        }
+
+        #[test]
+        fn verifier() {
+            let verifier = Verifier::new(std::time::Duration::from_secs(60));
+            let now = 1689129600000;
+            let content = SignContent {
+                method: "GET",
+                path: "/stocks",
+                timestamp: "1689129600000",
+                nonce: "n1",
+                ..Default::default()
+            };
+            let sign = sign_hmac("secret", &content);
+            let verify = |now, content: &SignContent, sign: &str| {
+                verifier.verify_at(now, SignVersion::HmacSha256, "secret", content, sign)
+            };
+
+            assert_eq!(verify(now, &content, &sign), Ok(()));
+            assert_eq!(verify(now, &content, &sign), Err(VerifyError::Replayed));
+            assert_eq!(verify(now + 61_000, &content, &sign), Err(VerifyError::Expired));
+            assert_eq!(verify(now, &content, "bad"), Err(VerifyError::InvalidSign));
+
+            let content = SignContent { nonce: "", ..content };
+            assert_eq!(verify(now, &content, &sign), Err(VerifyError::MissingNonce));
+
+            let legacy = super::sign("", "secret", content.path, content.timestamp);
+            assert_eq!(
+                verifier.verify_at(now, SignVersion::Legacy, "secret", &content, &legacy),
+                Ok(())
+            );
+            let verifier = Verifier::new(std::time::Duration::from_secs(60)).with_legacy(false);
+            assert_eq!(
+                verifier.verify_at(now, SignVersion::Legacy, "secret", &content, &legacy),
+                Err(VerifyError::LegacyDisabled)
+            );
+            assert_eq!(RemoteLoader::default().sign_version, SignVersion::Legacy);
+        }
+
+        #[test]
+        fn nonce_capacity() {
+            let verifier = Verifier::new(std::time::Duration::from_secs(60)).with_max_nonces(2);
+            let now = 1689129600000;
+            let verify = |nonce: &str, now: i64| {
+                let timestamp = now.to_string();
+                let content = SignContent {
+                    method: "GET",
+                    path: "/stocks",
+                    timestamp: &timestamp,
+                    nonce,
+                    ..Default::default()
+                };
+                let sign = sign_hmac("secret", &content);
+                verifier.verify_at(now, SignVersion::HmacSha256, "secret", &content, &sign)
+            };
+            for nonce in ["n1", "n2", "n3"] {
+                assert_eq!(verify(nonce, now), Ok(()));
+            }
+            assert_eq!(verifier.nonces.lock().unwrap().order.len(), 2);
+            assert_eq!(verify("n3", now), Err(VerifyError::Replayed));
+            assert_eq!(verify("n1", now), Ok(()));
+            assert_eq!(verify("n4", now + 61_000), Ok(()));
+            assert_eq!(verifier.nonces.lock().unwrap().seen.len(), 1);
+        }
+
         #[tokio::test]
         #[ignore]
         async fn load_stocks() {
@@ -782,3 +1399,41 @@ pub mod remote {
         }
     }
 }
+
+/// 截止日期的比较上界，只给出日期时包含当天所有的分钟线，只到分钟时包含这一分钟
+pub(crate) fn end_bound(end: &str) -> String {
+    match end.len() {
+        10 => format!("{}~", end),
+        16 => format!("{}:59", end),
+        _ => end.to_string(),
+    }
+}
+
+/// 二进制列式存储
+pub mod binary;
+
+/// 通达信数据文件
+pub mod tdx;
+
+/// 加密的凭证文件
+pub mod keystore;
+
+/// 外部命令凭证
+pub mod command;
+
+/// 本地数据目录的版本和迁移
+pub mod layout;
+
+/// 多日分钟线
+pub mod minutes;
+
+/// 原子写入和K线写入
+pub mod writer;
+
+/// SQLite 存储
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Parquet / Arrow 数据集
+#[cfg(feature = "arrow")]
+pub mod dataset;
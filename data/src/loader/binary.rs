@@ -0,0 +1,499 @@
+//! K线二进制列式存储
+//!
+//! 文件布局（小端序）：
+//!
+//! | 偏移 | 长度 | 内容 |
+//! |------|------|------|
+//! | 0    | 4    | 魔数 `TRDB` |
+//! | 4    | 2    | 版本号，当前为 1 |
+//! | 6    | 2    | 周期类型：0 日线，1 周线，2 分钟线 |
+//! | 8    | 2    | 分钟数，非分钟线为 0 |
+//! | 10   | 2    | 日期格式：0 `%Y-%m-%d`，1 `%Y-%m-%d %H:%M:%S`，2 `%Y-%m-%d %H:%M` |
+//! | 12   | 4    | K线数量 `n` |
+//! | 16   | 8n   | 日期列，`i64` 秒（按 UTC 解释的本地时间） |
+//! | ...  | 8n   | open / high / low / close / volume / amount 列，均为 `f64` |
+//!
+//! 每一列定长，按下标即可直接定位任意一根K线，不需要解析整个文件。
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use chrono::NaiveDateTime;
+
+use crate::{Bar, Chart, Period};
+
+pub const MAGIC: &[u8; 4] = b"TRDB";
+pub const VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 16;
+
+/// 日期列之后的数值列
+const VALUE_COLUMNS: usize = 6;
+pub(crate) const DATE_FORMATS: [&str; 3] = ["%Y-%m-%d", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Header {
+    pub version: u16,
+    pub period: Period,
+    date_format: u16,
+    pub count: usize,
+}
+
+impl Header {
+    fn encode(&self) -> [u8; HEADER_SIZE] {
+        let (kind, minutes) = match self.period {
+            Period::Day => (0u16, 0u16),
+            Period::Week => (1, 0),
+            Period::Minute(minutes) => (2, minutes as u16),
+        };
+        let mut buf = [0u8; HEADER_SIZE];
+        buf[0..4].copy_from_slice(MAGIC);
+        buf[4..6].copy_from_slice(&self.version.to_le_bytes());
+        buf[6..8].copy_from_slice(&kind.to_le_bytes());
+        buf[8..10].copy_from_slice(&minutes.to_le_bytes());
+        buf[10..12].copy_from_slice(&self.date_format.to_le_bytes());
+        buf[12..16].copy_from_slice(&(self.count as u32).to_le_bytes());
+        buf
+    }
+
+    fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < HEADER_SIZE || &data[0..4] != MAGIC {
+            bail!("invalid binary chart file");
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let version = u16_at(4);
+        if version != VERSION {
+            bail!("unsupported binary chart version: {}", version);
+        }
+        let period = match (u16_at(6), u16_at(8)) {
+            (0, _) => Period::Day,
+            (1, _) => Period::Week,
+            (2, minutes) if minutes > 0 => Period::Minute(minutes as usize),
+            (kind, minutes) => bail!("invalid period: {}/{}", kind, minutes),
+        };
+        let date_format = u16_at(10);
+        if date_format as usize >= DATE_FORMATS.len() {
+            bail!("invalid date format: {}", date_format);
+        }
+        let count = u32::from_le_bytes(data[12..16].try_into().unwrap()) as usize;
+        let expected = HEADER_SIZE + count * 8 * (VALUE_COLUMNS + 1);
+        if data.len() != expected {
+            bail!("invalid binary chart length: {}, expected {}", data.len(), expected);
+        }
+        Ok(Self { version, period, date_format, count })
+    }
+}
+
+pub(crate) fn date_format(date: &str) -> u16 {
+    match date.len() {
+        19 => 1,
+        16 => 2,
+        _ => 0,
+    }
+}
+
+pub(crate) fn parse_date(date: &str) -> anyhow::Result<i64> {
+    let datetime = match date.len() {
+        10 => chrono::NaiveDate::parse_from_str(date, DATE_FORMATS[0]).map(|v| v.and_hms_opt(0, 0, 0).unwrap()),
+        16 => NaiveDateTime::parse_from_str(date, DATE_FORMATS[2]),
+        _ => NaiveDateTime::parse_from_str(date, DATE_FORMATS[1]),
+    };
+    Ok(datetime.context(format!("invalid date: {}", date))?.timestamp())
+}
+
+/// 编码为二进制格式，K线需按日期升序排列
+pub fn encode(chart: &Chart) -> anyhow::Result<Vec<u8>> {
+    let header = Header {
+        version: VERSION,
+        period: *chart.period(),
+        date_format: chart.first().map(|v| date_format(&v.date)).unwrap_or_default(),
+        count: chart.len(),
+    };
+    let mut buf = Vec::with_capacity(HEADER_SIZE + chart.len() * 8 * (VALUE_COLUMNS + 1));
+    buf.extend_from_slice(&header.encode());
+    for bar in chart.iter() {
+        buf.extend_from_slice(&parse_date(&bar.date)?.to_le_bytes());
+    }
+    let columns: [fn(&Bar) -> f64; VALUE_COLUMNS] =
+        [|v| v.open, |v| v.high, |v| v.low, |v| v.close, |v| v.volume, |v| v.amount];
+    for column in columns {
+        for bar in chart.iter() {
+            buf.extend_from_slice(&column(bar).to_le_bytes());
+        }
+    }
+    Ok(buf)
+}
+
+/// 通过 [`super::writer::write`] 原子写入
+pub fn write(path: impl AsRef<Path>, chart: &Chart) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    super::writer::write(path, encode(chart)?).context(format!("write binary chart: {}", path.display()))
+}
+
+/// 二进制K线读取器，数据可以是内存中的字节或内存映射的文件
+#[derive(Debug)]
+pub struct BinaryChart<B> {
+    data: B,
+    header: Header,
+}
+
+impl<B: AsRef<[u8]>> BinaryChart<B> {
+    pub fn parse(data: B) -> anyhow::Result<Self> {
+        let header = Header::decode(data.as_ref())?;
+        Ok(Self { data, header })
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.count == 0
+    }
+
+    fn value(&self, column: usize, index: usize) -> [u8; 8] {
+        let offset = HEADER_SIZE + (column * self.header.count + index) * 8;
+        self.data.as_ref()[offset..offset + 8].try_into().unwrap()
+    }
+
+    fn timestamp(&self, index: usize) -> i64 {
+        i64::from_le_bytes(self.value(0, index))
+    }
+
+    pub fn date(&self, index: usize) -> String {
+        let format = DATE_FORMATS[self.header.date_format as usize];
+        NaiveDateTime::from_timestamp_opt(self.timestamp(index), 0)
+            .map(|v| v.format(format).to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn bar(&self, index: usize) -> Bar {
+        let value = |column: usize| f64::from_le_bytes(self.value(column, index));
+        Bar {
+            date: self.date(index),
+            open: value(1),
+            high: value(2),
+            low: value(3),
+            close: value(4),
+            volume: value(5),
+            amount: value(6),
+            yesterday: if index > 0 { f64::from_le_bytes(self.value(4, index - 1)) } else { 0.0 },
+        }
+    }
+
+    /// 时间戳满足 `before` 的K线数量，K线按日期升序
+    fn partition(&self, before: impl Fn(i64) -> bool) -> usize {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if before(self.timestamp(mid)) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+
+    /// 日期不晚于 `end` 的K线数量，`end` 为日期时包含当天全部数据
+    pub fn position(&self, end: &str) -> anyhow::Result<usize> {
+        let end = match end.len() {
+            10 => parse_date(end)? + 86400 - 1,
+            _ => parse_date(end)?,
+        };
+        Ok(self.partition(|v| v <= end))
+    }
+
+    /// 只解码截止 `end` 的最后 `limit` 根K线
+    pub fn chart(&self, end: Option<&str>, limit: Option<usize>) -> anyhow::Result<Chart> {
+        self.range(None, end, limit)
+    }
+
+    /// 只解码 `start` 到 `end` 之间的最后 `limit` 根K线
+    pub fn range(&self, start: Option<&str>, end: Option<&str>, limit: Option<usize>) -> anyhow::Result<Chart> {
+        let stop = match end {
+            Some(end) => self.position(end)?,
+            None => self.len(),
+        };
+        let first = match start {
+            Some(start) => {
+                let start = parse_date(start)?;
+                self.partition(|v| v < start)
+            }
+            None => 0,
+        };
+        let start = stop.saturating_sub(limit.unwrap_or(usize::MAX)).max(first);
+        let items = (start..stop.max(start))
+            .map(|index| self.bar(index))
+            .filter(Bar::is_ok)
+            .collect();
+        Ok(Chart::with_period(items, self.header.period))
+    }
+}
+
+/// 读取整个文件
+pub fn open(path: impl AsRef<Path>) -> anyhow::Result<BinaryChart<Vec<u8>>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).context(format!("read binary chart: {}", path.display()))?;
+    BinaryChart::parse(data).context(format!("parse binary chart: {}", path.display()))
+}
+
+/// 以内存映射方式打开文件，只有访问到的K线才会被读入
+#[cfg(feature = "mmap")]
+pub fn open_mmap(path: impl AsRef<Path>) -> anyhow::Result<BinaryChart<memmap2::Mmap>> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).context(format!("open binary chart: {}", path.display()))?;
+    // SAFETY: 文件只通过临时文件加重命名的方式整体替换，映射期间内容不会被原地修改
+    let data = unsafe { memmap2::Mmap::map(&file) }.context(format!("mmap binary chart: {}", path.display()))?;
+    BinaryChart::parse(data).context(format!("parse binary chart: {}", path.display()))
+}
+
+/// 读取文件中 `start` 到 `end` 之间的最后 `limit` 根K线，开启 `mmap` 特性时使用内存映射
+pub fn load(
+    path: impl AsRef<Path>,
+    start: Option<&str>,
+    end: Option<&str>,
+    limit: Option<usize>,
+) -> anyhow::Result<Chart> {
+    #[cfg(feature = "mmap")]
+    return open_mmap(path)?.range(start, end, limit);
+    #[cfg(not(feature = "mmap"))]
+    return open(path)?.range(start, end, limit);
+}
+
+/// 根据相邻K线的最小间隔推断分钟周期
+pub(crate) fn infer_minutes(bars: &[Bar]) -> Option<usize> {
+    bars.windows(2)
+        .filter_map(|v| Some(parse_date(&v[1].date).ok()? - parse_date(&v[0].date).ok()?))
+        .filter(|v| *v > 0)
+        .min()
+        .map(|v| (v / 60) as usize)
+        .filter(|v| *v > 0)
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ConvertStats {
+    pub day_files: usize,
+    pub minute_symbols: usize,
+    pub bars: usize,
+}
+
+pub(crate) fn csv_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for entry in std::fs::read_dir(dir).context(format!("read dir: {}", dir.display()))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().map(|v| v == "csv").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 把目录下两级子目录中的条目展开，对应 `{s1}/{s2}/` 的存储结构
+pub(crate) fn nested_entries(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut entries = vec![];
+    if !dir.exists() {
+        return Ok(entries);
+    }
+    for s1 in std::fs::read_dir(dir)? {
+        let s1 = s1?.path();
+        if !s1.is_dir() {
+            continue;
+        }
+        for s2 in std::fs::read_dir(&s1)? {
+            let s2 = s2?.path();
+            if !s2.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&s2)? {
+                entries.push(entry?.path());
+            }
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+pub(crate) fn read_csv(path: &Path) -> anyhow::Result<Vec<Bar>> {
+    let content = std::fs::read_to_string(path).context(format!("read {}", path.display()))?;
+    crate::csv::read_bars(&content).context(format!("parse {}", path.display()))
+}
+
+/// 把 `LocalLoader` 的 csv 目录结构转换为二进制文件，转换结果与 csv 文件放在同一目录
+///
+/// - `stocks/day/{s1}/{s2}/{symbol}.csv` 转换为 `stocks/day/{s1}/{s2}/{symbol}.bin`
+/// - `stocks/minutes/{s1}/{s2}/{symbol}/{date}.csv` 合并为 `stocks/minutes/{s1}/{s2}/{symbol}.bin`
+///
+/// 完成后重建二进制格式的快照索引
+pub fn convert(base_dir: impl AsRef<Path>) -> anyhow::Result<ConvertStats> {
+    let base_dir = base_dir.as_ref();
+    super::layout::init(base_dir)?;
+    let mut stats = ConvertStats::default();
+
+    for path in nested_entries(&base_dir.join("stocks/day"))? {
+        if path.extension().map(|v| v != "csv").unwrap_or(true) {
+            continue;
+        }
+        let bars = read_csv(&path)?;
+        stats.bars += bars.len();
+        stats.day_files += 1;
+        write(path.with_extension("bin"), &Chart::new(bars))?;
+    }
+
+    for dir in nested_entries(&base_dir.join("stocks/minutes"))? {
+        if !dir.is_dir() {
+            continue;
+        }
+        let mut bars = vec![];
+        for file in csv_files(&dir)? {
+            bars.append(&mut read_csv(&file)?);
+        }
+        if bars.is_empty() {
+            continue;
+        }
+        let minutes = infer_minutes(&bars).unwrap_or(5);
+        stats.bars += bars.len();
+        stats.minute_symbols += 1;
+        write(dir.with_extension("bin"), &Chart::with_period(bars, Period::Minute(minutes)))?;
+    }
+    crate::LocalLoader::new(base_dir)?
+        .with_format(crate::StorageFormat::Binary)
+        .rebuild_index()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{bars, TempDir};
+    use crate::{ChartLoader, ChartParamter, LocalLoader, StorageFormat};
+
+    fn write_csv(path: PathBuf, bars: &[Bar]) {
+        use crate::csv::Column::*;
+        let writer = crate::csv::Writer::new(&[Date, Open, High, Low, Close, Volume, Amount]);
+        std::fs::write(path, writer.write_bars(bars)).unwrap();
+    }
+
+    fn assert_same(a: &[Bar], b: &[Bar]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(a.date, b.date);
+            assert_eq!((a.open, a.high, a.low, a.close), (b.open, b.high, b.low, b.close));
+            assert_eq!((a.volume, a.amount), (b.volume, b.amount));
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for (period, dates) in [
+            (Period::Day, vec!["2023-07-10", "2023-07-11", "2023-07-12"]),
+            (Period::Minute(5), vec!["2023-07-12 09:35:00", "2023-07-12 09:40:00"]),
+            (Period::Minute(1), vec!["2023-07-12 09:31", "2023-07-12 09:32"]),
+            (Period::Week, vec![]),
+        ] {
+            let chart = Chart::with_period(bars(&dates), period);
+            let data = encode(&chart).unwrap();
+            assert_eq!(data.len(), HEADER_SIZE + dates.len() * 56);
+
+            let binary = BinaryChart::parse(data).unwrap();
+            assert_eq!(binary.header().period, period);
+            let output = binary.chart(None, None).unwrap();
+            assert_eq!(*output.period(), period);
+            assert_same(&chart, &output);
+            if output.len() > 1 {
+                assert_eq!(output[1].yesterday, chart[0].close);
+            }
+        }
+    }
+
+    #[test]
+    fn range() {
+        let chart = Chart::new(bars(&["2023-07-10", "2023-07-11", "2023-07-12", "2023-07-13"]));
+        let binary = BinaryChart::parse(encode(&chart).unwrap()).unwrap();
+
+        assert_eq!(binary.position("2023-07-12").unwrap(), 3);
+        assert_eq!(binary.position("2023-07-09").unwrap(), 0);
+        assert_eq!(binary.position("2023-07-20").unwrap(), 4);
+
+        let output = binary.chart(Some("2023-07-12"), Some(2)).unwrap();
+        assert_same(&output, &chart[1..3]);
+        let output = binary.chart(None, Some(10)).unwrap();
+        assert_same(&output, &chart);
+        let output = binary.range(Some("2023-07-11"), Some("2023-07-12"), None).unwrap();
+        assert_same(&output, &chart[1..3]);
+        let output = binary.range(Some("2023-07-12"), None, Some(1)).unwrap();
+        assert_same(&output, &chart[3..]);
+        assert!(binary.range(Some("2023-07-13"), Some("2023-07-11"), None).unwrap().is_empty());
+
+        let minutes = Chart::with_period(
+            bars(&["2023-07-12 14:55:00", "2023-07-12 15:00:00", "2023-07-13 09:35:00"]),
+            Period::Minute(5),
+        );
+        let binary = BinaryChart::parse(encode(&minutes).unwrap()).unwrap();
+        assert_eq!(binary.position("2023-07-12").unwrap(), 2);
+        assert_eq!(binary.position("2023-07-12 14:55:00").unwrap(), 1);
+        assert_eq!(infer_minutes(&minutes), Some(5));
+    }
+
+    #[test]
+    fn invalid() {
+        let chart = Chart::new(bars(&["2023-07-10", "2023-07-11"]));
+        let data = encode(&chart).unwrap();
+
+        assert!(BinaryChart::parse(&data[..data.len() - 1]).is_err());
+        assert!(BinaryChart::parse(&data[..8]).is_err());
+
+        let mut bad = data.clone();
+        bad[0] = b'X';
+        assert!(BinaryChart::parse(&bad).is_err());
+
+        let mut bad = data;
+        bad[4..6].copy_from_slice(&2u16.to_le_bytes());
+        let err = BinaryChart::parse(&bad).unwrap_err();
+        assert!(err.to_string().contains("version"));
+    }
+
+    #[tokio::test]
+    async fn convert_layout() {
+        let dir = TempDir::new("binary");
+        let day = bars(&["2023-07-10", "2023-07-11", "2023-07-12"]);
+        std::fs::create_dir_all(dir.join("stocks/day/60/18")).unwrap();
+        write_csv(dir.join("stocks/day/60/18/601888.csv"), &day);
+
+        let minutes_dir = dir.join("stocks/minutes/60/18/601888");
+        std::fs::create_dir_all(&minutes_dir).unwrap();
+        let d1 = bars(&["2023-07-11 14:50:00", "2023-07-11 14:55:00", "2023-07-11 15:00:00"]);
+        let d2 = bars(&["2023-07-12 09:35:00", "2023-07-12 09:40:00"]);
+        write_csv(minutes_dir.join("2023-07-11.csv"), &d1);
+        write_csv(minutes_dir.join("2023-07-12.csv"), &d2);
+
+        let stats = convert(&dir).unwrap();
+        assert_eq!(stats, ConvertStats { day_files: 1, minute_symbols: 1, bars: 8 });
+
+        let loader = LocalLoader::new(&dir).unwrap().with_format(StorageFormat::Binary);
+        let chart = loader.chart(ChartParamter::day("601888").limit(2)).await.unwrap();
+        assert_same(&chart, &day[1..]);
+
+        let chart = loader.chart(ChartParamter::day("601888").end("2023-07-11")).await.unwrap();
+        assert_same(&chart, &day[..2]);
+
+        let chart = loader.chart(ChartParamter::new("601888", Period::Week)).await.unwrap();
+        assert_eq!(chart.len(), 1);
+
+        let param = ChartParamter::new("601888", Period::Minute(5)).end("2023-07-12").limit(3);
+        let chart = loader.chart(param).await.unwrap();
+        assert_eq!(*chart.period(), Period::Minute(5));
+        assert_same(&chart, &[d1[2].clone(), d2[0].clone(), d2[1].clone()]);
+
+        let csv = LocalLoader::new(&dir).unwrap();
+        let chart = csv.chart(ChartParamter::day("601888")).await.unwrap();
+        assert_same(&chart, &day);
+    }
+}
@@ -0,0 +1,185 @@
+//! 外部命令凭证
+//!
+//! 与 git 的 credential helper 类似，运行本地命令并从标准输出读取
+//! `{"access_key": "...", "secret_key": "..."}`，可以接入任意的密钥管理工具。
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use lazy_static::lazy_static;
+
+use super::remote::{Credential, CredentialProvider};
+
+lazy_static! {
+    /// 相同命令的结果在进程内只获取一次
+    static ref CACHE: Mutex<HashMap<Vec<String>, Credential>> = Mutex::new(HashMap::new());
+}
+
+pub struct CommandCredentialProvider {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandCredentialProvider {
+    pub fn new(program: impl Into<String>, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            program: program.into(),
+            args: args.into_iter().map(Into::into).collect(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// 通过系统 shell 执行，unix 为 `sh -c`，windows 为 `cmd /C`
+    pub fn shell(command: impl Into<String>) -> Self {
+        if cfg!(windows) {
+            Self::new("cmd", ["/C".to_string(), command.into()])
+        } else {
+            Self::new("sh", ["-c".to_string(), command.into()])
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn key(&self) -> Vec<String> {
+        std::iter::once(self.program.clone()).chain(self.args.iter().cloned()).collect()
+    }
+
+    fn display(&self) -> String {
+        self.key().join(" ")
+    }
+
+    fn run(&self) -> anyhow::Result<Credential> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(format!("spawn credential command `{}`", self.display()))?;
+
+        // 在单独的线程中读取输出，避免管道写满后子进程阻塞
+        let read = |mut pipe: Box<dyn Read + Send>| {
+            std::thread::spawn(move || {
+                let mut output = Vec::new();
+                let _ = pipe.read_to_end(&mut output);
+                output
+            })
+        };
+        let stdout = read(Box::new(child.stdout.take().unwrap()));
+        let stderr = read(Box::new(child.stderr.take().unwrap()));
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                // 子进程退出后管道关闭，读取线程随之结束；命令启动的后台进程可能仍持有管道，
+                // 等待一小段时间后仍未结束的线程不再等待，由后台进程退出时自行结束
+                let readers = [stdout, stderr];
+                let grace = Instant::now() + Duration::from_millis(100);
+                while readers.iter().any(|v| !v.is_finished()) && Instant::now() < grace {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                for reader in readers.into_iter().filter(|v| v.is_finished()) {
+                    let _ = reader.join();
+                }
+                bail!("credential command `{}` timed out after {:?}", self.display(), self.timeout);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        if !status.success() {
+            bail!(
+                "credential command `{}` failed with {}: {}",
+                self.display(),
+                status,
+                String::from_utf8_lossy(&stderr).trim()
+            );
+        }
+        let credential: Credential = serde_json::from_slice(&stdout)
+            .context(format!("credential command `{}` returned invalid json", self.display()))?;
+        if credential.access_key.is_empty() || credential.secret_key.is_empty() {
+            bail!(
+                "credential command `{}` returned empty access_key or secret_key",
+                self.display()
+            );
+        }
+        Ok(credential)
+    }
+}
+
+impl CredentialProvider for CommandCredentialProvider {
+    fn credential(&self) -> anyhow::Result<Option<Credential>> {
+        let key = self.key();
+        if let Some(credential) = CACHE.lock().unwrap().get(&key) {
+            return Ok(Some(credential.clone()));
+        }
+        tracing::debug!("load credential from command `{}`", self.display());
+        let credential = self.run()?;
+        CACHE.lock().unwrap().insert(key, credential.clone());
+        Ok(Some(credential))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn command_credential() {
+        let dir = TempDir::new("command");
+        let counter = dir.join("counter");
+        let script = format!(
+            r#"echo x >> {}; echo '{{"access_key": "ak", "secret_key": "sk"}}'"#,
+            counter.display()
+        );
+        let provider = CommandCredentialProvider::shell(&script);
+        assert_eq!(provider.credential().unwrap().unwrap().secret_key, "sk");
+        assert_eq!(
+            CommandCredentialProvider::shell(&script)
+                .credential()
+                .unwrap()
+                .unwrap()
+                .access_key,
+            "ak"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&counter).unwrap().lines().count(),
+            1,
+            "result must be cached"
+        );
+
+        let err = CommandCredentialProvider::shell("echo denied >&2; exit 3")
+            .credential()
+            .unwrap_err();
+        assert!(err.to_string().contains("denied"), "{}", err);
+        let err = CommandCredentialProvider::shell("echo plain").credential().unwrap_err();
+        assert!(err.to_string().contains("invalid json"), "{}", err);
+        let err = CommandCredentialProvider::new("/nonexistent/helper", Vec::<String>::new())
+            .credential()
+            .unwrap_err();
+        assert!(err.to_string().contains("spawn"), "{}", err);
+
+        let started = Instant::now();
+        let err = CommandCredentialProvider::shell("sleep 5")
+            .with_timeout(Duration::from_millis(100))
+            .credential()
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+}
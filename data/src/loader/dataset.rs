@@ -0,0 +1,459 @@
+//! Parquet / Arrow IPC 数据集，方便和 pandas、polars 交换数据
+//!
+//! 每一行是一根K线，多只股票、多个周期可以放在同一个文件中：
+//!
+//! | 列 | 类型 | 说明 |
+//! |----|------|------|
+//! | `symbol` | `Utf8` | 股票代码 |
+//! | `period` | `Utf8` | 周期：`day`、`week`、`{n}m` |
+//! | `timestamp` | `Timestamp(Millisecond, None)` | K线时间，不带时区的本地时间 |
+//! | `open` / `high` / `low` / `close` | `Float64` | 价格 |
+//! | `volume` | `Float64` | 成交量 |
+//! | `amount` | `Float64` | 成交额 |
+//! | 其他 | `Float64`，可为空 | 指标列，没有该指标的股票为空值 |
+//!
+//! 文件格式按扩展名区分：`.parquet` 为 Parquet，`.arrow`、`.ipc`、`.feather` 为 Arrow IPC。
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context};
+use arrow::array::{Array, ArrayRef, Float64Array, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::NaiveDateTime;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use super::binary::{date_format, parse_date, DATE_FORMATS};
+use crate::stock::GetSymbolCode;
+use crate::{Bar, Chart, ChartLoader, ChartParamter, DataError, Period};
+
+pub const SYMBOL: &str = "symbol";
+pub const PERIOD: &str = "period";
+pub const TIMESTAMP: &str = "timestamp";
+pub const VALUES: [&str; 6] = ["open", "high", "low", "close", "volume", "amount"];
+
+/// 分钟线日期格式，写在 schema 的 metadata 中，读取时还原日期字符串
+const DATE_FORMAT_KEY: &str = "trading.date_format";
+
+/// 文件格式
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FileFormat {
+    Parquet,
+    Ipc,
+}
+
+impl FileFormat {
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|v| v.to_str()) {
+            Some("parquet") => Ok(FileFormat::Parquet),
+            Some("arrow" | "ipc" | "feather") => Ok(FileFormat::Ipc),
+            _ => Err(DataError::Unsupported(format!("unknown dataset format: {}", path.display())).into()),
+        }
+    }
+}
+
+/// 一只股票的K线和对应的指标列
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub symbol: String,
+    pub chart: Chart,
+    pub columns: Vec<(String, Vec<f64>)>,
+}
+
+impl Series {
+    pub fn new(symbol: impl GetSymbolCode, chart: Chart) -> Self {
+        Self { symbol: symbol.symbol().to_string(), chart, columns: vec![] }
+    }
+
+    /// 添加指标列，长度需与K线一致
+    pub fn with_column(mut self, name: impl ToString, values: Vec<f64>) -> Self {
+        self.columns.push((name.to_string(), values));
+        self
+    }
+
+    pub fn column(&self, name: &str) -> Option<&[f64]> {
+        self.columns.iter().find(|(v, _)| v == name).map(|(_, v)| v.as_slice())
+    }
+}
+
+/// 所有序列的指标列名，按出现顺序去重
+fn column_names(series: &[Series]) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for (name, _) in series.iter().flat_map(|v| &v.columns) {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    names
+}
+
+pub fn schema(columns: &[String]) -> Schema {
+    let mut fields = vec![
+        Field::new(SYMBOL, DataType::Utf8, false),
+        Field::new(PERIOD, DataType::Utf8, false),
+        Field::new(TIMESTAMP, DataType::Timestamp(TimeUnit::Millisecond, None), false),
+    ];
+    fields.extend(VALUES.iter().map(|v| Field::new(*v, DataType::Float64, false)));
+    fields.extend(columns.iter().map(|v| Field::new(v, DataType::Float64, true)));
+    Schema::new(fields)
+}
+
+/// 转换为一个 `RecordBatch`
+pub fn to_batch(series: &[Series]) -> anyhow::Result<RecordBatch> {
+    let names = column_names(series);
+    for v in series {
+        for (name, values) in &v.columns {
+            if VALUES.contains(&name.as_str()) || [SYMBOL, PERIOD, TIMESTAMP].contains(&name.as_str()) {
+                bail!("[{}] column name conflict: {}", v.symbol, name);
+            }
+            if values.len() != v.chart.len() {
+                bail!(
+                    "[{}] column {} length {}, expected {}",
+                    v.symbol,
+                    name,
+                    values.len(),
+                    v.chart.len()
+                );
+            }
+        }
+    }
+
+    let bars = || series.iter().flat_map(|v| v.chart.iter());
+    let mut symbols = vec![];
+    let mut periods = vec![];
+    let mut timestamps = vec![];
+    for v in series {
+        let period = v.chart.period().to_string();
+        for bar in v.chart.iter() {
+            symbols.push(v.symbol.as_str());
+            periods.push(period.clone());
+            timestamps.push(parse_date(&bar.date).context(format!("[{}]", v.symbol))? * 1000);
+        }
+    }
+
+    let mut arrays: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(symbols)),
+        Arc::new(StringArray::from(periods)),
+        Arc::new(TimestampMillisecondArray::from(timestamps)),
+    ];
+    let values: [fn(&Bar) -> f64; 6] = [|v| v.open, |v| v.high, |v| v.low, |v| v.close, |v| v.volume, |v| v.amount];
+    for value in values {
+        arrays.push(Arc::new(bars().map(value).collect::<Float64Array>()));
+    }
+    for name in &names {
+        let column = series.iter().flat_map(|v| match v.column(name) {
+            Some(values) => values.iter().map(|v| Some(*v)).collect::<Vec<_>>(),
+            None => vec![None; v.chart.len()],
+        });
+        arrays.push(Arc::new(column.collect::<Float64Array>()));
+    }
+
+    let mut schema = schema(&names);
+    let minute = series.iter().find(|v| matches!(v.chart.period(), Period::Minute(_)));
+    if let Some(bar) = minute.and_then(|v| v.chart.first()) {
+        let format = DATE_FORMATS[date_format(&bar.date) as usize].to_string();
+        schema = schema.with_metadata(HashMap::from([(DATE_FORMAT_KEY.to_string(), format)]));
+    }
+    Ok(RecordBatch::try_new(Arc::new(schema), arrays)?)
+}
+
+fn column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> anyhow::Result<&'a T> {
+    let Some(column) = batch.column_by_name(name) else {
+        bail!("missing column {}", name);
+    };
+    column
+        .as_any()
+        .downcast_ref::<T>()
+        .context(format!("invalid column type {}: {}", name, column.data_type()))
+}
+
+/// 从 `RecordBatch` 还原，相同股票和周期的K线合并为一个序列
+pub fn from_batches(batches: &[RecordBatch]) -> anyhow::Result<Vec<Series>> {
+    let mut output: Vec<Series> = vec![];
+    let mut index: HashMap<(String, String), usize> = HashMap::new();
+    for batch in batches {
+        let schema = batch.schema();
+        let minute_format = schema
+            .metadata()
+            .get(DATE_FORMAT_KEY)
+            .map(String::as_str)
+            .unwrap_or(DATE_FORMATS[1]);
+        let symbols = column::<StringArray>(batch, SYMBOL)?;
+        let periods = column::<StringArray>(batch, PERIOD)?;
+        let timestamps = column::<TimestampMillisecondArray>(batch, TIMESTAMP)?;
+        let values = VALUES
+            .iter()
+            .map(|v| column::<Float64Array>(batch, v))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let known = [SYMBOL, PERIOD, TIMESTAMP];
+        let mut extra = vec![];
+        for field in schema.fields() {
+            let name = field.name().as_str();
+            if !known.contains(&name) && !VALUES.contains(&name) {
+                extra.push((name.to_string(), column::<Float64Array>(batch, name)?));
+            }
+        }
+
+        for row in 0..batch.num_rows() {
+            let (symbol, period) = (symbols.value(row), periods.value(row));
+            let key = (symbol.to_string(), period.to_string());
+            let position = match index.get(&key) {
+                Some(position) => *position,
+                None => {
+                    let period = Period::from_str(period).context(format!("[{}] row {}", symbol, row))?;
+                    let mut series = Series::new(symbol, Chart::with_period(vec![], period));
+                    series.columns = extra.iter().map(|(name, _)| (name.clone(), vec![])).collect();
+                    output.push(series);
+                    index.insert(key, output.len() - 1);
+                    output.len() - 1
+                }
+            };
+            let series = &mut output[position];
+            let format = match series.chart.period() {
+                Period::Minute(_) => minute_format,
+                _ => DATE_FORMATS[0],
+            };
+            let Some(date) = NaiveDateTime::from_timestamp_millis(timestamps.value(row)) else {
+                bail!("[{}] invalid timestamp at row {}", symbol, row);
+            };
+            let value = |column: usize| values[column].value(row);
+            let yesterday = series.chart.last().map(|v| v.close).unwrap_or_default();
+            series.chart.push(Bar {
+                date: date.format(format).to_string(),
+                open: value(0),
+                high: value(1),
+                low: value(2),
+                close: value(3),
+                volume: value(4),
+                amount: value(5),
+                yesterday,
+            });
+            for (name, array) in &extra {
+                let value = if array.is_null(row) { f64::NAN } else { array.value(row) };
+                match series.columns.iter_mut().find(|(v, _)| v == name) {
+                    Some((_, values)) => values.push(value),
+                    None => {
+                        let mut values = vec![f64::NAN; series.chart.len() - 1];
+                        values.push(value);
+                        series.columns.push((name.clone(), values));
+                    }
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+pub fn write_parquet(writer: impl std::io::Write + Send, series: &[Series]) -> anyhow::Result<()> {
+    let batch = to_batch(series)?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(props))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+pub fn write_ipc(writer: impl std::io::Write, series: &[Series]) -> anyhow::Result<()> {
+    let batch = to_batch(series)?;
+    let mut writer = arrow::ipc::writer::FileWriter::try_new(writer, &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// 按扩展名写入 Parquet 或 Arrow IPC 文件
+pub fn write(path: impl AsRef<Path>, series: &[Series]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let format = FileFormat::from_path(path)?;
+    let file = File::create(path).context(format!("create {}", path.display()))?;
+    match format {
+        FileFormat::Parquet => write_parquet(file, series),
+        FileFormat::Ipc => write_ipc(file, series),
+    }
+    .context(format!("write {}", path.display()))
+}
+
+/// 按扩展名读取 Parquet 或 Arrow IPC 文件
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Vec<Series>> {
+    let path = path.as_ref();
+    let format = FileFormat::from_path(path)?;
+    let file = File::open(path).context(format!("open {}", path.display()))?;
+    let batches = match format {
+        FileFormat::Parquet => {
+            // 批次本身不带 schema 的 metadata，使用文件中保存的 schema
+            let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+            let schema = builder.schema().clone();
+            let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+            batches
+                .into_iter()
+                .map(|v| v.with_schema(schema.clone()))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        FileFormat::Ipc => arrow::ipc::reader::FileReader::try_new(file, None)?.collect::<Result<Vec<_>, _>>()?,
+    };
+    from_batches(&batches).context(format!("read {}", path.display()))
+}
+
+/// 按修改时间缓存的文件内容
+type Cache = Arc<Mutex<Option<(SystemTime, Arc<Vec<Series>>)>>>;
+
+/// 读取数据集文件的加载器，周期需与文件中保存的一致
+///
+/// 文件内容按修改时间缓存，文件更新后下一次请求重新读取
+#[derive(Debug, Clone)]
+pub struct DatasetLoader {
+    path: PathBuf,
+    cache: Cache,
+}
+
+impl DatasetLoader {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), cache: Default::default() }
+    }
+
+    fn series(&self) -> anyhow::Result<Arc<Vec<Series>>> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|v| v.modified())
+            .context(format!("stat {}", self.path.display()))?;
+        let mut cache = self.cache.lock().unwrap();
+        if let Some((time, series)) = cache.as_ref() {
+            if *time == modified {
+                return Ok(series.clone());
+            }
+        }
+        let series = Arc::new(read(&self.path)?);
+        *cache = Some((modified, series.clone()));
+        Ok(series)
+    }
+}
+
+#[async_trait::async_trait]
+impl ChartLoader for DatasetLoader {
+    async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError> {
+        let param = param.into();
+        let loader = self.clone();
+        let series = tokio::task::spawn_blocking(move || loader.series()).await??;
+        let Some(series) = series
+            .iter()
+            .find(|v| v.symbol == param.symbol && *v.chart.period() == param.period)
+        else {
+            let message = format!("[{}] {} in {}", param.symbol, param.period, self.path.display());
+            return Err(DataError::NotFound(message));
+        };
+        let mut chart = series.chart.clone();
+        if let Some(end) = &param.end {
+            let end = super::end_bound(end);
+            let index = chart.partition_point(|v| v.date <= end);
+            chart.truncate(index);
+        }
+        chart.length(param.limit.unwrap_or(usize::MAX));
+        Ok(chart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{chart, TempDir};
+
+    fn assert_same(a: &Chart, b: &Chart) {
+        assert_eq!(a.period(), b.period());
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.date, b.date);
+            assert_eq!((a.open, a.high, a.low, a.close), (b.open, b.high, b.low, b.close));
+            assert_eq!((a.volume, a.amount), (b.volume, b.amount));
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = TempDir::new("dataset");
+
+        let day = chart(&["2023-07-10", "2023-07-11", "2023-07-12"], Period::Day);
+        let ma: Vec<f64> = vec![
+            f64::NAN,
+            (day[0].close + day[1].close) / 2.0,
+            (day[1].close + day[2].close) / 2.0,
+        ];
+        let minutes = chart(&["2023-07-12 09:35", "2023-07-12 09:40"], Period::Minute(5));
+        let series = vec![
+            Series::new("601888", day.clone()).with_column("ma2", ma.clone()),
+            Series::new("600444", day.clone()),
+            Series::new("601888", minutes.clone()),
+        ];
+
+        for name in ["charts.parquet", "charts.arrow"] {
+            let path = dir.join(name);
+            write(&path, &series).unwrap();
+            let output = read(&path).unwrap();
+            assert_eq!(output.len(), 3);
+            assert_eq!(output[0].symbol, "601888");
+            assert_same(&output[0].chart, &day);
+            assert_eq!(output[0].chart[1].yesterday, day[0].close);
+            let column = output[0].column("ma2").unwrap();
+            assert!(column[0].is_nan());
+            assert_eq!(&column[1..], &ma[1..]);
+            assert!(output[1].column("ma2").unwrap().iter().all(|v| v.is_nan()));
+            assert_same(&output[2].chart, &minutes);
+
+            let loader = DatasetLoader::new(&path);
+            let chart = loader
+                .chart(ChartParamter::day("601888").end("2023-07-11").limit(1))
+                .await
+                .unwrap();
+            assert_eq!(chart.len(), 1);
+            assert_eq!(chart[0].date, "2023-07-11");
+            let param = ChartParamter::new("601888", Period::Minute(5)).end("2023-07-12");
+            assert_eq!(loader.chart(param).await.unwrap().len(), 2);
+            assert!(loader.chart(ChartParamter::new("600444", Period::Week)).await.is_err());
+
+            // 缓存命中时不重新读取，修改时间变化后读取新内容
+            let cached = loader.series().unwrap();
+            assert!(Arc::ptr_eq(&cached, &loader.series().unwrap()));
+            write(&path, &series[1..2]).unwrap();
+            let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+            let file = File::options().write(true).open(&path).unwrap();
+            file.set_modified(modified + std::time::Duration::from_secs(1)).unwrap();
+            assert!(loader.chart(ChartParamter::day("601888")).await.unwrap_err().is_not_found());
+            assert_eq!(loader.chart(ChartParamter::day("600444")).await.unwrap().len(), 3);
+        }
+
+        let batch = to_batch(&series).unwrap();
+        let names: Vec<_> = batch.schema().fields().iter().map(|v| v.name().clone()).collect();
+        assert_eq!(
+            names,
+            [
+                "symbol",
+                "period",
+                "timestamp",
+                "open",
+                "high",
+                "low",
+                "close",
+                "volume",
+                "amount",
+                "ma2"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let series = Series::new("601888", chart(&["2023-07-10"], Period::Day)).with_column("ma", vec![]);
+        assert!(to_batch(&[series]).is_err());
+        let series = Series::new("601888", chart(&["2023-07-10"], Period::Day)).with_column("close", vec![1.0]);
+        assert!(to_batch(&[series]).is_err());
+        assert!(write("charts.csv", &[]).is_err());
+    }
+}
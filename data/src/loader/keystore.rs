@@ -0,0 +1,285 @@
+//! 加密的凭证文件
+//!
+//! 凭证以 json 序列化后使用 XChaCha20-Poly1305 加密，密钥由口令经 Argon2id 派生，盐、nonce 和
+//! KDF 参数以 base64 保存在同一个 json 文件中。每次写入都会重新生成盐和 nonce。
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::remote::{Credential, CredentialProvider};
+
+/// 读取口令的环境变量
+pub const PASSPHRASE_ENV: &str = "TRADING_CREDENTIAL_PASSPHRASE";
+
+const VERSION: u32 = 1;
+const KDF: &str = "argon2id";
+const CIPHER: &str = "xchacha20poly1305";
+const SALT_SIZE: usize = 16;
+
+/// Argon2id 参数，默认值为 OWASP 推荐的最低配置
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// 内存（KiB）
+    pub m_cost: u32,
+    /// 迭代次数
+    pub t_cost: u32,
+    /// 并行度
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    /// 参数上限，避免构造的凭证文件耗尽内存或 CPU
+    pub const MAX: KdfParams = KdfParams { m_cost: 1024 * 1024, t_cost: 16, p_cost: 16 };
+
+    fn derive(&self, passphrase: &str, salt: &[u8]) -> anyhow::Result<Zeroizing<[u8; 32]>> {
+        let max = Self::MAX;
+        if self.m_cost > max.m_cost || self.t_cost > max.t_cost || self.p_cost > max.p_cost {
+            bail!("kdf params exceed limits: {:?}, max {:?}", self, max);
+        }
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|e| anyhow::anyhow!("invalid kdf params: {}", e))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+            .map_err(|e| anyhow::anyhow!("derive key: {}", e))?;
+        Ok(key)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Kdf {
+    algorithm: String,
+    #[serde(flatten)]
+    params: KdfParams,
+    salt: String,
+}
+
+/// 加密凭证文件的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedCredential {
+    version: u32,
+    kdf: Kdf,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedCredential {
+    pub fn seal(credential: &Credential, passphrase: &str, params: KdfParams) -> anyhow::Result<Self> {
+        if passphrase.is_empty() {
+            bail!("passphrase must not be empty");
+        }
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        let key = params.derive(passphrase, &salt)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = Zeroizing::new(serde_json::to_vec(credential)?);
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| anyhow::anyhow!("encrypt credential"))?;
+        Ok(Self {
+            version: VERSION,
+            kdf: Kdf { algorithm: KDF.to_string(), params, salt: BASE64.encode(salt) },
+            cipher: CIPHER.to_string(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    pub fn open(&self, passphrase: &str) -> anyhow::Result<Credential> {
+        if self.version != VERSION {
+            bail!("unsupported credential file version: {}", self.version);
+        }
+        if self.kdf.algorithm != KDF || self.cipher != CIPHER {
+            bail!("unsupported algorithm: {} / {}", self.kdf.algorithm, self.cipher);
+        }
+        let salt = BASE64.decode(&self.kdf.salt).context("decode salt")?;
+        let nonce = BASE64.decode(&self.nonce).context("decode nonce")?;
+        if nonce.len() != 24 {
+            bail!("invalid nonce length: {}", nonce.len());
+        }
+        let ciphertext = BASE64.decode(&self.ciphertext).context("decode ciphertext")?;
+        let key = self.kdf.params.derive(passphrase, &salt)?;
+        let plaintext = XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| anyhow::anyhow!("decrypt credential: wrong passphrase or corrupted file"))?;
+        serde_json::from_slice(&plaintext).context("deserialize credential")
+    }
+
+    pub fn params(&self) -> KdfParams {
+        self.kdf.params
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).context(format!("read credential file: {}", path.display()))?;
+        serde_json::from_str(&content).context(format!("parse credential file: {}", path.display()))
+    }
+
+    /// 先写入临时文件再替换，unix 下文件权限为 0600
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let tmp = path.with_extension(format!("tmp{}", fastrand::u32(..)));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let write = || -> anyhow::Result<()> {
+            let mut file = options.open(&tmp)?;
+            serde_json::to_writer_pretty(&mut file, self)?;
+            file.sync_all()?;
+            Ok(std::fs::rename(&tmp, path)?)
+        };
+        write().map_err(|e| {
+            let _ = std::fs::remove_file(&tmp);
+            e.context(format!("write credential file: {}", path.display()))
+        })
+    }
+}
+
+/// 创建加密凭证文件，文件已存在时报错
+pub fn create_credential(
+    path: impl AsRef<Path>,
+    credential: &Credential,
+    passphrase: &str,
+    params: KdfParams,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if path.exists() {
+        bail!("credential file already exists: {}", path.display());
+    }
+    EncryptedCredential::seal(credential, passphrase, params)?.write(path)
+}
+
+/// 用新的口令重新加密，`credential` 不为空时同时替换凭证
+pub fn rotate_credential(
+    path: impl AsRef<Path>,
+    passphrase: &str,
+    new_passphrase: &str,
+    credential: Option<Credential>,
+) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let file = EncryptedCredential::read(path)?;
+    let current = file.open(passphrase)?;
+    let credential = credential.unwrap_or(current);
+    EncryptedCredential::seal(&credential, new_passphrase, file.params())?.write(path)
+}
+
+/// 校验口令，返回解密后的凭证
+pub fn verify_credential(path: impl AsRef<Path>, passphrase: &str) -> anyhow::Result<Credential> {
+    EncryptedCredential::read(path)?.open(passphrase)
+}
+
+/// 加密凭证提供者，文件不存在时返回 `None`，口令默认从 `TRADING_CREDENTIAL_PASSPHRASE` 读取
+pub struct EncryptedCredentialProvider {
+    path: PathBuf,
+    passphrase: Option<Zeroizing<String>>,
+}
+
+impl EncryptedCredentialProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), passphrase: None }
+    }
+
+    pub fn with_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.passphrase = Some(Zeroizing::new(passphrase.into()));
+        self
+    }
+
+    /// `config_dir()/credential.enc`
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        Ok(super::local::config_dir()?.join("credential.enc"))
+    }
+}
+
+impl Default for EncryptedCredentialProvider {
+    fn default() -> Self {
+        Self::new(Self::default_path().unwrap())
+    }
+}
+
+impl CredentialProvider for EncryptedCredentialProvider {
+    fn credential(&self) -> anyhow::Result<Option<Credential>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        tracing::debug!("load encrypted credential from {:?}", self.path);
+        let passphrase = match &self.passphrase {
+            Some(passphrase) => passphrase.clone(),
+            None => Zeroizing::new(std::env::var(PASSPHRASE_ENV).context(format!(
+                "{} is encrypted, set {}",
+                self.path.display(),
+                PASSPHRASE_ENV
+            ))?),
+        };
+        Ok(Some(verify_credential(&self.path, &passphrase)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// 测试中使用最小的 KDF 参数
+    const PARAMS: KdfParams = KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+
+    #[test]
+    fn encrypted_credential() {
+        let dir = TempDir::new("keystore");
+        let path = dir.join("credential.enc");
+        let credential = Credential { access_key: "ak".to_string(), secret_key: "plaintext-secret-marker".to_string() };
+
+        let missing = EncryptedCredentialProvider::new(&path).with_passphrase("secret");
+        assert!(missing.credential().unwrap().is_none());
+
+        create_credential(&path, &credential, "secret", PARAMS).unwrap();
+        assert!(create_credential(&path, &credential, "secret", PARAMS).is_err());
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            !content.contains("plaintext-secret-marker"),
+            "secret must not be stored in plaintext"
+        );
+
+        let provider = EncryptedCredentialProvider::new(&path).with_passphrase("secret");
+        assert_eq!(provider.credential().unwrap().unwrap().secret_key, "plaintext-secret-marker");
+        let err = verify_credential(&path, "wrong").unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase"));
+
+        let rotated = Credential { secret_key: "sk2".to_string(), ..credential };
+        rotate_credential(&path, "secret", "secret2", Some(rotated)).unwrap();
+        assert!(verify_credential(&path, "secret").is_err());
+        assert_eq!(verify_credential(&path, "secret2").unwrap().secret_key, "sk2");
+        assert_eq!(EncryptedCredential::read(&path).unwrap().params(), PARAMS);
+
+        let mut tampered: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let mut expensive = tampered.clone();
+        expensive["kdf"]["m_cost"] = u32::MAX.into();
+        std::fs::write(&path, expensive.to_string()).unwrap();
+        let err = verify_credential(&path, "secret2").unwrap_err();
+        assert!(err.to_string().contains("exceed limits"), "{}", err);
+        tampered["ciphertext"] = BASE64.encode(b"tampered").into();
+        std::fs::write(&path, tampered.to_string()).unwrap();
+        assert!(verify_credential(&path, "secret2").is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! 本地数据目录的版本和迁移
+//!
+//! 数据目录根部的 `layout.json` 记录目录结构的版本，没有该文件的目录视为版本 1。
+//! 每个迁移只负责把目录从 `from` 升级到 `from + 1`，先生成需要修改的文件列表，
+//! 再备份受影响的文件并执行，每一步完成后更新 `layout.json`，中断后可以从中间的版本继续。
+//!
+//! - 版本 1：`stocks.csv`、`stocks/day/{s1}/{s2}/{symbol}.csv`、`stocks/minutes/{s1}/{s2}/{symbol}/{date}.csv`
+//! - 版本 2：目录结构不变，`stocks.csv` 统一为制表符分隔
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+/// 当前支持的目录版本
+pub const LAYOUT_VERSION: u32 = 2;
+
+pub const MANIFEST: &str = "layout.json";
+
+/// 没有 `layout.json` 的目录
+const LEGACY_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// 最后一次写入的时间
+    #[serde(default)]
+    pub updated: String,
+}
+
+impl Manifest {
+    pub fn new(version: u32) -> Self {
+        Self { version, updated: chrono::Local::now().format(crate::FULL_FORMAT).to_string() }
+    }
+
+    pub fn read(dir: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        let path = dir.as_ref().join(MANIFEST);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path).context(format!("read {}", path.display()))?;
+        let manifest = serde_json::from_str(&content).context(format!("parse {}", path.display()))?;
+        Ok(Some(manifest))
+    }
+
+    pub fn write(&self, dir: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = dir.as_ref().join(MANIFEST);
+        super::writer::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// 数据目录的版本，不存在或者为空的目录为当前版本
+pub fn version(dir: impl AsRef<Path>) -> anyhow::Result<u32> {
+    let dir = dir.as_ref();
+    if let Some(manifest) = Manifest::read(dir)? {
+        return Ok(manifest.version);
+    }
+    let empty = match std::fs::read_dir(dir) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(_) => true,
+    };
+    Ok(if empty { LAYOUT_VERSION } else { LEGACY_VERSION })
+}
+
+/// 新建的数据目录写入当前版本，已有数据的目录保持不变
+pub fn init(dir: impl AsRef<Path>) -> anyhow::Result<()> {
+    let dir = dir.as_ref();
+    if Manifest::read(dir)?.is_none() && version(dir)? == LAYOUT_VERSION {
+        std::fs::create_dir_all(dir)?;
+        Manifest::new(LAYOUT_VERSION).write(dir)?;
+    }
+    Ok(())
+}
+
+/// 检查目录版本是否可以读取，比当前版本新的目录直接报错，旧版本提示迁移
+pub fn check(dir: impl AsRef<Path>) -> anyhow::Result<u32> {
+    let dir = dir.as_ref();
+    let version = version(dir)?;
+    if version == 0 || version > LAYOUT_VERSION {
+        bail!(
+            "unsupported data layout version {} in {}, this build supports up to {}",
+            version,
+            dir.display(),
+            LAYOUT_VERSION
+        );
+    }
+    if version < LAYOUT_VERSION {
+        tracing::warn!("data layout version {} in {} is outdated, run migrate", version, dir.display());
+    }
+    Ok(version)
+}
+
+/// 迁移中的一次文件修改，路径相对于数据目录
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Change {
+    Write { path: PathBuf, content: Vec<u8> },
+    Rename { from: PathBuf, to: PathBuf },
+}
+
+impl Change {
+    /// 执行前需要备份的文件
+    fn affected(&self) -> Vec<&Path> {
+        match self {
+            Change::Write { path, .. } => vec![path],
+            Change::Rename { from, to } => vec![from, to],
+        }
+    }
+
+    fn apply(&self, dir: &Path) -> anyhow::Result<()> {
+        match self {
+            Change::Write { path, content } => super::writer::write(dir.join(path), content),
+            Change::Rename { from, to } => {
+                let (from, to) = (dir.join(from), dir.join(to));
+                if let Some(parent) = to.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(&from, &to).context(format!("rename {} to {}", from.display(), to.display()))
+            }
+        }
+    }
+}
+
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    /// 根据目录中现有的文件生成修改列表，不修改任何文件
+    pub plan: fn(&Path) -> anyhow::Result<Vec<Change>>,
+}
+
+/// 按版本排列的所有迁移
+pub fn migrations() -> Vec<Migration> {
+    vec![Migration { from: 1, description: "normalize stocks.csv to tab separated", plan: tab_stocks }]
+}
+
+/// 旧版 `write_stocks_data` 以逗号分隔，读取时按制表符解析
+fn tab_stocks(dir: &Path) -> anyhow::Result<Vec<Change>> {
+    let path = dir.join("stocks.csv");
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let content = std::fs::read_to_string(&path).context(format!("read {}", path.display()))?;
+    let header = content.lines().next().unwrap_or_default();
+    if header.contains('\t') || !header.contains(',') {
+        return Ok(vec![]);
+    }
+    let lines: Vec<_> = content.lines().map(|v| v.replacen(',', "\t", 1)).collect();
+    Ok(vec![Change::Write {
+        path: PathBuf::from("stocks.csv"),
+        content: lines.join("\n").into_bytes(),
+    }])
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct MigrateOptions {
+    /// 只生成修改列表，不修改文件
+    pub dry_run: bool,
+    /// 修改前复制受影响的文件到 `backup/layout-{时间}/`
+    pub backup: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    /// 每个迁移的版本、说明和修改
+    pub steps: Vec<(u32, &'static str, Vec<Change>)>,
+    pub backup: Option<PathBuf>,
+}
+
+/// 把目录升级到当前版本。`dry_run` 时每个迁移都基于目录中现有的文件生成修改列表
+pub fn migrate(dir: impl AsRef<Path>, options: MigrateOptions) -> anyhow::Result<MigrationReport> {
+    let dir = dir.as_ref();
+    let from = check(dir)?;
+    let mut report = MigrationReport { from, to: from, ..Default::default() };
+    if from == LAYOUT_VERSION {
+        return Ok(report);
+    }
+    let backup = dir
+        .join("backup")
+        .join(format!("layout-{}", chrono::Local::now().format("%Y%m%d%H%M%S")));
+
+    for migration in migrations().into_iter().filter(|v| v.from >= from) {
+        let changes = (migration.plan)(dir).context(format!("plan migration from {}", migration.from))?;
+        if !options.dry_run {
+            if options.backup {
+                for path in changes.iter().flat_map(Change::affected) {
+                    let (source, target) = (dir.join(path), backup.join(path));
+                    if source.exists() {
+                        std::fs::create_dir_all(target.parent().unwrap())?;
+                        std::fs::copy(&source, &target).context(format!("backup {}", source.display()))?;
+                        report.backup = Some(backup.clone());
+                    }
+                }
+            }
+            for change in &changes {
+                change.apply(dir)?;
+            }
+            Manifest::new(migration.from + 1).write(dir)?;
+        }
+        report.to = migration.from + 1;
+        report.steps.push((migration.from, migration.description, changes));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn migrate_layout() {
+        let dir = TempDir::new("layout");
+        std::fs::write(dir.join("stocks.csv"), "股票代码,股票名称\n600444,国机通用").unwrap();
+        assert_eq!(check(&dir).unwrap(), 1);
+
+        let options = MigrateOptions { dry_run: true, backup: true };
+        let report = migrate(&dir, options).unwrap();
+        assert_eq!((report.from, report.to, report.steps[0].2.len()), (1, 2, 1));
+        assert_eq!(version(&dir).unwrap(), 1);
+        assert!(report.backup.is_none());
+
+        let report = migrate(&dir, MigrateOptions { dry_run: false, backup: true }).unwrap();
+        assert_eq!(version(&dir).unwrap(), LAYOUT_VERSION);
+        let content = std::fs::read_to_string(dir.join("stocks.csv")).unwrap();
+        assert_eq!(content, "股票代码\t股票名称\n600444\t国机通用");
+        let backup = std::fs::read_to_string(report.backup.unwrap().join("stocks.csv")).unwrap();
+        assert_eq!(backup, "股票代码,股票名称\n600444,国机通用");
+        let names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert!(names.iter().all(|v| !v.ends_with(".tmp")), "{:?}", names);
+        assert!(migrate(&dir, MigrateOptions::default()).unwrap().steps.is_empty());
+
+        Manifest::new(LAYOUT_VERSION + 1).write(&dir).unwrap();
+        let err = check(&dir).unwrap_err();
+        assert!(err.to_string().contains("unsupported data layout version 3"));
+        assert!(crate::LocalLoader::new(&dir).is_err());
+    }
+}
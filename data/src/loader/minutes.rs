@@ -0,0 +1,416 @@
+//! 多日分钟线
+//!
+//! 从截止日期开始按交易日倒序读取 `{date}.csv`，直到凑满 `limit` 根K线。停牌或缺失的交易日、
+//! 无法读取的文件记录在 [`MinuteReport`] 中，不中断加载。请求的周期没有存储时，
+//! 由存储的 1/5 分钟线合成 15/30/60 分钟线。
+
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+
+use anyhow::Context;
+use chrono::{NaiveDate, NaiveTime, Timelike};
+
+use super::binary::{csv_files, infer_minutes};
+use super::local::{LocalLoader, StorageFormat};
+use crate::{Bar, Chart, ChartParamter, DataError, Period};
+
+/// 上午开盘时间 9:30，单位分钟
+const MORNING_OPEN: i64 = 9 * 60 + 30;
+/// 下午开盘时间 13:00，单位分钟
+const AFTERNOON_OPEN: i64 = 13 * 60;
+/// 上午 120 分钟，下午 120 分钟
+const SESSION_MINUTES: i64 = 240;
+
+/// 加载过程中跳过的交易日
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct MinuteReport {
+    /// 存储的分钟周期，没有数据时为空
+    pub stored: Option<usize>,
+    /// 加载范围内没有数据的交易日
+    pub missing: Vec<String>,
+    /// 无法读取的交易日和错误信息
+    pub unreadable: Vec<(String, String)>,
+}
+
+impl MinuteReport {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty() && self.unreadable.is_empty()
+    }
+}
+
+impl Display for MinuteReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "missing days: {:?}", self.missing)?;
+        for (day, err) in &self.unreadable {
+            write!(f, ", unreadable {}: {}", day, err)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MinuteChart {
+    pub chart: Chart,
+    pub report: MinuteReport,
+}
+
+/// 交易时段内的分钟偏移，上午 9:30 为 0，下午 13:00 为 120
+fn session_offset(time: NaiveTime) -> i64 {
+    let minutes = (time.hour() * 60 + time.minute()) as i64;
+    if minutes <= MORNING_OPEN + 120 {
+        minutes - MORNING_OPEN
+    } else {
+        minutes - AFTERNOON_OPEN + 120
+    }
+}
+
+fn session_time(offset: i64) -> (i64, i64) {
+    let minutes = if offset <= 120 {
+        MORNING_OPEN + offset
+    } else {
+        AFTERNOON_OPEN + offset - 120
+    };
+    (minutes / 60, minutes % 60)
+}
+
+/// 把较短周期的分钟线合并为 `minutes` 分钟线，K线的时间为所在区间的结束时间，
+/// 上午和下午分别对齐，集合竞价的 9:30 合并到第一根K线中
+pub fn resample(bars: Vec<Bar>, minutes: usize) -> anyhow::Result<Vec<Bar>> {
+    let minutes = minutes as i64;
+    let mut output: Vec<Bar> = vec![];
+    for bar in bars {
+        let (day, time) = bar
+            .date
+            .get(..10)
+            .zip(bar.date.get(11..16))
+            .and_then(|(day, time)| Some((day, NaiveTime::parse_from_str(time, "%H:%M").ok()?)))
+            .context(format!("invalid minute bar date: {}", bar.date))?;
+        let offset = session_offset(time).clamp(1, SESSION_MINUTES);
+        let end = ((offset + minutes - 1) / minutes * minutes).min(SESSION_MINUTES);
+        let (hour, minute) = session_time(end);
+        let seconds = if bar.date.len() > 16 { ":00" } else { "" };
+        let date = format!("{} {:02}:{:02}{}", day, hour, minute, seconds);
+        match output.last_mut() {
+            Some(last) if last.date == date => last.merge(bar),
+            _ => output.push(Bar { date, ..bar }),
+        }
+    }
+    Ok(output)
+}
+
+/// 从存储的周期转换到请求的周期，只能由短周期合成整数倍的长周期
+fn convert(bars: Vec<Bar>, stored: usize, minutes: usize) -> anyhow::Result<Vec<Bar>> {
+    if stored == minutes {
+        return Ok(bars);
+    }
+    if !minutes.is_multiple_of(stored) {
+        return Err(DataError::Unsupported(format!("{}m from stored {}m", minutes, stored)).into());
+    }
+    resample(bars, minutes)
+}
+
+/// 加载范围，`end` 只有日期或者只到分钟时包含对应的所有K线
+struct Range<'a> {
+    start: Option<&'a str>,
+    end: Option<String>,
+}
+
+impl<'a> Range<'a> {
+    fn new(param: &'a ChartParamter) -> Result<Self, DataError> {
+        param.validate()?;
+        let end = param.end.as_deref().map(super::end_bound);
+        Ok(Self { start: param.start.as_deref(), end })
+    }
+
+    fn contains(&self, date: &str) -> bool {
+        self.start.is_none_or(|start| date >= start) && self.end.as_deref().is_none_or(|end| date <= end)
+    }
+
+    fn contains_day(&self, day: &str) -> bool {
+        self.start.is_none_or(|start| day >= start.get(..10).unwrap_or(start))
+            && self.end.as_deref().is_none_or(|end| day <= end.get(..10).unwrap_or(end))
+    }
+}
+
+/// `first` 到 `last` 之间没有加载到数据的交易日
+fn missing_days(first: &str, last: &str, loaded: &BTreeSet<String>) -> anyhow::Result<Vec<String>> {
+    let parse = |v: &str| {
+        let day = v.get(..10).context(format!("invalid date: {}", v))?;
+        NaiveDate::parse_from_str(day, crate::FORMAT).context(format!("invalid date: {}", v))
+    };
+    let (mut day, last) = (parse(first)?, parse(last)?);
+    let mut missing = vec![];
+    while day <= last {
+        let date = day.format(crate::FORMAT).to_string();
+        if crate::is_trading_day(&date)? && !loaded.contains(&date) {
+            missing.push(date);
+        }
+        day = day.succ_opt().context("date out of range")?;
+    }
+    Ok(missing)
+}
+
+impl LocalLoader {
+    /// 加载 `start` 到 `end` 之间的最后 `limit` 根分钟线。
+    /// 只指定 `start` 时加载之后所有的K线，都不指定时默认加载 5 个交易日
+    pub fn minutes(&self, param: &ChartParamter) -> anyhow::Result<MinuteChart> {
+        let Period::Minute(minutes) = param.period else {
+            return Err(DataError::Unsupported(format!("{:?} is not minute period", param.period)).into());
+        };
+        if minutes == 0 {
+            return Err(DataError::Unsupported("0m".to_string()).into());
+        }
+        let limit = match (param.limit, &param.start) {
+            (Some(limit), _) => limit,
+            (None, Some(_)) => usize::MAX,
+            (None, None) => (60 / minutes).max(1) * 4 * 5,
+        };
+        let range = Range::new(param)?;
+        let (bars, report) = match self.format() {
+            StorageFormat::Binary => self.binary_minutes(param, &range, limit, minutes)?,
+            _ => self.csv_minutes(param, &range, limit, minutes)?,
+        };
+        Ok(MinuteChart { chart: Chart::with_period(bars, Period::Minute(minutes)), report })
+    }
+
+    fn csv_minutes(
+        &self,
+        param: &ChartParamter,
+        range: &Range,
+        limit: usize,
+        minutes: usize,
+    ) -> anyhow::Result<(Vec<Bar>, MinuteReport)> {
+        let dir = self.minutes_chart_dir(&param.symbol)?;
+        let mut report = MinuteReport::default();
+        let files: Vec<_> = csv_files(&dir)?
+            .into_iter()
+            .filter_map(|path| Some((path.file_stem()?.to_str()?.to_string(), path)))
+            .filter(|(day, _)| day.len() == 10 && range.contains_day(day))
+            .collect();
+
+        let mut days = vec![];
+        let mut loaded = BTreeSet::new();
+        let mut count = 0;
+        for (day, path) in files.iter().rev() {
+            if count >= limit {
+                break;
+            }
+            let chart = std::fs::read_to_string(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| self.parse_chart(content));
+            let bars: Vec<_> = match chart {
+                Ok(chart) => chart.value().into_iter().filter(|v| range.contains(&v.date)).collect(),
+                Err(e) => {
+                    report.unreadable.push((day.clone(), format!("{:#}", e)));
+                    continue;
+                }
+            };
+            if bars.is_empty() {
+                continue;
+            }
+            let stored = infer_minutes(&bars).or(report.stored).unwrap_or(minutes);
+            report.stored = Some(stored);
+            let bars = convert(bars, stored, minutes).context(format!("[{}] {}", param.symbol, day))?;
+            count += bars.len();
+            loaded.insert(day.clone());
+            days.push(bars);
+        }
+        report.unreadable.reverse();
+
+        let mut bars: Vec<Bar> = days.into_iter().rev().flatten().collect();
+        // 每个文件的第一根K线没有昨收，使用前一个交易日的收盘价
+        for i in 1..bars.len() {
+            if bars[i].yesterday == 0.0 {
+                bars[i].yesterday = bars[i - 1].close;
+            }
+        }
+        bars.drain(..bars.len().saturating_sub(limit));
+
+        if let (Some(first), Some(last)) = (loaded.first(), loaded.last()) {
+            let first = match range.start {
+                Some(start) if count < limit => start,
+                _ => first,
+            };
+            let unreadable: BTreeSet<_> = report.unreadable.iter().map(|(day, _)| day.clone()).collect();
+            report.missing = missing_days(first, last, &loaded)?
+                .into_iter()
+                .filter(|day| !unreadable.contains(day))
+                .collect();
+        }
+        Ok((bars, report))
+    }
+
+    fn binary_minutes(
+        &self,
+        param: &ChartParamter,
+        range: &Range,
+        limit: usize,
+        minutes: usize,
+    ) -> anyhow::Result<(Vec<Bar>, MinuteReport)> {
+        let path = self.minutes_binary_path(&param.symbol)?;
+        let mut report = MinuteReport::default();
+        if !path.exists() {
+            return Ok((vec![], report));
+        }
+        // 没有指定开始日期时只读取末尾的K线，多读一组以便按 `minutes` 对齐
+        let tail = match range.start {
+            Some(_) => None,
+            None => Some(limit.saturating_add(1).saturating_mul(minutes)),
+        };
+        let chart = super::binary::load(&path, range.start, param.end.as_deref(), tail).context(format!(
+            "[{}] read minutes chart file: {}",
+            param.symbol,
+            path.display()
+        ))?;
+        let stored = match chart.period() {
+            Period::Minute(stored) => Some(*stored),
+            _ => infer_minutes(&chart),
+        };
+        let bars: Vec<_> = chart.value().into_iter().filter(|v| range.contains(&v.date)).collect();
+        let stored = stored.unwrap_or(minutes);
+        report.stored = Some(stored);
+        let mut bars = convert(bars, stored, minutes)?;
+        let complete = bars.len() <= limit;
+        bars.drain(..bars.len().saturating_sub(limit));
+
+        let loaded: BTreeSet<_> = bars.iter().filter_map(|v| v.date.get(..10)).map(str::to_string).collect();
+        if let (Some(first), Some(last)) = (loaded.first(), loaded.last()) {
+            let first = match range.start {
+                Some(start) if complete => start,
+                _ => first,
+            };
+            report.missing = missing_days(first, last, &loaded)?;
+        }
+        Ok((bars, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::csv::Column::*;
+    use crate::testing::TempDir;
+
+    /// 一个交易日的 5 分钟线，上午 9:35-11:30，下午 13:05-15:00
+    fn day_bars(day: &str) -> Vec<Bar> {
+        let offsets = (1..=48).map(|i| i * 5);
+        offsets
+            .map(|offset| {
+                let (hour, minute) = session_time(offset);
+                let mut bar = Bar::random(&format!("{} {:02}:{:02}:00", day, hour, minute), 10.0, 20.0);
+                bar.volume = 100.0;
+                bar
+            })
+            .collect()
+    }
+
+    fn write_day(dir: &Path, day: &str) -> Vec<Bar> {
+        let bars = day_bars(day);
+        let writer = crate::csv::Writer::new(&[Date, Open, High, Low, Close, Volume, Amount]);
+        std::fs::write(dir.join(format!("{}.csv", day)), writer.write_bars(&bars)).unwrap();
+        bars
+    }
+
+    #[test]
+    fn resample_session() {
+        let bars = day_bars("2023-07-12");
+        for (minutes, count, first, last) in
+            [(15, 16, "09:45", "15:00"), (30, 8, "10:00", "15:00"), (60, 4, "10:30", "15:00")]
+        {
+            let output = resample(bars.clone(), minutes).unwrap();
+            assert_eq!(output.len(), count, "{}m", minutes);
+            assert_eq!(&output[0].date[11..16], first);
+            assert_eq!(&output[count - 1].date[11..16], last);
+            assert!(output.iter().all(|v| v.volume == 100.0 * (minutes / 5) as f64));
+            assert_eq!(output[0].open, bars[0].open);
+            assert_eq!(output[0].close, bars[minutes / 5 - 1].close);
+        }
+        let output = resample(bars, 60).unwrap();
+        assert_eq!(&output[1].date[11..16], "11:30");
+        assert_eq!(&output[2].date[11..16], "14:00");
+    }
+
+    #[test]
+    fn load_minutes() {
+        let dir = TempDir::new("minutes");
+        let loader = LocalLoader::new(&dir).unwrap();
+        let path = loader.minutes_chart_dir("600000").unwrap();
+        std::fs::create_dir_all(&path).unwrap();
+        // 2023-07-12 停牌，2023-07-13 的文件损坏
+        let mut bars = write_day(&path, "2023-07-10");
+        bars.extend(write_day(&path, "2023-07-11"));
+        std::fs::write(path.join("2023-07-13.csv"), "date,open\n2023-07-13 09:35:00,abc").unwrap();
+        bars.extend(write_day(&path, "2023-07-14"));
+
+        let param = ChartParamter::new("600000", Period::Minute(5)).limit(60).end("2023-07-14");
+        let output = loader.minutes(&param).unwrap();
+        assert_eq!(output.chart.len(), 60);
+        assert_eq!(output.chart.last().unwrap().date, "2023-07-14 15:00:00");
+        assert_eq!(output.chart[0].date, "2023-07-11 14:05:00");
+        assert_eq!(output.report.stored, Some(5));
+        assert_eq!(output.report.missing, vec!["2023-07-12"]);
+        assert_eq!(output.report.unreadable.len(), 1);
+        assert_eq!(output.report.unreadable[0].0, "2023-07-13");
+        assert!(!output.report.is_complete());
+        // 跨天的第一根K线使用前一个交易日的收盘价
+        let first = output.chart.iter().position(|v| v.date.starts_with("2023-07-14")).unwrap();
+        assert_eq!(output.chart[first].yesterday, output.chart[first - 1].close);
+
+        let param = ChartParamter::new("600000", Period::Minute(5))
+            .start("2023-07-11")
+            .end("2023-07-11");
+        let output = loader.minutes(&param).unwrap();
+        assert_eq!(output.chart.len(), 48);
+        assert!(output.report.is_complete());
+
+        let param = ChartParamter::new("600000", Period::Minute(30)).start("2023-07-07");
+        let output = loader.minutes(&param).unwrap();
+        assert_eq!(output.chart.len(), 24);
+        assert_eq!(*output.chart.period(), Period::Minute(30));
+        assert_eq!(output.chart[0].date, "2023-07-10 10:00:00");
+        assert_eq!(output.chart[0].close, bars[5].close);
+        assert_eq!(output.report.missing, vec!["2023-07-07", "2023-07-12"]);
+
+        let param = ChartParamter::new("600000", Period::Minute(60)).end("2023-07-11 11:30");
+        let output = loader.minutes(&param).unwrap();
+        assert_eq!(output.chart.len(), 6);
+        assert_eq!(output.chart.last().unwrap().date, "2023-07-11 11:30:00");
+
+        let param = ChartParamter::new("600000", Period::Minute(7));
+        let err = DataError::from(loader.minutes(&param).unwrap_err());
+        assert!(matches!(err, DataError::Unsupported(_)), "{}", err);
+
+        for date in ["2023", "2023-07", "2023-07-1é", "2023-07-11 9:30", "2023-13-01"] {
+            for param in [
+                ChartParamter::new("600000", Period::Minute(5)).end(date),
+                ChartParamter::new("600000", Period::Minute(5)).start(date),
+            ] {
+                let err = DataError::from(loader.minutes(&param).unwrap_err());
+                assert!(matches!(err, DataError::Unsupported(_)), "{}: {}", date, err);
+            }
+        }
+
+        let param = ChartParamter::new("600001", Period::Minute(5));
+        assert!(loader.minutes(&param).unwrap().chart.is_empty());
+
+        std::fs::remove_file(path.join("2023-07-13.csv")).unwrap();
+        super::super::binary::convert(&dir).unwrap();
+        let binary = loader.clone().with_format(StorageFormat::Binary);
+        for param in [
+            ChartParamter::new("600000", Period::Minute(5)).limit(60).end("2023-07-14"),
+            ChartParamter::new("600000", Period::Minute(5))
+                .start("2023-07-11")
+                .end("2023-07-11"),
+            ChartParamter::new("600000", Period::Minute(30)).start("2023-07-07"),
+            ChartParamter::new("600000", Period::Minute(60)).end("2023-07-11 11:30"),
+            ChartParamter::new("600000", Period::Minute(60)).limit(3),
+        ] {
+            let (csv, binary) = (loader.minutes(&param).unwrap(), binary.minutes(&param).unwrap());
+            let dates = |chart: &Chart| chart.iter().map(|v| (v.date.clone(), v.close)).collect::<Vec<_>>();
+            assert_eq!(dates(&binary.chart), dates(&csv.chart), "{:?}", param);
+            assert_eq!(binary.report.missing, csv.report.missing, "{:?}", param);
+        }
+    }
+}
@@ -0,0 +1,491 @@
+//! 基于 SQLite 的本地存储，股票列表、K线和除权除息数据保存在同一个文件中，
+//! 既可以作为加载器使用，也可以直接用 SQL 做分析。
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Context;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use super::binary::{csv_files, infer_minutes, nested_entries, read_csv};
+use super::local::{merge_week, parse_stocks_data};
+use crate::stock::GetSymbolCode;
+use crate::{
+    Bar, BarLoader, Chart, ChartLoader, ChartParamter, DataError, MarketCurrentLoader, Period, Stock, Stocks,
+    StocksLoader, TradingDay,
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS stocks (
+    symbol TEXT PRIMARY KEY,
+    name   TEXT NOT NULL
+);
+
+-- 主键即 (symbol, period, date) 索引
+CREATE TABLE IF NOT EXISTS bars (
+    symbol TEXT NOT NULL,
+    period TEXT NOT NULL,
+    date   TEXT NOT NULL,
+    open   REAL NOT NULL,
+    high   REAL NOT NULL,
+    low    REAL NOT NULL,
+    close  REAL NOT NULL,
+    volume REAL NOT NULL,
+    amount REAL NOT NULL DEFAULT 0,
+    PRIMARY KEY (symbol, period, date)
+) WITHOUT ROWID;
+
+CREATE INDEX IF NOT EXISTS bars_period_date ON bars (period, date);
+
+CREATE TABLE IF NOT EXISTS corporate_actions (
+    symbol       TEXT NOT NULL,
+    date         TEXT NOT NULL,
+    dividend     REAL NOT NULL DEFAULT 0,
+    bonus        REAL NOT NULL DEFAULT 0,
+    transfer     REAL NOT NULL DEFAULT 0,
+    rights       REAL NOT NULL DEFAULT 0,
+    rights_price REAL NOT NULL DEFAULT 0,
+    PRIMARY KEY (symbol, date)
+) WITHOUT ROWID;
+";
+
+const BAR_COLUMNS: &str = "date, open, high, low, close, volume, amount";
+
+/// 除权除息，数值均为每股
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorporateAction {
+    pub symbol: String,
+    pub date: String,
+    /// 现金分红
+    pub dividend: f64,
+    /// 送股
+    pub bonus: f64,
+    /// 转增
+    pub transfer: f64,
+    /// 配股
+    pub rights: f64,
+    /// 配股价
+    pub rights_price: f64,
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ImportStats {
+    pub stocks: usize,
+    pub day_files: usize,
+    pub minute_files: usize,
+    pub bars: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteLoader {
+    path: Option<PathBuf>,
+    conn: Arc<Mutex<Connection>>,
+}
+
+fn bar(row: &Row) -> rusqlite::Result<Bar> {
+    Ok(Bar {
+        date: row.get(0)?,
+        open: row.get(1)?,
+        high: row.get(2)?,
+        low: row.get(3)?,
+        close: row.get(4)?,
+        volume: row.get(5)?,
+        amount: row.get(6)?,
+        yesterday: row.get::<_, Option<f64>>(7)?.unwrap_or_default(),
+    })
+}
+
+/// 日期结束条件，没有截止日期时包含所有的K线
+fn end_bound(end: Option<&str>) -> String {
+    end.map(super::end_bound).unwrap_or_else(|| "~".to_string())
+}
+
+impl SqliteLoader {
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let conn = Connection::open(&path).context(format!("open sqlite: {}", path.display()))?;
+        Self::init(Some(path), conn)
+    }
+
+    pub fn memory() -> anyhow::Result<Self> {
+        Self::init(None, Connection::open_in_memory()?)
+    }
+
+    /// 默认存储在 `data_dir()/trading.db`
+    pub fn base() -> anyhow::Result<Self> {
+        Self::open(super::local::data_dir()?.join("trading.db"))
+    }
+
+    fn init(path: Option<PathBuf>, conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch("PRAGMA journal_mode = WAL;").context("enable wal")?;
+        conn.execute_batch(SCHEMA).context("create schema")?;
+        Ok(Self { path, conn: Arc::new(Mutex::new(conn)) })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// 直接访问数据库连接，用于自定义查询
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap()
+    }
+
+    async fn call<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> anyhow::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap())).await?
+    }
+
+    pub fn save_stocks(&self, stocks: &Stocks) -> anyhow::Result<()> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("INSERT OR REPLACE INTO stocks (symbol, name) VALUES (?1, ?2)")?;
+            for stock in stocks.iter() {
+                stmt.execute(params![stock.symbol, stock.name])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 保存K线，相同日期的数据会被覆盖
+    pub fn save_chart(&self, symbol: impl GetSymbolCode, chart: &Chart) -> anyhow::Result<usize> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        Self::insert_bars(&tx, symbol.symbol(), chart.period(), chart)?;
+        tx.commit()?;
+        Ok(chart.len())
+    }
+
+    fn insert_bars(conn: &Connection, symbol: &str, period: &Period, bars: &[Bar]) -> anyhow::Result<()> {
+        let mut stmt = conn.prepare_cached(&format!(
+            "INSERT OR REPLACE INTO bars (symbol, period, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            BAR_COLUMNS
+        ))?;
+        let period = period.to_string();
+        for bar in bars {
+            stmt.execute(params![
+                symbol, period, bar.date, bar.open, bar.high, bar.low, bar.close, bar.volume, bar.amount
+            ])?;
+        }
+        Ok(())
+    }
+
+    pub fn save_actions(&self, actions: &[CorporateAction]) -> anyhow::Result<()> {
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO corporate_actions \
+                 (symbol, date, dividend, bonus, transfer, rights, rights_price) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for v in actions {
+                stmt.execute(params![
+                    v.symbol,
+                    v.date,
+                    v.dividend,
+                    v.bonus,
+                    v.transfer,
+                    v.rights,
+                    v.rights_price
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn actions(&self, symbol: impl GetSymbolCode) -> anyhow::Result<Vec<CorporateAction>> {
+        let conn = self.connection();
+        let mut stmt = conn.prepare(
+            "SELECT symbol, date, dividend, bonus, transfer, rights, rights_price \
+             FROM corporate_actions WHERE symbol = ?1 ORDER BY date",
+        )?;
+        let rows = stmt.query_map(params![symbol.symbol()], |row| {
+            Ok(CorporateAction {
+                symbol: row.get(0)?,
+                date: row.get(1)?,
+                dividend: row.get(2)?,
+                bonus: row.get(3)?,
+                transfer: row.get(4)?,
+                rights: row.get(5)?,
+                rights_price: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// 从 `LocalLoader` 的 csv 目录结构导入股票列表、日线和分钟线
+    pub fn import(&self, base_dir: impl AsRef<Path>) -> anyhow::Result<ImportStats> {
+        let base_dir = base_dir.as_ref();
+        let mut stats = ImportStats::default();
+
+        let stocks_path = base_dir.join("stocks.csv");
+        if stocks_path.exists() {
+            let content = std::fs::read_to_string(&stocks_path).context("read stocks file")?;
+            let stocks = parse_stocks_data(content)?;
+            stats.stocks = stocks.len();
+            self.save_stocks(&stocks)?;
+        }
+
+        let mut conn = self.connection();
+        let tx = conn.transaction()?;
+        for path in nested_entries(&base_dir.join("stocks/day"))? {
+            if path.extension().map(|v| v != "csv").unwrap_or(true) {
+                continue;
+            }
+            let Some(symbol) = path.file_stem().and_then(|v| v.to_str()) else {
+                continue;
+            };
+            let bars = read_csv(&path)?;
+            Self::insert_bars(&tx, symbol, &Period::Day, &bars)?;
+            stats.day_files += 1;
+            stats.bars += bars.len();
+        }
+
+        for dir in nested_entries(&base_dir.join("stocks/minutes"))? {
+            let Some(symbol) = dir.file_name().and_then(|v| v.to_str()).filter(|_| dir.is_dir()) else {
+                continue;
+            };
+            for file in csv_files(&dir)? {
+                let bars = read_csv(&file)?;
+                let period = Period::Minute(infer_minutes(&bars).unwrap_or(5));
+                Self::insert_bars(&tx, symbol, &period, &bars)?;
+                stats.minute_files += 1;
+                stats.bars += bars.len();
+            }
+        }
+        tx.commit()?;
+        Ok(stats)
+    }
+
+    /// 截止 `end` 的最后 `limit` 根K线，多取一根用于计算昨收
+    fn query_bars(
+        conn: &Connection,
+        symbol: &str,
+        period: &Period,
+        end: &str,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Bar>> {
+        let mut stmt = conn.prepare_cached(&format!(
+            "SELECT {}, NULL FROM bars WHERE symbol = ?1 AND period = ?2 AND date <= ?3 ORDER BY date DESC LIMIT ?4",
+            BAR_COLUMNS
+        ))?;
+        let limit = limit.saturating_add(1).min(i64::MAX as usize) as i64;
+        let mut bars = stmt
+            .query_map(params![symbol, period.to_string(), end, limit], bar)?
+            .collect::<Result<Vec<_>, _>>()?;
+        bars.reverse();
+
+        let mut yesterday = 0.0;
+        for bar in bars.iter_mut() {
+            bar.yesterday = yesterday;
+            yesterday = bar.close;
+        }
+        if bars.len() as i64 == limit {
+            bars.remove(0);
+        }
+        Ok(bars)
+    }
+}
+
+#[async_trait::async_trait]
+impl StocksLoader for SqliteLoader {
+    async fn stocks(&self) -> Result<Stocks, DataError> {
+        let output = self
+            .call(|conn| {
+                let mut stmt = conn.prepare("SELECT symbol, name FROM stocks ORDER BY symbol")?;
+                let rows = stmt.query_map([], |row| Ok(Stock { symbol: row.get(0)?, name: row.get(1)? }))?;
+                Ok(Stocks::new(rows.collect::<Result<_, _>>()?))
+            })
+            .await?;
+        Ok(output)
+    }
+}
+
+#[async_trait::async_trait]
+impl ChartLoader for SqliteLoader {
+    async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError> {
+        let param = param.into();
+        let output = self
+            .call(move |conn| match param.period {
+                Period::Week => {
+                    let end = match &param.end {
+                        Some(end) => Some(TradingDay::from_str(end)?.week_end_day().to_string()),
+                        None => None,
+                    };
+                    let days =
+                        Self::query_bars(conn, &param.symbol, &Period::Day, &end_bound(end.as_deref()), usize::MAX)?;
+                    let mut chart = Chart::with_period(merge_week(days), Period::Week);
+                    chart.length(param.limit.unwrap_or(usize::MAX));
+                    Ok(chart)
+                }
+                Period::Day => {
+                    let end = end_bound(param.end.as_deref());
+                    let limit = param.limit.unwrap_or(usize::MAX);
+                    Ok(Chart::new(Self::query_bars(conn, &param.symbol, &Period::Day, &end, limit)?))
+                }
+                Period::Minute(minutes) => {
+                    let end = end_bound(Some(&TradingDay::trading(param.end.clone())?.to_string()));
+                    let limit = param.limit.unwrap_or((60 / minutes) * 4 * 5);
+                    let bars = Self::query_bars(conn, &param.symbol, &param.period, &end, limit)?;
+                    Ok(Chart::with_period(bars, param.period))
+                }
+            })
+            .await?;
+        Ok(output)
+    }
+}
+
+const LATEST: &str = "
+SELECT b.date, b.open, b.high, b.low, b.close, b.volume, b.amount,
+       (SELECT p.close FROM bars p
+         WHERE p.symbol = b.symbol AND p.period = b.period AND p.date < b.date
+         ORDER BY p.date DESC LIMIT 1),
+       b.symbol
+  FROM bars b
+  JOIN (SELECT symbol, MAX(date) AS date FROM bars WHERE period = 'day' GROUP BY symbol) l
+    ON b.symbol = l.symbol AND b.date = l.date
+ WHERE b.period = 'day'";
+
+#[async_trait::async_trait]
+impl MarketCurrentLoader for SqliteLoader {
+    /// 每只股票最新的日线
+    async fn market(&self) -> Result<HashMap<String, Bar>, DataError> {
+        let output = self
+            .call(|conn| {
+                let mut stmt = conn.prepare(LATEST)?;
+                let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(8)?, bar(row)?)))?;
+                Ok(rows.collect::<Result<_, _>>()?)
+            })
+            .await?;
+        Ok(output)
+    }
+}
+
+#[async_trait::async_trait]
+impl BarLoader for SqliteLoader {
+    async fn current(&self, symbol: impl GetSymbolCode + Send) -> Result<Bar, DataError> {
+        let symbol = symbol.symbol().to_string();
+        let output = self
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!("{} AND b.symbol = ?1", LATEST))?;
+                let bar = stmt.query_row(params![symbol], bar).optional()?;
+                bar.ok_or_else(|| DataError::NotFound(symbol.clone()).into())
+            })
+            .await?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{bars, TempDir};
+
+    #[tokio::test]
+    async fn store() {
+        let loader = SqliteLoader::memory().unwrap();
+        loader
+            .save_stocks(&Stocks::new(vec![
+                Stock::new("中国中免", "601888"),
+                Stock::new("国机通用", "600444"),
+            ]))
+            .unwrap();
+        let days = ["2023-07-06", "2023-07-07", "2023-07-10", "2023-07-11", "2023-07-12"];
+        loader.save_chart("601888", &Chart::new(bars(&days))).unwrap();
+        loader.save_chart("600444", &Chart::new(bars(&days[..2]))).unwrap();
+        let minutes = ["2023-07-12 09:35:00", "2023-07-12 09:40:00", "2023-07-13 09:35:00"];
+        loader
+            .save_chart("601888", &Chart::with_period(bars(&minutes), Period::Minute(5)))
+            .unwrap();
+
+        let stocks = loader.stocks().await.unwrap();
+        assert_eq!(stocks.len(), 2);
+        assert_eq!(stocks[0].symbol, "600444");
+
+        let chart = loader
+            .chart(ChartParamter::day("601888").limit(2).end("2023-07-11"))
+            .await
+            .unwrap();
+        assert_eq!(chart.len(), 2);
+        assert_eq!(chart[0].date, "2023-07-10");
+        assert_eq!(chart[0].yesterday, 11.5);
+        assert_eq!(chart[1].date, "2023-07-11");
+
+        let chart = loader.chart(ChartParamter::day("601888")).await.unwrap();
+        assert_eq!(chart.len(), 5);
+        assert_eq!(chart[0].yesterday, 0.0);
+
+        let chart = loader.chart(ChartParamter::new("601888", Period::Week)).await.unwrap();
+        assert_eq!(chart.len(), 2);
+        assert_eq!(chart[0].date, "2023-07-03");
+        assert_eq!(chart[1].volume, 3000.0);
+
+        let param = ChartParamter::new("601888", Period::Minute(5)).end("2023-07-12");
+        let chart = loader.chart(param).await.unwrap();
+        assert_eq!(chart.len(), 2);
+        assert_eq!(*chart.period(), Period::Minute(5));
+
+        let market = loader.market().await.unwrap();
+        assert_eq!(market.len(), 2);
+        assert_eq!(market["601888"].date, "2023-07-12");
+        assert_eq!(market["601888"].yesterday, 13.5);
+        assert_eq!(market["600444"].date, "2023-07-07");
+
+        assert_eq!(loader.current("600444").await.unwrap().date, "2023-07-07");
+        assert!(loader.current("000001").await.is_err());
+
+        let action = CorporateAction {
+            symbol: "601888".to_string(),
+            date: "2023-07-10".to_string(),
+            dividend: 0.5,
+            ..Default::default()
+        };
+        loader.save_actions(std::slice::from_ref(&action)).unwrap();
+        assert_eq!(loader.actions("601888").unwrap(), vec![action]);
+
+        let count: i64 = loader
+            .connection()
+            .query_row("SELECT COUNT(*) FROM bars", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 10);
+    }
+
+    #[tokio::test]
+    async fn import() {
+        let dir = TempDir::new("sqlite");
+        std::fs::create_dir_all(dir.join("stocks/day/60/18")).unwrap();
+        std::fs::create_dir_all(dir.join("stocks/minutes/60/18/601888")).unwrap();
+        std::fs::write(dir.join("stocks.csv"), "股票代码\t股票名称\n601888\t中国中免").unwrap();
+        let writer = crate::csv::Writer::default();
+        let days = bars(&["2023-07-10", "2023-07-11"]);
+        std::fs::write(dir.join("stocks/day/60/18/601888.csv"), writer.write_bars(days.iter())).unwrap();
+        let minutes = bars(&["2023-07-11 09:35:00", "2023-07-11 09:40:00", "2023-07-11 09:45:00"]);
+        std::fs::write(
+            dir.join("stocks/minutes/60/18/601888/2023-07-11.csv"),
+            writer.write_bars(minutes.iter()),
+        )
+        .unwrap();
+
+        let loader = SqliteLoader::open(dir.join("trading.db")).unwrap();
+        let stats = loader.import(&dir).unwrap();
+        assert_eq!(stats, ImportStats { stocks: 1, day_files: 1, minute_files: 1, bars: 5 });
+
+        let loader = SqliteLoader::open(dir.join("trading.db")).unwrap();
+        assert_eq!(loader.stocks().await.unwrap().len(), 1);
+        let chart = loader.chart(ChartParamter::day("601888")).await.unwrap();
+        assert_eq!(chart.len(), 2);
+        let param = ChartParamter::new("601888", Period::Minute(5)).end("2023-07-11");
+        assert_eq!(loader.chart(param).await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,410 @@
+//! 通达信 `vipdoc` 数据文件
+//!
+//! - 日线 `{market}/lday/{market}{symbol}.day`，每条 32 字节：
+//!   `u32` 日期 `YYYYMMDD`，`u32` 开高低收（价格乘以 100，基金为 1000），`f32` 成交额，`u32` 成交量，`u32` 保留
+//! - 分钟线 `{market}/minline/*.lc1`、`{market}/fzline/*.lc5`，每条 32 字节：
+//!   `u16` 日期 `(年 - 2004) * 2048 + 月 * 100 + 日`，`u16` 自零点起的分钟数，`f32` 开高低收，`f32` 成交额，
+//!   `u32` 成交量，`u32` 保留
+//!
+//! 所有数值均为小端序。
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+use super::local::merge_week;
+use crate::csv::Writer;
+use crate::stock::GetSymbolCode;
+use crate::{Bar, Chart, ChartLoader, ChartParamter, DataError, LocalLoader, Period, TradingDay};
+
+pub const RECORD_SIZE: usize = 32;
+
+/// 交易所
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Market {
+    Sh,
+    Sz,
+    Bj,
+}
+
+impl Market {
+    pub const ALL: [Market; 3] = [Market::Sh, Market::Sz, Market::Bj];
+
+    /// 按股票代码首位推断交易所
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol.chars().next()? {
+            '5' | '6' | '9' => Some(Market::Sh),
+            '0' | '1' | '2' | '3' => Some(Market::Sz),
+            '4' | '8' => Some(Market::Bj),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Market::Sh => "sh",
+            Market::Sz => "sz",
+            Market::Bj => "bj",
+        }
+    }
+}
+
+/// 日线价格的倍数，基金（ETF、LOF）保留三位小数
+pub fn price_scale(symbol: &str) -> f64 {
+    let fund = symbol.starts_with('5') || ["15", "16", "18"].iter().any(|v| symbol.starts_with(v));
+    if fund {
+        1000.0
+    } else {
+        100.0
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn f32_at(data: &[u8], offset: usize) -> f64 {
+    f32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as f64
+}
+
+/// `f32` 价格保留三位小数，去掉单精度带来的误差
+fn round_price(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+fn records(data: &[u8]) -> anyhow::Result<std::slice::ChunksExact<'_, u8>> {
+    if !data.len().is_multiple_of(RECORD_SIZE) {
+        bail!("invalid tdx file size: {}", data.len());
+    }
+    Ok(data.chunks_exact(RECORD_SIZE))
+}
+
+/// 解析日线文件，`scale` 为价格倍数
+pub fn parse_day(data: &[u8], scale: f64) -> anyhow::Result<Vec<Bar>> {
+    let mut bars = vec![];
+    let mut yesterday = 0.0;
+    for (index, record) in records(data)?.enumerate() {
+        let date = u32_at(record, 0);
+        let (year, month, day) = (date / 10000, date / 100 % 100, date % 100);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            bail!("invalid date {} at record {}", date, index);
+        }
+        let bar = Bar {
+            date: format!("{:04}-{:02}-{:02}", year, month, day),
+            open: u32_at(record, 4) as f64 / scale,
+            high: u32_at(record, 8) as f64 / scale,
+            low: u32_at(record, 12) as f64 / scale,
+            close: u32_at(record, 16) as f64 / scale,
+            amount: f32_at(record, 20),
+            volume: u32_at(record, 24) as f64,
+            yesterday,
+        };
+        yesterday = bar.close;
+        bars.push(bar);
+    }
+    Ok(bars)
+}
+
+/// 解析 `.lc1` / `.lc5` 分钟线文件
+pub fn parse_minutes(data: &[u8]) -> anyhow::Result<Vec<Bar>> {
+    let mut bars = vec![];
+    let mut yesterday = 0.0;
+    for (index, record) in records(data)?.enumerate() {
+        let (date, minutes) = (u16_at(record, 0) as u32, u16_at(record, 2) as u32);
+        let (year, month, day) = (date / 2048 + 2004, date % 2048 / 100, date % 2048 % 100);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) || minutes >= 24 * 60 {
+            bail!("invalid time {} {} at record {}", date, minutes, index);
+        }
+        let bar = Bar {
+            date: format!("{:04}-{:02}-{:02} {:02}:{:02}:00", year, month, day, minutes / 60, minutes % 60),
+            open: round_price(f32_at(record, 4)),
+            high: round_price(f32_at(record, 8)),
+            low: round_price(f32_at(record, 12)),
+            close: round_price(f32_at(record, 16)),
+            amount: f32_at(record, 20),
+            volume: u32_at(record, 24) as f64,
+            yesterday,
+        };
+        yesterday = bar.close;
+        bars.push(bar);
+    }
+    Ok(bars)
+}
+
+/// 截止 `end` 的数据，只给出日期时包含当天所有的分钟线
+fn truncate(bars: &mut Vec<Bar>, end: Option<&str>) {
+    let Some(end) = end else {
+        return;
+    };
+    let end = super::end_bound(end);
+    let index = bars.partition_point(|v| v.date.as_str() <= end.as_str());
+    bars.truncate(index);
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct ImportStats {
+    pub day_files: usize,
+    pub minute_files: usize,
+    pub bars: usize,
+    /// 代码与所在目录的交易所不一致的文件（如上证指数 `sh000001`），不会导入
+    pub skipped: usize,
+}
+
+/// 直接读取通达信 `vipdoc` 目录的加载器，支持日线、周线、1 分钟和 5 分钟线
+#[derive(Debug, Clone)]
+pub struct TdxLoader {
+    vipdoc: PathBuf,
+}
+
+impl TdxLoader {
+    pub fn new(vipdoc: impl Into<PathBuf>) -> Self {
+        Self { vipdoc: vipdoc.into() }
+    }
+
+    fn path(&self, symbol: &str, dir: &str, ext: &str) -> anyhow::Result<PathBuf> {
+        let Some(market) = Market::from_symbol(symbol) else {
+            return Err(DataError::Unsupported(format!("unknown market: {}", symbol)).into());
+        };
+        let name = format!("{}{}.{}", market.name(), symbol, ext);
+        Ok(self.vipdoc.join(market.name()).join(dir).join(name))
+    }
+
+    pub fn day_path(&self, symbol: impl GetSymbolCode) -> anyhow::Result<PathBuf> {
+        self.path(symbol.symbol(), "lday", "day")
+    }
+
+    /// 分钟线所在的目录和扩展名，只支持 1 分钟和 5 分钟
+    fn minutes_dir(minutes: usize) -> anyhow::Result<(&'static str, &'static str)> {
+        match minutes {
+            1 => Ok(("minline", "lc1")),
+            5 => Ok(("fzline", "lc5")),
+            _ => Err(DataError::Unsupported(format!("tdx not support {} minutes", minutes)).into()),
+        }
+    }
+
+    pub fn minutes_path(&self, symbol: impl GetSymbolCode, minutes: usize) -> anyhow::Result<PathBuf> {
+        let (dir, ext) = Self::minutes_dir(minutes)?;
+        self.path(symbol.symbol(), dir, ext)
+    }
+
+    pub fn read_day(&self, symbol: impl GetSymbolCode) -> anyhow::Result<Vec<Bar>> {
+        let symbol = symbol.symbol();
+        let path = self.day_path(symbol)?;
+        let data = std::fs::read(&path).context(format!("[{}] read tdx file: {}", symbol, path.display()))?;
+        parse_day(&data, price_scale(symbol)).context(format!("parse {}", path.display()))
+    }
+
+    pub fn read_minutes(&self, symbol: impl GetSymbolCode, minutes: usize) -> anyhow::Result<Vec<Bar>> {
+        let symbol = symbol.symbol();
+        let path = self.minutes_path(symbol, minutes)?;
+        let data = std::fs::read(&path).context(format!("[{}] read tdx file: {}", symbol, path.display()))?;
+        parse_minutes(&data).context(format!("parse {}", path.display()))
+    }
+
+    fn load(&self, param: ChartParamter) -> anyhow::Result<Chart> {
+        match param.period {
+            Period::Day => {
+                let mut bars = self.read_day(&param.symbol)?;
+                truncate(&mut bars, param.end.as_deref());
+                let mut chart = Chart::new(bars);
+                chart.length(param.limit.unwrap_or(usize::MAX));
+                Ok(chart)
+            }
+            Period::Week => {
+                let mut bars = self.read_day(&param.symbol)?;
+                if let Some(end) = &param.end {
+                    truncate(&mut bars, Some(&TradingDay::from_str(end)?.week_end_day().to_string()));
+                }
+                let mut chart = Chart::with_period(merge_week(bars), Period::Week);
+                chart.length(param.limit.unwrap_or(usize::MAX));
+                Ok(chart)
+            }
+            Period::Minute(minutes) => {
+                let mut bars = self.read_minutes(&param.symbol, minutes)?;
+                truncate(&mut bars, param.end.as_deref());
+                let mut chart = Chart::with_period(bars, Period::Minute(minutes));
+                chart.length(param.limit.unwrap_or((60 / minutes) * 4 * 5));
+                Ok(chart)
+            }
+        }
+    }
+
+    /// 导入到 `LocalLoader` 的 csv 目录结构，`minutes` 为 1 或 5 时同时导入对应的分钟线
+    pub fn import(&self, loader: &LocalLoader, minutes: Option<usize>) -> anyhow::Result<ImportStats> {
+        let minutes = minutes.map(|v| Self::minutes_dir(v).map(|dir| (v, dir))).transpose()?;
+        super::layout::init(loader.base_dir())?;
+        let writer = Writer::new(super::writer::CHART_COLUMNS);
+        let mut stats = ImportStats::default();
+
+        for market in Market::ALL {
+            for (symbol, path) in self.files(market, "lday", "day", &mut stats)? {
+                let bars = self.read_day(&symbol)?;
+                let target = loader.day_chart_path(&symbol)?;
+                super::writer::write(&target, writer.write_bars(&bars))?;
+                tracing::debug!("import {} -> {}", path.display(), target.display());
+                stats.day_files += 1;
+                stats.bars += bars.len();
+            }
+
+            let Some((minutes, (dir, ext))) = minutes else {
+                continue;
+            };
+            for (symbol, path) in self.files(market, dir, ext, &mut stats)? {
+                let bars = self.read_minutes(&symbol, minutes)?;
+                let target = loader.minutes_chart_dir(&symbol)?;
+                for day in bars.chunk_by(|a, b| a.date[..10] == b.date[..10]) {
+                    let file = target.join(format!("{}.csv", &day[0].date[..10]));
+                    super::writer::write(&file, writer.write_bars(day))?;
+                }
+                tracing::debug!("import {} -> {}", path.display(), target.display());
+                stats.minute_files += 1;
+                stats.bars += bars.len();
+            }
+        }
+        loader.clone().with_format(crate::StorageFormat::Csv).rebuild_index()?;
+        Ok(stats)
+    }
+
+    /// 目录下的数据文件，返回股票代码和路径
+    fn files(
+        &self,
+        market: Market,
+        dir: &str,
+        ext: &str,
+        stats: &mut ImportStats,
+    ) -> anyhow::Result<Vec<(String, PathBuf)>> {
+        let dir = self.vipdoc.join(market.name()).join(dir);
+        let mut files = vec![];
+        if !dir.exists() {
+            return Ok(files);
+        }
+        for entry in std::fs::read_dir(&dir).context(format!("read dir: {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().map(|v| v != ext).unwrap_or(true) {
+                continue;
+            }
+            let Some(symbol) = stem_symbol(&path, market) else {
+                stats.skipped += 1;
+                continue;
+            };
+            files.push((symbol, path));
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// 从 `sh600000.day` 中取出代码，交易所不匹配时返回 `None`
+fn stem_symbol(path: &Path, market: Market) -> Option<String> {
+    let stem = path.file_stem()?.to_str()?;
+    let symbol = stem.strip_prefix(market.name())?;
+    if symbol.len() != 6 || Market::from_symbol(symbol) != Some(market) {
+        return None;
+    }
+    Some(symbol.to_string())
+}
+
+#[async_trait::async_trait]
+impl ChartLoader for TdxLoader {
+    async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError> {
+        let param = param.into();
+        let loader = self.clone();
+        Ok(tokio::task::spawn_blocking(move || loader.load(param)).await??)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tdx/vipdoc")
+    }
+
+    #[test]
+    fn market() {
+        assert_eq!(Market::from_symbol("600000"), Some(Market::Sh));
+        assert_eq!(Market::from_symbol("300750"), Some(Market::Sz));
+        assert_eq!(Market::from_symbol("830799"), Some(Market::Bj));
+        assert_eq!(Market::from_symbol(""), None);
+        assert_eq!(price_scale("510300"), 1000.0);
+        assert_eq!(price_scale("159915"), 1000.0);
+        assert_eq!(price_scale("000001"), 100.0);
+        assert!(parse_day(&[0; 31], 100.0).is_err());
+    }
+
+    #[tokio::test]
+    async fn loader() {
+        let loader = TdxLoader::new(fixtures());
+
+        let chart = loader.chart(ChartParamter::day("600000")).await.unwrap();
+        assert_eq!(chart.len(), 6);
+        assert_eq!(chart[0].date, "2023-07-10");
+        assert_eq!(
+            (chart[0].open, chart[0].high, chart[0].low, chart[0].close),
+            (7.3, 7.35, 7.25, 7.31)
+        );
+        assert_eq!((chart[0].volume, chart[0].amount), (16400000.0, 1.2e8));
+        assert_eq!(chart[1].yesterday, 7.31);
+
+        let chart = loader
+            .chart(ChartParamter::day("600000").end("2023-07-12").limit(2))
+            .await
+            .unwrap();
+        assert_eq!(chart.len(), 2);
+        assert_eq!(chart[1].date, "2023-07-12");
+
+        let chart = loader.chart(ChartParamter::day("510300")).await.unwrap();
+        assert_eq!(chart[0].close, 3.948);
+
+        let chart = loader.chart(ChartParamter::new("600000", Period::Week)).await.unwrap();
+        assert_eq!(chart.len(), 2);
+        assert_eq!(chart[0].date, "2023-07-10");
+        assert_eq!((chart[0].open, chart[0].close, chart[0].high), (7.3, 7.27, 7.4));
+
+        let param = ChartParamter::new("600000", Period::Minute(5)).end("2023-07-14");
+        let chart = loader.chart(param).await.unwrap();
+        assert_eq!(chart.len(), 3);
+        assert_eq!(chart[0].date, "2023-07-14 09:35:00");
+        assert_eq!(chart[2].date, "2023-07-14 15:00:00");
+        assert_eq!((chart[0].open, chart[0].close, chart[0].volume), (7.29, 7.3, 1100000.0));
+
+        let chart = loader.chart(ChartParamter::new("600000", Period::Minute(1))).await.unwrap();
+        assert_eq!(chart.len(), 3);
+        assert_eq!(chart[2].date, "2023-07-17 09:33:00");
+
+        assert!(loader.chart(ChartParamter::new("600000", Period::Minute(30))).await.is_err());
+        assert!(loader.chart(ChartParamter::day("600001")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn import() {
+        let dir = TempDir::new("tdx");
+        let local = LocalLoader::new(&dir).unwrap();
+        assert!(TdxLoader::new(fixtures()).import(&local, Some(15)).is_err());
+        assert!(
+            std::fs::read_dir(&dir).unwrap().next().is_none(),
+            "invalid minutes must fail before writing"
+        );
+        let stats = TdxLoader::new(fixtures()).import(&local, Some(5)).unwrap();
+        assert_eq!(stats, ImportStats { day_files: 3, minute_files: 1, bars: 14, skipped: 1 });
+        assert!(super::super::layout::Manifest::read(&dir).unwrap().is_some());
+
+        let chart = local.chart(ChartParamter::day("000001")).await.unwrap();
+        assert_eq!(chart.len(), 2);
+        assert_eq!(chart[1].yesterday, 11.5);
+        assert_eq!(chart[1].amount, 1e9);
+
+        let days = local.minutes_chart_dir("600000").unwrap();
+        assert!(days.join("2023-07-14.csv").exists());
+        assert!(days.join("2023-07-17.csv").exists());
+        let content = std::fs::read_to_string(days.join("2023-07-17.csv")).unwrap();
+        assert_eq!(crate::csv::read_bars(&content).unwrap().len(), 2);
+    }
+}
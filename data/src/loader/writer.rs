@@ -0,0 +1,333 @@
+//! 原子写入
+//!
+//! 内容先写入同目录下的临时文件，`sync` 后重命名替换目标文件，读取方不会看到写了一半的文件。
+//! 读改写的过程通过 `{文件名}.lock` 上的排他锁串行化，同步进程和应用可以同时写入同一个数据目录。
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use fs2::FileExt;
+
+use super::local::{LocalLoader, StorageFormat};
+use crate::csv::{Column, Writer};
+use crate::stock::GetSymbolCode;
+use crate::{Bar, Chart, DataError, Period};
+
+/// K线 csv 文件写入的列
+pub const CHART_COLUMNS: &[Column] = &[
+    Column::Date,
+    Column::Open,
+    Column::High,
+    Column::Low,
+    Column::Close,
+    Column::Volume,
+    Column::Amount,
+];
+
+/// 目标文件的排他锁，释放时解锁，锁文件保留
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    fn lock_path(target: &Path) -> PathBuf {
+        let name = target.file_name().map(|v| v.to_string_lossy()).unwrap_or_default();
+        target.with_file_name(format!("{}.lock", name))
+    }
+
+    fn open(target: &Path) -> anyhow::Result<File> {
+        let path = Self::lock_path(target);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).context(format!("create dir: {}", parent.display()))?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .context(format!("open lock file: {}", path.display()))
+    }
+
+    /// 阻塞直到获得锁
+    pub fn exclusive(target: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let target = target.as_ref();
+        let file = Self::open(target)?;
+        file.lock_exclusive().context(format!("lock {}", target.display()))?;
+        Ok(Self { file })
+    }
+
+    /// 锁已被占用时返回 `None`
+    pub fn try_exclusive(target: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
+        let file = Self::open(target.as_ref())?;
+        match file.try_lock_exclusive() {
+            Ok(()) => Ok(Some(Self { file })),
+            Err(e) if e.kind() == fs2::lock_contended_error().kind() => Ok(None),
+            Err(e) => Err(e).context(format!("lock {}", target.as_ref().display())),
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// 写入临时文件后重命名，不加锁，调用方需要已经持有 [`FileLock`]
+pub fn replace(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let parent = path.parent().filter(|v| !v.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent).context(format!("create dir: {}", parent.display()))?;
+    let name = path.file_name().map(|v| v.to_string_lossy()).unwrap_or_default();
+    // 以 `.` 开头并且不使用原扩展名，目录扫描时不会当作数据文件
+    let tmp = parent.join(format!(".{}.{}.tmp", name, fastrand::u32(..)));
+    let write = || -> anyhow::Result<()> {
+        let mut file = OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+        file.write_all(content.as_ref())?;
+        file.sync_all()?;
+        Ok(std::fs::rename(&tmp, path)?)
+    };
+    write().map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        e.context(format!("write {}", path.display()))
+    })
+}
+
+/// 加锁后原子写入
+pub fn write(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> anyhow::Result<()> {
+    let _lock = FileLock::exclusive(&path)?;
+    replace(path, content)
+}
+
+/// 按日期合并，日期相同时使用新的K线，结果按日期升序，返回合并结果和新增的数量
+pub fn merge_bars(existing: Vec<Bar>, bars: Vec<Bar>) -> (Vec<Bar>, usize) {
+    let mut items: BTreeMap<_, _> = existing.into_iter().map(|v| (v.date.clone(), v)).collect();
+    let count = items.len();
+    for bar in bars {
+        items.insert(bar.date.clone(), bar);
+    }
+    let added = items.len() - count;
+    (items.into_values().collect(), added)
+}
+
+impl LocalLoader {
+    /// 按 `parse_stocks_data` 读取的格式写入股票列表
+    pub fn save_stocks(&self, stocks: &crate::Stocks) -> anyhow::Result<()> {
+        super::layout::init(self.base_dir())?;
+        super::local::write_stocks_data(self.stocks_path()?, stocks)
+    }
+
+    /// 写入K线所在的文件，替换原有内容。csv 格式的分钟线只替换K线所在日期的文件
+    pub fn save_chart(&self, symbol: impl GetSymbolCode, chart: &Chart) -> anyhow::Result<()> {
+        super::layout::init(self.base_dir())?;
+        for (path, bars) in self.chart_files(symbol.symbol(), chart)? {
+            write(&path, self.encode(chart.period(), bars)?)?;
+            if *chart.period() == Period::Day {
+                self.update_index(symbol.symbol(), &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 与已有的K线按日期合并，返回新增的K线数量
+    pub fn merge_chart(&self, symbol: impl GetSymbolCode, chart: &Chart) -> anyhow::Result<usize> {
+        super::layout::init(self.base_dir())?;
+        let mut added = 0;
+        for (path, bars) in self.chart_files(symbol.symbol(), chart)? {
+            let _lock = FileLock::exclusive(&path)?;
+            let (bars, count) = merge_bars(self.decode(&path)?, bars);
+            replace(&path, self.encode(chart.period(), bars)?)?;
+            if *chart.period() == Period::Day {
+                self.update_index(symbol.symbol(), &path)?;
+            }
+            added += count;
+        }
+        Ok(added)
+    }
+
+    /// 逐个读取日线文件重建快照索引，返回股票数量。不通过 `LocalLoader` 写入的日线文件需要重建索引
+    pub fn rebuild_index(&self) -> anyhow::Result<usize> {
+        let path = self.index_path()?;
+        let _lock = FileLock::exclusive(&path)?;
+        let market: BTreeMap<_, _> = self.latest().scan_market()?.into_iter().collect();
+        replace(&path, serde_json::to_vec(&market)?)?;
+        Ok(market.len())
+    }
+
+    /// 日线文件写入后更新快照索引，索引不存在时重建
+    fn update_index(&self, symbol: &str, chart_path: &Path) -> anyhow::Result<()> {
+        let path = self.index_path()?;
+        let _lock = FileLock::exclusive(&path)?;
+        let loader = self.latest();
+        let mut market: BTreeMap<_, _> = match loader.read_index()? {
+            Some(market) => market.into_iter().collect(),
+            None => loader.scan_market()?.into_iter().collect(),
+        };
+        match loader.snapshot(chart_path)? {
+            Some(bar) => market.insert(symbol.to_string(), bar),
+            None => market.remove(symbol),
+        };
+        replace(&path, serde_json::to_vec(&market)?)
+    }
+
+    /// K线对应的文件，csv 格式的分钟线按日期拆分
+    fn chart_files(&self, symbol: &str, chart: &Chart) -> anyhow::Result<Vec<(PathBuf, Vec<Bar>)>> {
+        let bars = chart.to_vec();
+        let files = match (chart.period(), self.format()) {
+            (Period::Day, StorageFormat::Csv) => vec![(self.day_chart_path(symbol)?, bars)],
+            (Period::Day, StorageFormat::Binary) => vec![(self.day_binary_path(symbol)?, bars)],
+            (Period::Minute(_), StorageFormat::Binary) => vec![(self.minutes_binary_path(symbol)?, bars)],
+            (Period::Minute(_), StorageFormat::Csv) => {
+                let dir = self.minutes_chart_dir(symbol)?;
+                let mut days: BTreeMap<_, Vec<_>> = BTreeMap::new();
+                for bar in bars {
+                    let day = bar.date.get(..10).context(format!("invalid date: {}", bar.date))?;
+                    days.entry(format!("{}.csv", day)).or_default().push(bar);
+                }
+                days.into_iter().map(|(name, bars)| (dir.join(name), bars)).collect()
+            }
+            (Period::Week, _) => return Err(DataError::Unsupported("week chart is not stored".to_string()).into()),
+        };
+        Ok(files)
+    }
+
+    fn encode(&self, period: &Period, mut bars: Vec<Bar>) -> anyhow::Result<Vec<u8>> {
+        bars.sort();
+        Ok(match self.format() {
+            StorageFormat::Csv => Writer::new(CHART_COLUMNS).write_bars(&bars).into_bytes(),
+            StorageFormat::Binary => super::binary::encode(&Chart::with_period(bars, *period))?,
+        })
+    }
+
+    fn decode(&self, path: &Path) -> anyhow::Result<Vec<Bar>> {
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        Ok(match self.format() {
+            StorageFormat::Csv => super::binary::read_csv(path)?,
+            StorageFormat::Binary => super::binary::open(path)?.chart(None, None)?.value(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{bars, chart, TempDir};
+    use crate::{ChartLoader, ChartParamter, MarketCurrentLoader, Stock, Stocks, StocksLoader};
+
+    #[tokio::test]
+    async fn save_stocks() {
+        let dir = TempDir::new("writer");
+        let loader = LocalLoader::new(&dir).unwrap();
+        let stocks = Stocks::new(vec![Stock::new("国机通用", "600444"), Stock::new("浦发银行", "600000")]);
+        loader.save_stocks(&stocks).unwrap();
+        let output = loader.stocks().await.unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(
+            super::super::layout::version(&dir).unwrap(),
+            super::super::layout::LAYOUT_VERSION
+        );
+        assert!(dir.join(super::super::layout::MANIFEST).exists());
+        assert_eq!(output[0].symbol, "600000");
+        assert_eq!(output[1].name, "国机通用");
+    }
+
+    #[tokio::test]
+    async fn save_and_merge() {
+        for format in [StorageFormat::Csv, StorageFormat::Binary] {
+            let dir = TempDir::new("writer");
+            let loader = LocalLoader::new(&dir).unwrap().with_format(format);
+
+            let day = chart(&["2023-07-10", "2023-07-11"], Period::Day);
+            loader.save_chart("600000", &day).unwrap();
+            let update = chart(&["2023-07-11", "2023-07-12"], Period::Day);
+            assert_eq!(loader.merge_chart("600000", &update).unwrap(), 1);
+            assert_eq!(loader.merge_chart("600000", &update).unwrap(), 0);
+
+            let output = loader.chart(ChartParamter::day("600000")).await.unwrap();
+            let dates: Vec<_> = output.iter().map(|v| v.date.as_str()).collect();
+            assert_eq!(dates, ["2023-07-10", "2023-07-11", "2023-07-12"], "{:?}", format);
+            assert_eq!(output[1].close, update[0].close);
+
+            assert!(loader.index_path().unwrap().exists());
+            let market = loader.market().await.unwrap();
+            assert_eq!(market["600000"].date, "2023-07-12", "{:?}", format);
+            assert_eq!(market["600000"].yesterday, update[0].close);
+
+            let minutes = chart(
+                &["2023-07-11 14:55:00", "2023-07-11 15:00:00", "2023-07-12 09:35:00"],
+                Period::Minute(5),
+            );
+            loader.save_chart("600000", &minutes).unwrap();
+            let update = chart(&["2023-07-12 09:40:00"], Period::Minute(5));
+            assert_eq!(loader.merge_chart("600000", &update).unwrap(), 1);
+            let param = ChartParamter::new("600000", Period::Minute(5)).start("2023-07-11");
+            let output = loader.chart(param).await.unwrap();
+            assert_eq!(output.len(), 4, "{:?}", format);
+            assert_eq!(output[1].yesterday, minutes[0].close);
+
+            let week = Chart::with_period(vec![], Period::Week);
+            assert!(loader.save_chart("600000", &week).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn snapshot_index() {
+        let dir = TempDir::new("writer");
+        let loader = LocalLoader::new(&dir).unwrap();
+        let external = loader.day_chart_path("601888").unwrap();
+        std::fs::create_dir_all(external.parent().unwrap()).unwrap();
+        std::fs::write(&external, Writer::default().write_bars(&bars(&["2023-07-10"]))).unwrap();
+
+        // 索引不存在时写入前先扫描已有的文件
+        loader.save_chart("600000", &chart(&["2023-07-10"], Period::Day)).unwrap();
+        assert_eq!(loader.market().await.unwrap().len(), 2);
+
+        // 索引存在后不再读取日线文件，外部写入的文件需要重建索引
+        let other = loader.day_chart_path("600444").unwrap();
+        std::fs::create_dir_all(other.parent().unwrap()).unwrap();
+        std::fs::write(&other, Writer::default().write_bars(&bars(&["2023-07-11"]))).unwrap();
+        assert_eq!(loader.market().await.unwrap().len(), 2);
+        assert_eq!(loader.rebuild_index().unwrap(), 3);
+        let market = loader.market().await.unwrap();
+        assert_eq!(market["600444"].date, "2023-07-11");
+
+        // 指定截止日期时仍然读取日线文件
+        assert_eq!(loader.clone().with_as_of("2023-07-10").market().await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn concurrent_merge() {
+        let dir = TempDir::new("writer");
+        let loader = LocalLoader::new(&dir).unwrap();
+        let handles: Vec<_> = (1..=8)
+            .map(|day| {
+                let loader = loader.clone();
+                std::thread::spawn(move || {
+                    let chart = Chart::new(bars(&[&format!("2023-07-{:02}", day)]));
+                    loader.merge_chart("600000", &chart).unwrap()
+                })
+            })
+            .collect();
+        let added: usize = handles.into_iter().map(|v| v.join().unwrap()).sum();
+        assert_eq!(added, 8);
+        let path = loader.day_chart_path("600000").unwrap();
+        assert_eq!(super::super::binary::read_csv(&path).unwrap().len(), 8);
+
+        let lock = FileLock::exclusive(&path).unwrap();
+        assert!(FileLock::try_exclusive(&path).unwrap().is_none());
+        drop(lock);
+        assert!(FileLock::try_exclusive(&path).unwrap().is_some());
+
+        let names: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert!(names.iter().all(|v| !v.ends_with(".tmp")), "{:?}", names);
+    }
+}
@@ -0,0 +1,303 @@
+//! 实时行情推送
+//!
+//! `BarLoader::current` 和 `MarketCurrentLoader::market` 只能轮询，`QuoteStream` 以异步流的方式推送
+//! 逐笔行情和未完成的K线，按股票代码订阅和取消订阅。
+
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{Bar, Chart, Period};
+
+/// 最新成交
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    pub symbol: String,
+    pub date: String,
+    pub price: f64,
+    /// 截止当前的累计成交量
+    pub volume: f64,
+    /// 截止当前的累计成交额
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QuoteUpdate {
+    Quote(Quote),
+    /// 当前K线，`closed` 为 true 时表示该K线已经完成
+    Bar {
+        symbol: String,
+        bar: Bar,
+        closed: bool,
+    },
+}
+
+impl QuoteUpdate {
+    pub fn symbol(&self) -> &str {
+        match self {
+            QuoteUpdate::Quote(quote) => &quote.symbol,
+            QuoteUpdate::Bar { symbol, .. } => symbol,
+        }
+    }
+}
+
+pub type QuoteUpdates = Pin<Box<dyn Stream<Item = anyhow::Result<QuoteUpdate>> + Send>>;
+
+#[async_trait::async_trait]
+pub trait QuoteStream {
+    async fn subscribe(&self, symbols: &[String]) -> anyhow::Result<()>;
+
+    async fn unsubscribe(&self, symbols: &[String]) -> anyhow::Result<()>;
+
+    /// 已订阅股票的更新，订阅变化会立即反映到已经创建的流中
+    ///
+    /// 需要在 tokio 运行时中调用，不在运行时中时流只返回一个错误
+    fn updates(&self) -> QuoteUpdates;
+}
+
+/// 当前订阅的股票
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Subscriptions(Arc<RwLock<HashSet<String>>>);
+
+impl Subscriptions {
+    pub(crate) fn insert(&self, symbols: &[String]) {
+        self.0.write().unwrap().extend(symbols.iter().cloned());
+    }
+
+    pub(crate) fn remove(&self, symbols: &[String]) {
+        let mut set = self.0.write().unwrap();
+        symbols.iter().for_each(|v| {
+            set.remove(v);
+        });
+    }
+
+    pub(crate) fn contains(&self, symbol: &str) -> bool {
+        self.0.read().unwrap().contains(symbol)
+    }
+}
+
+/// 把 mpsc 接收端包装为 `QuoteUpdates`
+pub(crate) fn receiver_stream(receiver: mpsc::UnboundedReceiver<anyhow::Result<QuoteUpdate>>) -> QuoteUpdates {
+    Box::pin(futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|v| (v, receiver))
+    }))
+}
+
+/// 每根K线在原始周期内的时长
+fn period_duration(period: &Period) -> Duration {
+    match period {
+        Period::Minute(minutes) => Duration::from_secs(*minutes as u64 * 60),
+        Period::Day => Duration::from_secs(4 * 60 * 60),
+        Period::Week => Duration::from_secs(5 * 4 * 60 * 60),
+    }
+}
+
+/// 本地模拟行情，按指定速度回放保存的K线
+///
+/// 每根K线拆分为 `steps` 次更新，价格从开盘价逐步走到收盘价，最后一次推送完整的K线。
+/// 每次调用 `updates` 都会从头开始回放，回放结束后流随之结束。
+#[derive(Debug, Clone)]
+pub struct MockFeed {
+    charts: Vec<(String, Chart)>,
+    speed: f64,
+    steps: usize,
+    subscriptions: Subscriptions,
+}
+
+impl Default for MockFeed {
+    fn default() -> Self {
+        Self { charts: vec![], speed: 1.0, steps: 4, subscriptions: Subscriptions::default() }
+    }
+}
+
+impl MockFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_chart(mut self, symbol: impl ToString, chart: Chart) -> Self {
+        self.charts.push((symbol.to_string(), chart));
+        self
+    }
+
+    /// 回放速度，1.0 为实际速度，数值越大越快，不是正数时使用实际速度
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = if speed > 0.0 { speed } else { 1.0 };
+        self
+    }
+
+    pub fn with_steps(mut self, steps: usize) -> Self {
+        self.steps = steps.max(1);
+        self
+    }
+
+    /// 同一时间的K线按日期合并，依次回放所有股票
+    fn timeline(&self) -> Vec<(String, Vec<(String, Bar)>)> {
+        let mut timeline: HashMap<String, Vec<(String, Bar)>> = HashMap::new();
+        for (symbol, chart) in &self.charts {
+            for bar in chart.iter() {
+                timeline
+                    .entry(bar.date.clone())
+                    .or_default()
+                    .push((symbol.clone(), bar.clone()));
+            }
+        }
+        let mut timeline: Vec<_> = timeline.into_iter().collect();
+        timeline.sort_by(|a, b| a.0.cmp(&b.0));
+        timeline
+    }
+
+    /// 第 `step` 次更新时的K线
+    fn partial(bar: &Bar, step: usize, steps: usize) -> Bar {
+        if step == steps {
+            return bar.clone();
+        }
+        let ratio = step as f64 / steps as f64;
+        let close = bar.open + (bar.close - bar.open) * ratio;
+        Bar {
+            close,
+            high: bar.open.max(close),
+            low: bar.open.min(close),
+            volume: bar.volume * ratio,
+            amount: bar.amount * ratio,
+            ..bar.clone()
+        }
+    }
+
+    async fn replay(self, sender: mpsc::UnboundedSender<anyhow::Result<QuoteUpdate>>) {
+        let period = self.charts.first().map(|(_, v)| *v.period()).unwrap_or_default();
+        let interval = period_duration(&period).div_f64(self.speed * self.steps as f64);
+        for (_, bars) in self.timeline() {
+            for step in 1..=self.steps {
+                tokio::time::sleep(interval).await;
+                for (symbol, bar) in &bars {
+                    if !self.subscriptions.contains(symbol) {
+                        continue;
+                    }
+                    let bar = Self::partial(bar, step, self.steps);
+                    let quote = Quote {
+                        symbol: symbol.clone(),
+                        date: bar.date.clone(),
+                        price: bar.close,
+                        volume: bar.volume,
+                        amount: bar.amount,
+                    };
+                    let closed = step == self.steps;
+                    let updates = [
+                        QuoteUpdate::Quote(quote),
+                        QuoteUpdate::Bar { symbol: symbol.clone(), bar, closed },
+                    ];
+                    for update in updates {
+                        if sender.send(Ok(update)).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl QuoteStream for MockFeed {
+    async fn subscribe(&self, symbols: &[String]) -> anyhow::Result<()> {
+        self.subscriptions.insert(symbols);
+        Ok(())
+    }
+
+    async fn unsubscribe(&self, symbols: &[String]) -> anyhow::Result<()> {
+        self.subscriptions.remove(symbols);
+        Ok(())
+    }
+
+    fn updates(&self) -> QuoteUpdates {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(self.clone().replay(sender));
+            }
+            Err(e) => {
+                let _ = sender.send(Err(anyhow::anyhow!("mock feed requires a tokio runtime: {}", e)));
+            }
+        }
+        receiver_stream(receiver)
+    }
+}
+
+/// WebSocket 行情客户端和模拟推送服务
+#[cfg(feature = "websocket")]
+pub mod ws;
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    fn chart(dates: &[&str]) -> Chart {
+        let bars = dates.iter().map(|v| Bar::random(v, 10.0, 20.0)).collect();
+        Chart::with_period(bars, Period::Minute(5))
+    }
+
+    #[tokio::test]
+    async fn mock_feed() {
+        let a = chart(&["2023-07-12 09:35:00", "2023-07-12 09:40:00"]);
+        let b = chart(&["2023-07-12 09:35:00", "2023-07-12 09:40:00"]);
+        let feed = MockFeed::new()
+            .with_chart("601888", a.clone())
+            .with_chart("600444", b)
+            .with_speed(1e6);
+        feed.subscribe(&["601888".to_string()]).await.unwrap();
+
+        let updates: Vec<_> = feed.updates().map(Result::unwrap).collect().await;
+        assert_eq!(updates.len(), 2 * 4 * 2);
+        assert!(updates.iter().all(|v| v.symbol() == "601888"));
+
+        let bars: Vec<_> = updates
+            .iter()
+            .filter_map(|v| match v {
+                QuoteUpdate::Bar { bar, closed, .. } => Some((bar, *closed)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(bars[0].0.open, a[0].open);
+        assert!(!bars[0].1);
+        assert_eq!((bars[3].0.close, bars[3].0.volume, bars[3].1), (a[0].close, a[0].volume, true));
+        assert_eq!((bars[7].0.date.as_str(), bars[7].1), (a[1].date.as_str(), true));
+        match &updates[6] {
+            QuoteUpdate::Quote(quote) => assert_eq!(quote.price, a[0].close),
+            v => panic!("unexpected {:?}", v),
+        }
+
+        let mut stream = feed.clone().with_speed(1e4).updates();
+        stream.next().await.unwrap().unwrap();
+        feed.unsubscribe(&["601888".to_string()]).await.unwrap();
+        feed.subscribe(&["600444".to_string()]).await.unwrap();
+        let rest: Vec<_> = stream.map(Result::unwrap).collect().await;
+        assert!(rest.iter().any(|v| v.symbol() == "600444"));
+    }
+
+    #[test]
+    fn invalid_speed() {
+        for speed in [0.0, -1.0, f64::NAN] {
+            assert_eq!(MockFeed::new().with_speed(speed).speed, 1.0);
+        }
+        let updates = MockFeed::new().updates();
+        let first = futures::executor::block_on(updates.into_future()).0;
+        assert!(first.unwrap().is_err(), "updates outside a runtime must return an error");
+    }
+
+    #[test]
+    fn serialize() {
+        let update = QuoteUpdate::Quote(Quote { symbol: "601888".to_string(), price: 1.5, ..Default::default() });
+        let json = serde_json::to_string(&update).unwrap();
+        assert!(json.starts_with(r#"{"type":"quote","symbol":"601888""#));
+        assert_eq!(serde_json::from_str::<QuoteUpdate>(&json).unwrap(), update);
+    }
+}
@@ -0,0 +1,194 @@
+//! WebSocket 行情协议
+//!
+//! 客户端发送 `{"action":"subscribe","symbols":["601888"]}` 或 `{"action":"unsubscribe",...}`，
+//! 服务端以文本消息推送 `QuoteUpdate` 的 json。
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use super::{receiver_stream, MockFeed, QuoteStream, QuoteUpdate, QuoteUpdates, Subscriptions};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Command {
+    Subscribe { symbols: Vec<String> },
+    Unsubscribe { symbols: Vec<String> },
+}
+
+type Writer = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
+type Listener = mpsc::UnboundedSender<anyhow::Result<QuoteUpdate>>;
+
+/// WebSocket 行情客户端，连接断开后所有的更新流随之结束
+pub struct WsQuoteStream {
+    writer: tokio::sync::Mutex<Writer>,
+    subscriptions: Subscriptions,
+    /// 连接断开后为 `None`
+    listeners: Arc<Mutex<Option<Vec<Listener>>>>,
+}
+
+impl WsQuoteStream {
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let (socket, _) = tokio_tungstenite::connect_async(url)
+            .await
+            .context(format!("connect {}", url))?;
+        let (writer, mut reader) = socket.split();
+        let subscriptions = Subscriptions::default();
+        let listeners: Arc<Mutex<Option<Vec<Listener>>>> = Arc::new(Mutex::new(Some(vec![])));
+
+        let (filter, output) = (subscriptions.clone(), listeners.clone());
+        tokio::spawn(async move {
+            while let Some(message) = reader.next().await {
+                let update = match message {
+                    Ok(Message::Text(text)) => match serde_json::from_str::<QuoteUpdate>(&text) {
+                        Ok(update) if !filter.contains(update.symbol()) => continue,
+                        Ok(update) => Ok(update),
+                        Err(e) => Err(anyhow::anyhow!("invalid quote message: {}", e)),
+                    },
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => Err(anyhow::anyhow!("websocket: {}", e)),
+                };
+                let stop = update.is_err();
+                if let Some(listeners) = output.lock().unwrap().as_mut() {
+                    listeners.retain(|v| v.send(clone_result(&update)).is_ok());
+                }
+                if stop {
+                    break;
+                }
+            }
+            tracing::debug!("quote websocket closed");
+            output.lock().unwrap().take();
+        });
+
+        Ok(Self { writer: tokio::sync::Mutex::new(writer), subscriptions, listeners })
+    }
+
+    async fn send(&self, command: Command) -> anyhow::Result<()> {
+        let text = serde_json::to_string(&command)?;
+        self.writer.lock().await.send(Message::Text(text)).await.context("send command")
+    }
+}
+
+fn clone_result(update: &anyhow::Result<QuoteUpdate>) -> anyhow::Result<QuoteUpdate> {
+    match update {
+        Ok(update) => Ok(update.clone()),
+        Err(e) => Err(anyhow::anyhow!("{}", e)),
+    }
+}
+
+#[async_trait::async_trait]
+impl QuoteStream for WsQuoteStream {
+    async fn subscribe(&self, symbols: &[String]) -> anyhow::Result<()> {
+        self.subscriptions.insert(symbols);
+        self.send(Command::Subscribe { symbols: symbols.to_vec() }).await
+    }
+
+    async fn unsubscribe(&self, symbols: &[String]) -> anyhow::Result<()> {
+        self.subscriptions.remove(symbols);
+        self.send(Command::Unsubscribe { symbols: symbols.to_vec() }).await
+    }
+
+    fn updates(&self) -> QuoteUpdates {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        match self.listeners.lock().unwrap().as_mut() {
+            Some(listeners) => listeners.push(sender),
+            None => {
+                let _ = sender.send(Err(anyhow::anyhow!("quote websocket closed")));
+            }
+        }
+        receiver_stream(receiver)
+    }
+}
+
+/// 通过 WebSocket 推送模拟行情，每个连接独立订阅，收到第一次订阅后开始回放，回放结束后关闭连接
+pub async fn publish(listener: TcpListener, feed: MockFeed) -> anyhow::Result<()> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let feed = MockFeed { subscriptions: Subscriptions::default(), ..feed.clone() };
+        tokio::spawn(async move {
+            if let Err(e) = serve(stream, feed).await {
+                tracing::warn!("quote connection {}: {:?}", addr, e);
+            }
+        });
+    }
+}
+
+async fn serve(stream: TcpStream, feed: MockFeed) -> anyhow::Result<()> {
+    let socket = tokio_tungstenite::accept_async(stream).await?;
+    let (mut writer, mut reader) = socket.split();
+    let mut updates: Option<QuoteUpdates> = None;
+    loop {
+        let next = async {
+            match updates.as_mut() {
+                Some(updates) => updates.next().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            message = reader.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.into()),
+                };
+                match serde_json::from_str::<Command>(&text).context("parse command")? {
+                    Command::Subscribe { symbols } => feed.subscribe(&symbols).await?,
+                    Command::Unsubscribe { symbols } => feed.unsubscribe(&symbols).await?,
+                }
+                if updates.is_none() {
+                    updates = Some(feed.updates());
+                }
+            }
+            update = next => {
+                match update {
+                    Some(update) => writer.send(Message::Text(serde_json::to_string(&update?)?)).await?,
+                    None => return Ok(writer.close().await?),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bar, Chart, Period};
+
+    #[tokio::test]
+    async fn websocket() {
+        let bars = ["2023-07-12 09:35:00", "2023-07-12 09:40:00"]
+            .iter()
+            .map(|v| Bar::random(v, 10.0, 20.0));
+        let chart = Chart::with_period(bars.collect(), Period::Minute(5));
+        let feed = MockFeed::new()
+            .with_chart("601888", chart.clone())
+            .with_chart("600444", chart)
+            .with_speed(1e5)
+            .with_steps(2);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(publish(listener, feed));
+
+        let client = WsQuoteStream::connect(&format!("ws://{}", addr)).await.unwrap();
+        let updates = client.updates();
+        client.subscribe(&["600444".to_string()]).await.unwrap();
+
+        let updates: Vec<_> = updates.map(Result::unwrap).collect().await;
+        assert_eq!(updates.len(), 2 * 2 * 2);
+        assert!(updates.iter().all(|v| v.symbol() == "600444"));
+        assert!(matches!(updates.last(), Some(QuoteUpdate::Bar { closed: true, .. })));
+
+        let mut closed = client.updates();
+        assert!(closed.next().await.unwrap().is_err());
+    }
+}
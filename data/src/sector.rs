@@ -0,0 +1,380 @@
+//! 板块和指数成分股
+//!
+//! 行业板块、概念板块和指数（沪深300、中证500 等）统一为 [`Sector`]，成分股按 [`Membership`] 记录
+//! 纳入和剔除日期，可以还原任意一天的成分股。板块指数由成分股的K线按等权重计算。
+//!
+//! 本地文件：
+//!
+//! - `sectors.csv`：`code\tname\tkind`，`kind` 为 `industry`、`concept` 或 `index`
+//! - `sectors/{code}.csv`：`symbol\tadded\tremoved`，日期为空表示未知或仍在板块中
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::csv::{CsvError, Table};
+use crate::{deref, Bar, Chart, ChartLoader, ChartParamter, DataError, LocalLoader, Period, Stocks};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectorKind {
+    /// 行业板块
+    Industry,
+    /// 概念板块
+    Concept,
+    /// 指数成分股
+    Index,
+}
+
+impl FromStr for SectorKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "industry" => Ok(SectorKind::Industry),
+            "concept" => Ok(SectorKind::Concept),
+            "index" => Ok(SectorKind::Index),
+            _ => bail!("invalid sector kind: {}", s),
+        }
+    }
+}
+
+impl Display for SectorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SectorKind::Industry => write!(f, "industry"),
+            SectorKind::Concept => write!(f, "concept"),
+            SectorKind::Index => write!(f, "index"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Sector {
+    pub code: String,
+    pub name: String,
+    pub kind: SectorKind,
+}
+
+deref! {
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct Sectors(Vec<Sector>);
+}
+
+impl Sectors {
+    pub fn kind(&self, kind: SectorKind) -> Vec<&Sector> {
+        self.0.iter().filter(|v| v.kind == kind).collect()
+    }
+
+    pub fn parse_csv(content: &str) -> Result<Self, CsvError> {
+        let mut items = vec![];
+        for row in Table::parse(content, &["code", "name", "kind"], 1)?.rows() {
+            let code = row.required_text(0)?.to_string();
+            let kind = row.text(2).parse().map_err(|e| row.error(2, e))?;
+            items.push(Sector { code, name: row.text(1).to_string(), kind });
+        }
+        Ok(Self(items))
+    }
+
+    pub fn to_csv(&self) -> String {
+        self.0.iter().fold("code\tname\tkind".to_string(), |acc, v| {
+            format!("{}\n{}\t{}\t{}", acc, v.code, v.name, v.kind)
+        })
+    }
+}
+
+/// 在 `[added, removed)` 期间属于板块
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Membership {
+    pub symbol: String,
+    #[serde(default)]
+    pub added: Option<String>,
+    #[serde(default)]
+    pub removed: Option<String>,
+}
+
+impl Membership {
+    /// 日期可以包含时间
+    pub fn is_member(&self, date: &str) -> bool {
+        self.added.as_deref().is_none_or(|v| v <= date) && self.removed.as_deref().is_none_or(|v| date < v)
+    }
+}
+
+deref! {
+    /// 同一只股票可以有多条记录，对应多次纳入和剔除
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct Members(Vec<Membership>);
+}
+
+impl Members {
+    /// `date` 为空时返回当前的成分股
+    pub fn symbols(&self, date: Option<&str>) -> Vec<&str> {
+        let mut symbols: Vec<_> = self
+            .0
+            .iter()
+            .filter(|v| match date {
+                Some(date) => v.is_member(date),
+                None => v.removed.is_none(),
+            })
+            .map(|v| v.symbol.as_str())
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    pub fn contains(&self, symbol: &str, date: Option<&str>) -> bool {
+        self.0.iter().any(|v| {
+            v.symbol == symbol
+                && match date {
+                    Some(date) => v.is_member(date),
+                    None => v.removed.is_none(),
+                }
+        })
+    }
+
+    /// 等权重指数，每根K线为成分股当天涨跌幅的平均值，第一根K线的昨收为 `base`。
+    /// 只计算当天在板块中并且有昨收的成分股
+    pub fn index_chart(&self, charts: &HashMap<String, Chart>, base: f64) -> Chart {
+        let mut days: BTreeMap<&str, Vec<&Bar>> = BTreeMap::new();
+        let mut period = Period::Day;
+        for (symbol, chart) in charts {
+            period = *chart.period();
+            for bar in chart.iter() {
+                if bar.yesterday > 0.0 && self.contains(symbol, Some(&bar.date)) {
+                    days.entry(&bar.date).or_default().push(bar);
+                }
+            }
+        }
+
+        let mut items = vec![];
+        let mut yesterday = base;
+        for (date, bars) in days {
+            let ratio = |value: fn(&Bar) -> f64| {
+                yesterday * bars.iter().map(|v| value(v) / v.yesterday).sum::<f64>() / bars.len() as f64
+            };
+            let bar = Bar {
+                date: date.to_string(),
+                open: ratio(|v| v.open),
+                high: ratio(|v| v.high),
+                low: ratio(|v| v.low),
+                close: ratio(|v| v.close),
+                volume: bars.iter().map(|v| v.volume).sum(),
+                amount: bars.iter().map(|v| v.amount).sum(),
+                yesterday,
+            };
+            yesterday = bar.close;
+            items.push(bar);
+        }
+        Chart::with_period(items, period)
+    }
+
+    /// 加载所有曾经的成分股并计算板块指数，`param` 中的代码会被替换，找不到K线的成分股跳过
+    pub async fn chart(
+        &self,
+        loader: &(impl ChartLoader + Sync),
+        param: ChartParamter,
+        base: f64,
+    ) -> Result<Chart, DataError> {
+        let mut charts = HashMap::new();
+        for symbol in self.symbols_ever() {
+            let param = ChartParamter { symbol: symbol.to_string(), ..param.clone() };
+            match loader.chart(param).await {
+                Ok(chart) => {
+                    charts.insert(symbol.to_string(), chart);
+                }
+                Err(e) if e.is_not_found() => tracing::debug!("skip sector member {}: {}", symbol, e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.index_chart(&charts, base))
+    }
+
+    fn symbols_ever(&self) -> Vec<&str> {
+        let mut symbols: Vec<_> = self.0.iter().map(|v| v.symbol.as_str()).collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    pub fn parse_csv(content: &str) -> Result<Self, CsvError> {
+        let optional = |v: &str| (!v.is_empty()).then(|| v.to_string());
+        let mut items = vec![];
+        for row in Table::parse(content, &["symbol", "added", "removed"], 1)?.rows() {
+            items.push(Membership {
+                symbol: row.required_text(0)?.to_string(),
+                added: optional(row.text(1)),
+                removed: optional(row.text(2)),
+            });
+        }
+        Ok(Self(items))
+    }
+
+    pub fn to_csv(&self) -> String {
+        self.0.iter().fold("symbol\tadded\tremoved".to_string(), |acc, v| {
+            let added = v.added.as_deref().unwrap_or_default();
+            let removed = v.removed.as_deref().unwrap_or_default();
+            format!("{}\n{}\t{}\t{}", acc, v.symbol, added, removed)
+        })
+    }
+}
+
+impl Stocks {
+    /// 属于板块的股票，`date` 为空时按当前的成分股过滤
+    pub fn in_sector(&self, members: &Members, date: Option<&str>) -> Stocks {
+        let stocks = self.iter().filter(|v| members.contains(&v.symbol, date)).cloned().collect();
+        Stocks::new(stocks)
+    }
+}
+
+#[async_trait::async_trait]
+pub trait SectorLoader {
+    async fn sectors(&self) -> Result<Sectors, DataError>;
+
+    async fn members(&self, code: &str) -> Result<Members, DataError>;
+}
+
+#[async_trait::async_trait]
+impl<T: SectorLoader + Sync> SectorLoader for &T {
+    async fn sectors(&self) -> Result<Sectors, DataError> {
+        (*self).sectors().await
+    }
+
+    async fn members(&self, code: &str) -> Result<Members, DataError> {
+        (*self).members(code).await
+    }
+}
+
+impl LocalLoader {
+    pub fn sectors_path(&self) -> anyhow::Result<PathBuf> {
+        self.storage("sectors.csv")
+    }
+
+    pub fn members_path(&self, code: &str) -> anyhow::Result<PathBuf> {
+        if code.is_empty() || code.contains(['/', '\\', '.']) {
+            bail!(DataError::NotFound(format!("sector {}", code)));
+        }
+        self.storage(format!("sectors/{}.csv", code))
+    }
+
+    pub fn save_sectors(&self, sectors: &Sectors) -> anyhow::Result<()> {
+        crate::loader::layout::init(self.base_dir())?;
+        crate::loader::writer::write(self.sectors_path()?, sectors.to_csv())
+    }
+
+    pub fn save_members(&self, code: &str, members: &Members) -> anyhow::Result<()> {
+        crate::loader::layout::init(self.base_dir())?;
+        crate::loader::writer::write(self.members_path(code)?, members.to_csv())
+    }
+}
+
+#[async_trait::async_trait]
+impl SectorLoader for LocalLoader {
+    async fn sectors(&self) -> Result<Sectors, DataError> {
+        let path = self.sectors_path()?;
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| DataError::from(e).with_file(&path))?;
+        Sectors::parse_csv(&content).map_err(|e| DataError::from(e).with_file(&path))
+    }
+
+    async fn members(&self, code: &str) -> Result<Members, DataError> {
+        let path = self.members_path(code)?;
+        if !path.exists() {
+            return Err(DataError::NotFound(format!("sector {}", code)));
+        }
+        let content = tokio::fs::read_to_string(&path).await?;
+        Members::parse_csv(&content).map_err(|e| DataError::from(e).with_file(&path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use crate::{Stock, StocksLoader};
+
+    fn bar(date: &str, yesterday: f64, close: f64) -> Bar {
+        Bar {
+            date: date.to_string(),
+            open: yesterday,
+            high: close.max(yesterday),
+            low: close.min(yesterday),
+            close,
+            volume: 100.0,
+            amount: 0.0,
+            yesterday,
+        }
+    }
+
+    #[tokio::test]
+    async fn sector_members() {
+        let dir = TempDir::new("sector");
+        let loader = LocalLoader::new(&dir).unwrap();
+        let sectors = Sectors::new(vec![
+            Sector {
+                code: "000300".to_string(), name: "沪深300".to_string(), kind: SectorKind::Index
+            },
+            Sector {
+                code: "BK0475".to_string(), name: "银行".to_string(), kind: SectorKind::Industry
+            },
+        ]);
+        loader.save_sectors(&sectors).unwrap();
+        assert!(dir.join(crate::loader::layout::MANIFEST).exists());
+        assert_eq!(loader.sectors().await.unwrap(), sectors);
+        let err = Sectors::parse_csv("kind,code\nfoo,000300").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert!(Sectors::parse_csv("name,kind\n银行,industry").is_err());
+        assert_eq!(sectors.kind(SectorKind::Industry)[0].name, "银行");
+
+        let members =
+            Members::parse_csv("symbol\tadded\tremoved\n600000\t\t\n600001\t\t2023-07-11\n600002\t2023-07-11\t")
+                .unwrap();
+        loader.save_members("000300", &members).unwrap();
+        let members = loader.members("000300").await.unwrap();
+        assert_eq!(members.symbols(None), ["600000", "600002"]);
+        assert_eq!(members.symbols(Some("2023-07-10")), ["600000", "600001"]);
+        assert!(loader.members("000905").await.unwrap_err().is_not_found());
+        assert!(loader.members("../stocks").await.unwrap_err().is_not_found());
+
+        loader
+            .save_stocks(&Stocks::new(vec![
+                Stock::new("浦发银行", "600000"),
+                Stock::new("邯郸钢铁", "600001"),
+            ]))
+            .unwrap();
+        let stocks = loader.stocks().await.unwrap();
+        assert_eq!(stocks.in_sector(&members, None).len(), 1);
+        assert_eq!(stocks.in_sector(&members, Some("2023-07-10")).len(), 2);
+
+        let charts = HashMap::from([
+            (
+                "600000".to_string(),
+                Chart::new(vec![bar("2023-07-10", 10.0, 11.0), bar("2023-07-11", 11.0, 11.0)]),
+            ),
+            (
+                "600001".to_string(),
+                Chart::new(vec![bar("2023-07-10", 10.0, 9.0), bar("2023-07-11", 9.0, 18.0)]),
+            ),
+            (
+                "600002".to_string(),
+                Chart::new(vec![bar("2023-07-10", 10.0, 10.0), bar("2023-07-11", 10.0, 12.0)]),
+            ),
+        ]);
+        let index = members.index_chart(&charts, 1000.0);
+        assert_eq!(index.len(), 2);
+        // 2023-07-10: 600000 +10%，600001 -10%；2023-07-11: 600000 0%，600002 +20%，600001 已剔除
+        assert_eq!((index[0].yesterday, index[0].close), (1000.0, 1000.0));
+        assert!((index[1].close - 1100.0).abs() < 1e-9);
+        assert_eq!(index[1].volume, 200.0);
+
+        loader.merge_chart("600000", &charts["600000"]).unwrap();
+        // 本地 csv 的第一根K线没有昨收，不参与计算
+        let chart = members.chart(&loader, ChartParamter::day("000300"), 1000.0).await.unwrap();
+        assert_eq!(chart.len(), 1);
+        assert_eq!((chart[0].date.as_str(), chart[0].close), ("2023-07-11", 1000.0));
+    }
+}
@@ -0,0 +1,197 @@
+//! 可以作为 trait 对象使用的数据源
+//!
+//! 加载器 trait 的参数是泛型，无法作为 `dyn` 使用。`DataSource` 把它们合并为一个对象安全的 trait，
+//! 所有同时实现了四个加载器 trait 的类型都自动实现 `DataSource`，运行时可以在本地、远程、SQLite
+//! 和模拟数据之间切换，以 `Arc<dyn DataSource>` 保存。`Arc<dyn DataSource>` 也实现了加载器 trait，
+//! 可以直接传给使用泛型的代码。
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::loader::local::merge_week;
+use crate::stock::GetSymbolCode;
+use crate::{
+    Bar, BarLoader, Chart, ChartLoader, ChartParamter, DataError, MarketCurrentLoader, Period, Stock, Stocks,
+    StocksLoader, TradingDay,
+};
+
+#[async_trait::async_trait]
+pub trait DataSource: Send + Sync {
+    async fn stocks(&self) -> Result<Stocks, DataError>;
+
+    async fn chart(&self, param: ChartParamter) -> Result<Chart, DataError>;
+
+    async fn current(&self, symbol: &str) -> Result<Bar, DataError>;
+
+    async fn market(&self) -> Result<HashMap<String, Bar>, DataError>;
+}
+
+#[async_trait::async_trait]
+impl<T> DataSource for T
+where
+    T: StocksLoader + ChartLoader + BarLoader + MarketCurrentLoader + Send + Sync,
+{
+    async fn stocks(&self) -> Result<Stocks, DataError> {
+        StocksLoader::stocks(self).await
+    }
+
+    async fn chart(&self, param: ChartParamter) -> Result<Chart, DataError> {
+        ChartLoader::chart(self, param).await
+    }
+
+    async fn current(&self, symbol: &str) -> Result<Bar, DataError> {
+        BarLoader::current(self, symbol).await
+    }
+
+    async fn market(&self) -> Result<HashMap<String, Bar>, DataError> {
+        MarketCurrentLoader::market(self).await
+    }
+}
+
+#[async_trait::async_trait]
+impl StocksLoader for Arc<dyn DataSource> {
+    async fn stocks(&self) -> Result<Stocks, DataError> {
+        DataSource::stocks(self.as_ref()).await
+    }
+}
+
+#[async_trait::async_trait]
+impl ChartLoader for Arc<dyn DataSource> {
+    async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError> {
+        DataSource::chart(self.as_ref(), param.into()).await
+    }
+}
+
+#[async_trait::async_trait]
+impl BarLoader for Arc<dyn DataSource> {
+    async fn current(&self, symbol: impl GetSymbolCode + Send) -> Result<Bar, DataError> {
+        DataSource::current(self.as_ref(), symbol.symbol()).await
+    }
+}
+
+#[async_trait::async_trait]
+impl MarketCurrentLoader for Arc<dyn DataSource> {
+    async fn market(&self) -> Result<HashMap<String, Bar>, DataError> {
+        DataSource::market(self.as_ref()).await
+    }
+}
+
+/// 内存中的模拟数据源，用于测试和离线演示
+#[derive(Debug, Clone, Default)]
+pub struct MockSource {
+    stocks: Vec<Stock>,
+    charts: HashMap<(String, Period), Chart>,
+}
+
+impl MockSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_stock(mut self, stock: Stock) -> Self {
+        self.stocks.push(stock);
+        self
+    }
+
+    /// 按K线的周期保存，没有周线时由日线合并
+    pub fn with_chart(mut self, symbol: impl GetSymbolCode, chart: Chart) -> Self {
+        self.charts.insert((symbol.symbol().to_string(), *chart.period()), chart);
+        self
+    }
+
+    fn day(&self, symbol: &str) -> Option<&Chart> {
+        self.charts.get(&(symbol.to_string(), Period::Day))
+    }
+}
+
+#[async_trait::async_trait]
+impl StocksLoader for MockSource {
+    async fn stocks(&self) -> Result<Stocks, DataError> {
+        Ok(Stocks::new(self.stocks.clone()).sorted())
+    }
+}
+
+#[async_trait::async_trait]
+impl ChartLoader for MockSource {
+    async fn chart(&self, param: impl Into<ChartParamter> + Send) -> Result<Chart, DataError> {
+        let param = param.into();
+        let mut bars = match self.charts.get(&(param.symbol.clone(), param.period)) {
+            Some(chart) => chart.to_vec(),
+            None if param.period == Period::Week => match self.day(&param.symbol) {
+                Some(chart) => merge_week(chart.to_vec()),
+                None => return Err(DataError::NotFound(param.symbol)),
+            },
+            None => return Err(DataError::NotFound(format!("[{}] {}", param.symbol, param.period))),
+        };
+        if let Some(end) = &param.end {
+            let end = match param.period {
+                Period::Week => TradingDay::from_str(end)
+                    .map_err(|e| DataError::Other(e.into()))?
+                    .week_start_day()
+                    .to_string(),
+                _ => crate::loader::end_bound(end),
+            };
+            bars.retain(|v| v.date <= end);
+        }
+        let mut chart = Chart::with_period(bars, param.period);
+        chart.length(param.limit.unwrap_or(usize::MAX));
+        Ok(chart)
+    }
+}
+
+#[async_trait::async_trait]
+impl BarLoader for MockSource {
+    async fn current(&self, symbol: impl GetSymbolCode + Send) -> Result<Bar, DataError> {
+        let symbol = symbol.symbol();
+        let bar = self.day(symbol).and_then(|v| v.last());
+        bar.cloned().ok_or_else(|| DataError::NotFound(symbol.to_string()))
+    }
+}
+
+#[async_trait::async_trait]
+impl MarketCurrentLoader for MockSource {
+    async fn market(&self) -> Result<HashMap<String, Bar>, DataError> {
+        let market = self.charts.iter().filter(|((_, period), _)| *period == Period::Day);
+        Ok(market
+            .filter_map(|((symbol, _), chart)| Some((symbol.clone(), chart.last()?.clone())))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocalLoader;
+
+    /// 只依赖加载器 trait 的泛型代码
+    async fn latest_close(loader: &(impl ChartLoader + BarLoader)) -> f64 {
+        let chart = loader.chart(ChartParamter::day("601888").limit(1)).await.unwrap();
+        assert_eq!(chart[0].close, loader.current("601888").await.unwrap().close);
+        chart[0].close
+    }
+
+    #[tokio::test]
+    async fn dynamic() {
+        let bars = ["2023-07-10", "2023-07-11", "2023-07-12"]
+            .iter()
+            .map(|v| Bar::random(v, 10.0, 20.0));
+        let chart = Chart::new(bars.collect());
+        let mock = MockSource::new()
+            .with_stock(Stock::new("中国中免", "601888"))
+            .with_chart("601888", chart.clone());
+
+        let sources: Vec<Arc<dyn DataSource>> =
+            vec![Arc::new(mock), Arc::new(LocalLoader::new("/nonexistent").unwrap())];
+        let source = sources[0].as_ref();
+        assert_eq!(source.stocks().await.unwrap().len(), 1);
+        let output = source.chart(ChartParamter::day("601888").end("2023-07-11")).await.unwrap();
+        assert_eq!(output.len(), 2);
+        assert_eq!(source.chart(ChartParamter::new("601888", Period::Week)).await.unwrap().len(), 1);
+        assert_eq!(source.market().await.unwrap()["601888"].date, "2023-07-12");
+        assert!(source.current("600444").await.unwrap_err().is_not_found());
+        assert_eq!(latest_close(&sources[0]).await, chart[2].close);
+
+        assert!(sources[1].as_ref().stocks().await.is_err());
+    }
+}
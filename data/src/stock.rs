@@ -2,7 +2,7 @@ use std::fmt::{Display, Formatter};
 
 use serde::{Deserialize, Serialize};
 
-use crate::deref;
+use crate::{deref, DataError};
 
 pub trait GetSymbolCode {
     fn symbol(&self) -> &str;
@@ -69,12 +69,12 @@ impl Display for Stock {
 
 #[async_trait::async_trait]
 pub trait StocksLoader {
-    async fn stocks(&self) -> anyhow::Result<Stocks>;
+    async fn stocks(&self) -> Result<Stocks, DataError>;
 }
 
 #[async_trait::async_trait]
 impl<T: StocksLoader + std::marker::Sync> StocksLoader for &T {
-    async fn stocks(&self) -> anyhow::Result<Stocks> {
+    async fn stocks(&self) -> Result<Stocks, DataError> {
         self.stocks().await
     }
 }
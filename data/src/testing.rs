@@ -0,0 +1,63 @@
+//! 测试使用的临时目录和K线
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::{Bar, Chart, Period};
+
+/// 临时目录，释放时删除
+#[derive(Debug)]
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// 创建 `{系统临时目录}/trading-{name}-{随机数}`
+    pub(crate) fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("trading-{}-{}", name, fastrand::u64(..)));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl From<&TempDir> for PathBuf {
+    fn from(dir: &TempDir) -> Self {
+        dir.0.clone()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 第 i 根K线的开盘、最高、最低、收盘价为 `10+i`、`11+i`、`9`、`10.5+i`，成交量 1000，成交额 10000
+pub(crate) fn bars(dates: &[&str]) -> Vec<Bar> {
+    dates
+        .iter()
+        .enumerate()
+        .map(|(i, date)| {
+            let mut bar = Bar::new(date);
+            (bar.open, bar.high, bar.low, bar.close) = (10.0 + i as f64, 11.0 + i as f64, 9.0, 10.5 + i as f64);
+            bar.volume = 1000.0;
+            bar.amount = 10000.0;
+            bar
+        })
+        .collect()
+}
+
+pub(crate) fn chart(dates: &[&str], period: Period) -> Chart {
+    Chart::with_period(bars(dates), period)
+}
@@ -1,8 +1,10 @@
 pub use latestiter::*;
 pub use loopiter::*;
+pub use rolling::*;
 pub use sliceiter::*;
 
 pub mod csv;
 mod latestiter;
 mod loopiter;
+mod rolling;
 mod sliceiter;
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+/// 固定长度的滑动窗口，超出长度时移除最早的元素
+#[derive(Debug, Clone)]
+pub struct RollingWindow<T> {
+    items: VecDeque<T>,
+    period: usize,
+}
+
+impl<T> RollingWindow<T> {
+    pub fn new(period: usize) -> Self {
+        let period = period.max(1);
+        Self { items: VecDeque::with_capacity(period), period }
+    }
+
+    /// 返回被移除的元素
+    pub fn push(&mut self, value: T) -> Option<T> {
+        let removed = if self.items.len() == self.period { self.items.pop_front() } else { None };
+        self.items.push_back(value);
+        removed
+    }
+
+    pub fn period(&self) -> usize {
+        self.period
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.items.len() == self.period
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.items.front()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.items.back()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+/// 滑动窗口的累计和，每次更新 O(1)
+///
+/// 每移除 `period` 个元素重新求和一次，避免浮点误差累积
+#[derive(Debug, Clone)]
+pub struct RollingSum {
+    window: RollingWindow<f64>,
+    sum: f64,
+    removed: usize,
+}
+
+impl RollingSum {
+    pub fn new(period: usize) -> Self {
+        Self { window: RollingWindow::new(period), sum: 0.0, removed: 0 }
+    }
+
+    /// 返回窗口内的和
+    pub fn push(&mut self, value: f64) -> f64 {
+        self.sum += value;
+        if let Some(removed) = self.window.push(value) {
+            self.sum -= removed;
+            self.removed += 1;
+            if self.removed == self.window.period() {
+                self.removed = 0;
+                self.sum = self.window.iter().sum();
+            }
+        }
+        self.sum
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// 窗口未满时按已有的元素个数计算，窗口为空时为 `NaN`
+    pub fn mean(&self) -> f64 {
+        self.sum / self.window.len() as f64
+    }
+
+    pub fn window(&self) -> &RollingWindow<f64> {
+        &self.window
+    }
+
+    pub fn clear(&mut self) {
+        self.window.clear();
+        self.sum = 0.0;
+        self.removed = 0;
+    }
+}
+
+/// 滑动窗口的最大值或最小值，使用单调队列，每次更新均摊 O(1)
+#[derive(Debug, Clone)]
+pub struct RollingExtremum {
+    period: usize,
+    max: bool,
+    count: usize,
+    /// 序号和值，值单调
+    items: VecDeque<(usize, f64)>,
+}
+
+impl RollingExtremum {
+    pub fn max(period: usize) -> Self {
+        Self { period: period.max(1), max: true, count: 0, items: VecDeque::new() }
+    }
+
+    pub fn min(period: usize) -> Self {
+        Self { max: false, ..Self::max(period) }
+    }
+
+    /// 返回窗口内的最大值或最小值
+    pub fn push(&mut self, value: f64) -> f64 {
+        while let Some((_, last)) = self.items.back() {
+            let dominated = if self.max { *last <= value } else { *last >= value };
+            if !dominated {
+                break;
+            }
+            self.items.pop_back();
+        }
+        self.items.push_back((self.count, value));
+        self.count += 1;
+        while let Some((index, _)) = self.items.front() {
+            if index + self.period >= self.count {
+                break;
+            }
+            self.items.pop_front();
+        }
+        self.value()
+    }
+
+    /// 窗口为空时为 `NaN`
+    pub fn value(&self) -> f64 {
+        self.items.front().map(|v| v.1).unwrap_or(f64::NAN)
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rolling() {
+        let mut window = RollingWindow::new(3);
+        let removed: Vec<_> = (0..5).map(|v| window.push(v)).collect();
+        assert_eq!(removed, vec![None, None, None, Some(0), Some(1)]);
+        assert_eq!(window.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        let items = [3.0, 1.0, 4.0, 1.0, 5.0, 9.0, 2.0, 6.0, 5.0, 3.0, 5.0];
+        let mut sum = RollingSum::new(3);
+        let mut max = RollingExtremum::max(3);
+        let mut min = RollingExtremum::min(3);
+        for (i, value) in items.iter().enumerate() {
+            let window = &items[i.saturating_sub(2)..=i];
+            assert_eq!(sum.push(*value), window.iter().sum::<f64>());
+            assert_eq!(sum.mean(), window.iter().sum::<f64>() / window.len() as f64);
+            assert_eq!(max.push(*value), window.iter().copied().fold(f64::MIN, f64::max));
+            assert_eq!(min.push(*value), window.iter().copied().fold(f64::MAX, f64::min));
+        }
+
+        sum.clear();
+        max.clear();
+        assert!(sum.mean().is_nan() && max.value().is_nan());
+        assert_eq!(sum.push(1.5), 1.5);
+    }
+}
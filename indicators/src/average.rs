@@ -1,32 +1,39 @@
-use crate::ValueIndex;
+use crate::{Param, ParamError, Schema, ValueIndex};
 use trading_data::Bar;
-use trading_ext::LatestIterExt;
+use trading_ext::RollingSum;
 
 #[derive(Clone)]
 pub struct Indicator {
     args: Vec<usize>,
-    bars: Vec<f64>,
-    count: usize,
+    sums: Vec<RollingSum>,
 }
 
 impl Default for Indicator {
     fn default() -> Self {
-        Indicator::new(vec![3, 5, 8, 13, 21, 34, 55, 89, 144, 233])
+        Indicator::new(Indicator::schema().defaults())
     }
 }
 
 impl Indicator {
-    fn new(args: Vec<usize>) -> Self {
-        Self { count: args.iter().max_by(|a, b| a.cmp(b)).unwrap().clone(), args, bars: vec![] }
+    pub fn schema() -> Schema {
+        let params = [3, 5, 8, 13, 21, 34, 55, 89, 144, 233]
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| Param::new(format!("M{}", i + 1), "周期", v, 1, 1000))
+            .collect();
+        Schema::new("MA", "移动平均线", params).variadic()
     }
 
-    fn avg(&self, limit: &usize) -> f64 {
-        let limit = *limit;
-        if self.bars.len() >= limit {
-            self.bars.latest_iter(limit, 0).sum::<f64>() / limit as f64
-        } else {
-            self.bars.latest_iter(limit, 0).sum::<f64>() / self.bars.len() as f64
-        }
+    /// # Panics
+    ///
+    /// 参数不符合 [`schema`](Self::schema) 时 panic，参数来自外部输入时使用 [`try_new`](Self::try_new)
+    pub fn new(args: Vec<usize>) -> Self {
+        Self::try_new(args).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new(args: Vec<usize>) -> Result<Self, ParamError> {
+        Self::schema().validate(&args)?;
+        Ok(Self { sums: args.iter().map(|v| RollingSum::new(*v)).collect(), args })
     }
 }
 
@@ -48,15 +55,17 @@ impl crate::Indicator for Indicator {
     }
 
     fn next(&mut self, bar: &Bar) -> Vec<f64> {
-        self.bars.push(bar.close);
-        if self.bars.len() > self.count {
-            self.bars.remove(0);
-        }
-        self.args.iter().map(|v| self.avg(v)).collect()
+        self.sums
+            .iter_mut()
+            .map(|v| {
+                v.push(bar.close);
+                v.mean()
+            })
+            .collect()
     }
 
     fn reset(&mut self) {
-        self.bars.clear();
+        self.sums.iter_mut().for_each(RollingSum::clear);
     }
 }
 
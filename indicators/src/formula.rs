@@ -0,0 +1,329 @@
+//! 通达信公式函数
+//!
+//! 与通达信、同花顺的计算方式一致，方便核对指标数值：
+//! - 递推类函数（SMA、EMA、DMA）以第一个有效值作为初始值，输入为 `NaN` 时保持上一个值
+//! - 周期类函数（HHV、LLV、SUM、COUNT）在K线不足 N 根时按已有的K线计算，N 为 0 时从第一根K线开始计算
+//! - REF 在K线不足时为 `NaN`
+//!
+//! 每个函数都有逐根计算的结构体和按序列计算的函数，例如 [`Sma`] 和 [`sma`]。
+
+use trading_ext::{RollingExtremum, RollingSum, RollingWindow};
+
+/// 除数为 0 时返回 0，与通达信一致
+pub fn div(a: f64, b: f64) -> f64 {
+    if b == 0.0 {
+        0.0
+    } else {
+        a / b
+    }
+}
+
+/// SMA(X,N,M)：Y = (M×X + (N-M)×Y') / N
+#[derive(Debug, Clone)]
+pub struct Sma {
+    n: f64,
+    m: f64,
+    value: f64,
+}
+
+impl Sma {
+    pub fn new(n: usize, m: usize) -> Self {
+        Self { n: n as f64, m: m as f64, value: f64::NAN }
+    }
+
+    pub fn next(&mut self, x: f64) -> f64 {
+        if !x.is_nan() {
+            self.value = if self.value.is_nan() {
+                x
+            } else {
+                (self.m * x + (self.n - self.m) * self.value) / self.n
+            };
+        }
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = f64::NAN;
+    }
+}
+
+/// EMA(X,N)：Y = (2×X + (N-1)×Y') / (N+1)
+#[derive(Debug, Clone)]
+pub struct Ema(Sma);
+
+impl Ema {
+    pub fn new(n: usize) -> Self {
+        Self(Sma::new(n + 1, 2))
+    }
+
+    pub fn next(&mut self, x: f64) -> f64 {
+        self.0.next(x)
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset()
+    }
+}
+
+/// DMA(X,A)：Y = A×X + (1-A)×Y'，A 可以每根K线不同
+#[derive(Debug, Clone)]
+pub struct Dma {
+    value: f64,
+}
+
+impl Default for Dma {
+    fn default() -> Self {
+        Self { value: f64::NAN }
+    }
+}
+
+impl Dma {
+    pub fn next(&mut self, x: f64, a: f64) -> f64 {
+        if !x.is_nan() && !a.is_nan() {
+            self.value = if self.value.is_nan() { x } else { a * x + (1.0 - a) * self.value };
+        }
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        self.value = f64::NAN;
+    }
+}
+
+/// REF(X,N)：N 根K线之前的值
+#[derive(Debug, Clone)]
+pub struct Ref {
+    window: RollingWindow<f64>,
+}
+
+impl Ref {
+    pub fn new(n: usize) -> Self {
+        Self { window: RollingWindow::new(n + 1) }
+    }
+
+    pub fn next(&mut self, x: f64) -> f64 {
+        self.window.push(x);
+        match self.window.is_full() {
+            true => self.window.first().copied().unwrap_or(f64::NAN),
+            false => f64::NAN,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// HHV(X,N) 和 LLV(X,N)
+#[derive(Debug, Clone)]
+pub struct Extremum {
+    max: bool,
+    /// N 为 0 时为空
+    rolling: Option<RollingExtremum>,
+    value: f64,
+}
+
+impl Extremum {
+    fn new(n: usize, max: bool) -> Self {
+        let rolling = (n > 0).then(|| if max { RollingExtremum::max(n) } else { RollingExtremum::min(n) });
+        Self { max, rolling, value: f64::NAN }
+    }
+
+    pub fn next(&mut self, x: f64) -> f64 {
+        if let Some(rolling) = &mut self.rolling {
+            return rolling.push(x);
+        }
+        self.value = match (self.value.is_nan(), self.max) {
+            (true, _) => x,
+            (false, true) => self.value.max(x),
+            (false, false) => self.value.min(x),
+        };
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(rolling) = &mut self.rolling {
+            rolling.clear();
+        }
+        self.value = f64::NAN;
+    }
+
+    /// HHV(X,N)：N 根K线内的最大值
+    pub fn hhv(n: usize) -> Self {
+        Self::new(n, true)
+    }
+
+    /// LLV(X,N)：N 根K线内的最小值
+    pub fn llv(n: usize) -> Self {
+        Self::new(n, false)
+    }
+}
+
+/// SUM(X,N)：N 根K线的和
+#[derive(Debug, Clone)]
+pub struct Sum {
+    /// N 为 0 时为空
+    rolling: Option<RollingSum>,
+    value: f64,
+}
+
+impl Sum {
+    pub fn new(n: usize) -> Self {
+        Self { rolling: (n > 0).then(|| RollingSum::new(n)), value: 0.0 }
+    }
+
+    pub fn next(&mut self, x: f64) -> f64 {
+        match &mut self.rolling {
+            Some(rolling) => rolling.push(x),
+            None => {
+                self.value += x;
+                self.value
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(rolling) = &mut self.rolling {
+            rolling.clear();
+        }
+        self.value = 0.0;
+    }
+}
+
+/// COUNT(COND,N)：N 根K线内满足条件的次数
+#[derive(Debug, Clone)]
+pub struct Count(Sum);
+
+impl Count {
+    pub fn new(n: usize) -> Self {
+        Self(Sum::new(n))
+    }
+
+    pub fn next(&mut self, cond: bool) -> usize {
+        self.0.next(if cond { 1.0 } else { 0.0 }) as usize
+    }
+
+    pub fn reset(&mut self) {
+        self.0.reset()
+    }
+}
+
+/// CROSS(A,B)：A 从下方向上穿过 B，即上一根K线 A 不大于 B，当前 A 大于 B
+#[derive(Debug, Clone, Default)]
+pub struct Cross {
+    above: Option<bool>,
+}
+
+impl Cross {
+    pub fn next(&mut self, a: f64, b: f64) -> bool {
+        let above = a > b;
+        let cross = above && self.above == Some(false);
+        self.above = Some(above);
+        cross
+    }
+
+    pub fn reset(&mut self) {
+        self.above = None;
+    }
+}
+
+pub fn sma(x: &[f64], n: usize, m: usize) -> Vec<f64> {
+    let mut sma = Sma::new(n, m);
+    x.iter().map(|v| sma.next(*v)).collect()
+}
+
+pub fn ema(x: &[f64], n: usize) -> Vec<f64> {
+    let mut ema = Ema::new(n);
+    x.iter().map(|v| ema.next(*v)).collect()
+}
+
+pub fn dma(x: &[f64], a: &[f64]) -> Vec<f64> {
+    let mut dma = Dma::default();
+    x.iter().zip(a).map(|(x, a)| dma.next(*x, *a)).collect()
+}
+
+pub fn r#ref(x: &[f64], n: usize) -> Vec<f64> {
+    let mut r = Ref::new(n);
+    x.iter().map(|v| r.next(*v)).collect()
+}
+
+pub fn hhv(x: &[f64], n: usize) -> Vec<f64> {
+    let mut hhv = Extremum::hhv(n);
+    x.iter().map(|v| hhv.next(*v)).collect()
+}
+
+pub fn llv(x: &[f64], n: usize) -> Vec<f64> {
+    let mut llv = Extremum::llv(n);
+    x.iter().map(|v| llv.next(*v)).collect()
+}
+
+pub fn sum(x: &[f64], n: usize) -> Vec<f64> {
+    let mut sum = Sum::new(n);
+    x.iter().map(|v| sum.next(*v)).collect()
+}
+
+pub fn count(cond: &[bool], n: usize) -> Vec<usize> {
+    let mut count = Count::new(n);
+    cond.iter().map(|v| count.next(*v)).collect()
+}
+
+pub fn cross(a: &[f64], b: &[f64]) -> Vec<bool> {
+    let mut cross = Cross::default();
+    a.iter().zip(b).map(|(a, b)| cross.next(*a, *b)).collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use trading_data::Bar;
+
+    use super::*;
+
+    /// 核对指标数值使用的K线，最高价和最低价为收盘价加 0.3 和减 0.4
+    pub(crate) fn golden_bars() -> Vec<Bar> {
+        let close = [
+            10.0, 10.5, 10.2, 10.8, 11.5, 11.2, 11.0, 11.8, 12.4, 12.1, 11.6, 11.9, 12.8, 13.3, 13.0, 12.6, 12.2, 12.9,
+            13.6, 14.1,
+        ];
+        close
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let mut bar = Bar::new(format!("2023-07-{:02}", i + 1).as_str());
+                (bar.open, bar.high, bar.low, bar.close) = (*v, v + 0.3, v - 0.4, *v);
+                bar
+            })
+            .collect()
+    }
+
+    /// 按两位小数比较，`NaN` 与 `NaN` 相等
+    pub(crate) fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            let same = (a.is_nan() && e.is_nan()) || (a - e).abs() <= 0.005 + 1e-9;
+            assert!(same, "index {}: {} != {}\n{:?}", i, a, e, actual);
+        }
+    }
+
+    const X: [f64; 8] = [10.0, 11.0, 9.0, 12.0, 13.0, 8.0, 14.0, 15.0];
+
+    #[test]
+    fn primitives() {
+        assert_close(&sma(&X, 3, 1), &[10.0, 10.33, 9.89, 10.59, 11.40, 10.26, 11.51, 12.67]);
+        assert_close(&ema(&X, 3), &[10.0, 10.5, 9.75, 10.88, 11.94, 9.97, 11.98, 13.49]);
+        assert_close(&dma(&X, &[0.5; 8]), &[10.0, 10.5, 9.75, 10.88, 11.94, 9.97, 11.98, 13.49]);
+        assert_close(&r#ref(&X, 2), &[f64::NAN, f64::NAN, 10.0, 11.0, 9.0, 12.0, 13.0, 8.0]);
+        assert_close(&r#ref(&X, 0), &X);
+        assert_close(&hhv(&X, 3), &[10.0, 11.0, 11.0, 12.0, 13.0, 13.0, 14.0, 15.0]);
+        assert_close(&llv(&X, 3), &[10.0, 10.0, 9.0, 9.0, 9.0, 8.0, 8.0, 8.0]);
+        assert_close(&llv(&X, 0), &[10.0, 10.0, 9.0, 9.0, 9.0, 8.0, 8.0, 8.0]);
+        assert_close(&hhv(&X, 0)[..5], &[10.0, 11.0, 11.0, 12.0, 13.0]);
+        assert_close(&sum(&X, 3), &[10.0, 21.0, 30.0, 32.0, 34.0, 33.0, 35.0, 37.0]);
+        assert_close(&sum(&X, 0)[5..], &[63.0, 77.0, 92.0]);
+
+        let up: Vec<_> = X.windows(2).map(|v| v[1] > v[0]).collect();
+        assert_eq!(count(&up, 3), [1, 1, 2, 2, 2, 2, 2]);
+        let ma = [11.0; 8];
+        assert_eq!(cross(&X, &ma), [false, false, false, true, false, false, true, false]);
+        assert_eq!(div(1.0, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,216 @@
+//! 批量计算指标
+//!
+//! [`IndicatorFrame`] 按K线日期对齐保存多个指标的输出，每个输出是一列，列名来自 [`ValueIndex`](crate::ValueIndex)。
+//! 多个指标的列名相同时，后出现的列名加上序号，例如同时计算 `RSI` 和 `RSI(6)` 时为 `RSI06`、`RSI06_2`。
+
+use serde::{Deserialize, Serialize};
+
+use trading_data::Chart;
+
+use crate::{registry, Indicator, SpecError};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    /// 所属指标的写法，例如 `KDJ(9,3,3)`
+    pub indicator: String,
+    #[serde(with = "nan_as_null")]
+    pub values: Vec<f64>,
+}
+
+/// json 不支持 `NaN`，序列化为 `null`，读取时还原为 `NaN`
+mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|v| (!v.is_nan()).then_some(*v)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+        let values = Vec::<Option<f64>>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|v| v.unwrap_or(f64::NAN)).collect())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IndicatorFrame {
+    dates: Vec<String>,
+    columns: Vec<Column>,
+}
+
+impl IndicatorFrame {
+    /// 计算前会重置指标
+    pub fn compute(chart: &Chart, indicators: &mut [Box<dyn Indicator>]) -> Self {
+        let mut frame = Self { dates: chart.iter().map(|v| v.date.clone()).collect(), columns: vec![] };
+        for indicator in indicators.iter_mut() {
+            indicator.reset();
+            let spec = registry::Spec { name: indicator.name().to_string(), args: indicator.arguments().clone() };
+            let index = indicator.index();
+            let mut values = vec![Vec::with_capacity(chart.len()); index.len()];
+            for bar in chart.iter() {
+                let output = indicator.next(bar);
+                for (column, value) in index.iter().enumerate() {
+                    values[column].push(output.get(value.index).copied().unwrap_or(f64::NAN));
+                }
+            }
+            for (value, values) in index.iter().zip(values) {
+                let name = frame.unique(&value.name);
+                frame.columns.push(Column { name, indicator: spec.to_string(), values });
+            }
+        }
+        frame
+    }
+
+    /// 使用内置指标计算，例如 `["MA(5,10)", "KDJ"]`
+    pub fn parse(chart: &Chart, specs: &[&str]) -> Result<Self, SpecError> {
+        let mut indicators = specs.iter().map(|v| registry::parse(v)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::compute(chart, &mut indicators))
+    }
+
+    fn unique(&self, name: &str) -> String {
+        let exists = |name: &str| self.columns.iter().any(|v| v.name == name);
+        if !exists(name) {
+            return name.to_string();
+        }
+        (2..)
+            .map(|i| format!("{}_{}", name, i))
+            .find(|v| !exists(v))
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty()
+    }
+
+    pub fn dates(&self) -> &[String] {
+        &self.dates
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.columns.iter().map(|v| v.name.as_str()).collect()
+    }
+
+    pub fn column(&self, name: &str) -> Option<&[f64]> {
+        self.columns.iter().find(|v| v.name == name).map(|v| v.values.as_slice())
+    }
+
+    fn position(&self, date: &str) -> Option<usize> {
+        self.dates.binary_search_by(|v| v.as_str().cmp(date)).ok()
+    }
+
+    /// 某根K线的所有输出，顺序与 [`names`](Self::names) 一致
+    pub fn row(&self, date: &str) -> Option<Vec<f64>> {
+        let index = self.position(date)?;
+        Some(self.columns.iter().map(|v| v.values[index]).collect())
+    }
+
+    pub fn value(&self, date: &str, name: &str) -> Option<f64> {
+        Some(self.column(name)?[self.position(date)?])
+    }
+
+    /// 日期在 `start..=end` 之间的部分，边界为空时不限制
+    pub fn slice(&self, start: Option<&str>, end: Option<&str>) -> Self {
+        let from = start.map(|v| self.dates.partition_point(|d| d.as_str() < v)).unwrap_or(0);
+        let to = end
+            .map(|v| self.dates.partition_point(|d| d.as_str() <= v))
+            .unwrap_or(self.len())
+            .max(from);
+        self.range(from, to)
+    }
+
+    /// 最后 `count` 根K线
+    pub fn tail(&self, count: usize) -> Self {
+        self.range(self.len().saturating_sub(count), self.len())
+    }
+
+    fn range(&self, from: usize, to: usize) -> Self {
+        Self {
+            dates: self.dates[from..to].to_vec(),
+            columns: self
+                .columns
+                .iter()
+                .map(|v| Column { values: v.values[from..to].to_vec(), ..v.clone() })
+                .collect(),
+        }
+    }
+
+    /// 表头为 `date` 和列名，`NaN` 输出为空
+    pub fn to_csv(&self) -> String {
+        let mut content = std::iter::once("date").chain(self.names()).collect::<Vec<_>>().join(",");
+        for (index, date) in self.dates.iter().enumerate() {
+            content.push('\n');
+            content.push_str(date);
+            for column in &self.columns {
+                let value = column.values[index];
+                content.push(',');
+                if !value.is_nan() {
+                    content.push_str(&value.to_string());
+                }
+            }
+        }
+        content
+    }
+
+    /// 按列输出，`NaN` 输出为 `null`
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trading_data::Bar;
+
+    use super::*;
+
+    #[test]
+    fn compute_chart() {
+        let bars = (1..=5)
+            .map(|i| {
+                let mut bar = Bar::new(format!("2023-07-0{}", i).as_str());
+                (bar.open, bar.high, bar.low, bar.close, bar.volume) = (i as f64, i as f64, i as f64, i as f64, 10.0);
+                bar
+            })
+            .collect();
+        let chart = Chart::new(bars);
+        let frame = IndicatorFrame::parse(&chart, &["MA(2,3)", "VOL(2)", "MA(2)"]).unwrap();
+        assert_eq!(frame.len(), 5);
+        assert_eq!(frame.names(), ["MA02", "MA03", "VOL", "MAVOL", "MA02_2"]);
+        assert_eq!(frame.columns()[4].indicator, "MA(2)");
+        assert_eq!(frame.column("MA03").unwrap(), [1.0, 1.5, 2.0, 3.0, 4.0]);
+        assert_eq!(frame.value("2023-07-04", "MA02"), Some(3.5));
+        assert_eq!(frame.row("2023-07-02"), Some(vec![1.5, 1.5, 10.0, 10.0, 1.5]));
+        assert_eq!(frame.row("2023-07-09"), None);
+
+        let slice = frame.slice(Some("2023-07-02"), Some("2023-07-03"));
+        assert_eq!(slice.dates(), ["2023-07-02", "2023-07-03"]);
+        assert_eq!(slice.column("MA02_2").unwrap(), [1.5, 2.5]);
+        assert_eq!(frame.slice(Some("2023-07-06"), None).len(), 0);
+        assert_eq!(
+            frame.tail(1).to_csv(),
+            "date,MA02,MA03,VOL,MAVOL,MA02_2\n2023-07-05,4.5,4,10,10,4.5"
+        );
+
+        let json: IndicatorFrame = serde_json::from_str(&frame.to_json().unwrap()).unwrap();
+        assert_eq!(json, frame);
+        assert!(IndicatorFrame::parse(&chart, &["BOLL"]).is_err());
+
+        let frame = IndicatorFrame::parse(&chart, &["RSI", "RSI(6)"]).unwrap();
+        assert_eq!(frame.names(), ["RSI06", "RSI12", "RSI24", "RSI06_2"]);
+        assert!(frame.column("RSI06").unwrap()[0].is_nan());
+        let content = frame.to_json().unwrap();
+        assert!(content.contains("null"), "{}", content);
+        // `NaN` 不等于自身，按 csv 比较
+        let json: IndicatorFrame = serde_json::from_str(&content).unwrap();
+        assert_eq!(json.to_csv(), frame.to_csv());
+        assert!(json.column("RSI06").unwrap()[0].is_nan());
+    }
+}
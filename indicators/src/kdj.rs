@@ -1,71 +1,56 @@
 use std::string::ToString;
 
 use lazy_static::lazy_static;
-use ta::Next;
 
 use trading_data::Bar;
 
-use crate::ValueIndex;
-
-#[derive(Debug, Clone)]
-struct Chip {
-    period: usize,
-    value: f64,
-}
-
-impl Chip {
-    pub fn new(period: usize) -> Self {
-        Self { period, value: 0.0 }
-    }
-}
-
-impl Next<f64> for Chip {
-    type Output = f64;
-    fn next(&mut self, input: f64) -> Self::Output {
-        let v = (self.value * 2.0 + input * 1.0) / self.period as f64;
-        self.value = v;
-        v
-    }
-}
+use crate::formula::{div, Extremum, Sma};
+use crate::{Param, ParamError, Schema, ValueIndex};
 
 #[derive(Debug, Clone)]
 pub struct Indicator {
     args: Vec<usize>,
-    n: usize,
-    items: Vec<Bar>,
-    k: Chip,
-    d: Chip,
+    high: Extremum,
+    low: Extremum,
+    k: Sma,
+    d: Sma,
 }
 
 impl Indicator {
-    fn new(args: Vec<usize>) -> Self {
-        Self { n: args[0], k: Chip::new(args[1]), d: Chip::new(args[2]), items: vec![], args }
+    pub fn schema() -> Schema {
+        Schema::new(
+            "KDJ",
+            "随机指标",
+            vec![
+                Param::new("N", "RSV 周期", 9, 1, 100),
+                Param::new("M1", "K 平滑周期", 3, 1, 100),
+                Param::new("M2", "D 平滑周期", 3, 1, 100),
+            ],
+        )
     }
 
-    fn rsv_value(&self) -> f64 {
-        // CLOSE
-        let close = self.items.last().unwrap().close;
-        // LLV(LOW,P1)
-        let low = self.items.iter().min_by(|&a, &b| a.low.total_cmp(&b.low)).unwrap().low;
-        // HHV(HIGH,P1)
-        let high = self.items.iter().max_by(|&a, &b| a.high.total_cmp(&b.high)).unwrap().high;
-
-        // RSV:=(CLOSE-LLV(LOW,P1))/(HHV(HIGH,P1)-LLV(LOW,P1))*100;
-        let rsv = (close - low) / (high - low) * 100.0;
-
-        if rsv > 100.0 {
-            100.0
-        } else if rsv < 0.0 {
-            0.0
-        } else {
-            rsv
-        }
+    /// # Panics
+    ///
+    /// 参数不符合 [`schema`](Self::schema) 时 panic，参数来自外部输入时使用 [`try_new`](Self::try_new)
+    pub fn new(args: Vec<usize>) -> Self {
+        Self::try_new(args).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new(args: Vec<usize>) -> Result<Self, ParamError> {
+        Self::schema().validate(&args)?;
+        Ok(Self {
+            high: Extremum::hhv(args[0]),
+            low: Extremum::llv(args[0]),
+            k: Sma::new(args[1], 1),
+            d: Sma::new(args[2], 1),
+            args,
+        })
     }
 }
 
 impl Default for Indicator {
     fn default() -> Self {
-        Indicator::new(vec![9, 3, 3])
+        Indicator::new(Indicator::schema().defaults())
     }
 }
 
@@ -89,27 +74,27 @@ impl crate::Indicator for Indicator {
     }
 
     fn next(&mut self, bar: &Bar) -> Vec<f64> {
-        self.items.push(bar.clone());
-        if self.items.len() > self.n {
-            self.items.remove(0);
-        }
-
-        let rsv = self.rsv_value();
+        // RSV:=(CLOSE-LLV(LOW,N))/(HHV(HIGH,N)-LLV(LOW,N))*100;
+        let low = self.low.next(bar.low);
+        let high = self.high.next(bar.high);
+        let rsv = div(bar.close - low, high - low) * 100.0;
 
-        // K:SMA(RSV,P2,1);
+        // K:SMA(RSV,M1,1);
         let k = self.k.next(rsv);
 
-        // D:SMA(K,P3,1);
+        // D:SMA(K,M2,1);
         let d = self.d.next(k);
 
-        // J:3*K-2*D
+        // J:3*K-2*D;
         let j = (k * 3.0) - (d * 2.0);
-        //Output { rsv, k, d, j }
         vec![k, d, j]
     }
 
     fn reset(&mut self) {
-        self.items.clear()
+        self.high.reset();
+        self.low.reset();
+        self.k.reset();
+        self.d.reset();
     }
 }
 
@@ -119,6 +104,7 @@ mod tests {
 
     use trading_data::{ChartLoader, LocalLoader, Stock, TradingDay};
 
+    use crate::formula::tests::{assert_close, golden_bars};
     use crate::Indicator;
 
     #[tokio::test]
@@ -128,7 +114,7 @@ mod tests {
         let chart = loader.chart(&stock).await;
         assert!(chart.is_ok());
         let chart = chart.unwrap();
-        let mut kdj = super::Indicator::new(vec![9, 3, 3]);
+        let mut kdj = super::Indicator::try_new(vec![9, 3, 3]).unwrap();
         let day = (TradingDay::latest() - 10).to_string();
         for bar in chart.iter() {
             let kdj = kdj.next(bar);
@@ -137,4 +123,15 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn golden() {
+        let mut kdj = super::Indicator::default();
+        let out: Vec<_> = golden_bars().iter().map(|v| kdj.next(v)).collect();
+        let column = |i: usize| out[15..].iter().map(|v| v[i]).collect::<Vec<_>>();
+        assert_close(&out[0], &[57.14, 57.14, 57.14]);
+        assert_close(&column(0), &[73.31, 62.76, 65.45, 73.26, 78.73]);
+        assert_close(&column(1), &[76.62, 72.0, 69.82, 70.97, 73.55]);
+        assert_close(&column(2), &[66.67, 44.27, 56.72, 77.86, 89.08]);
+    }
 }
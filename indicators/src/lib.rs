@@ -1,8 +1,16 @@
 use trading_data::Bar;
 
+pub use frame::{Column, IndicatorFrame};
+pub use param::{Param, ParamError, Schema};
+pub use registry::{Registry, Spec, SpecError};
+
 pub mod average;
+pub mod formula;
+pub mod frame;
 pub mod kdj;
 pub mod macd;
+pub mod param;
+pub mod registry;
 pub mod rsi;
 pub mod volume;
 
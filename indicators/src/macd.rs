@@ -1,36 +1,56 @@
 use lazy_static::lazy_static;
 use std::string::ToString;
-use ta::{Next, Reset};
 
-use crate::ValueIndex;
+use crate::formula::Ema;
+use crate::{Param, ParamError, Schema, ValueIndex};
 use trading_data::Bar;
 
 #[derive(Debug, Clone)]
 pub struct Indicator {
     args: Vec<usize>,
-    long_period: usize,
-    short_ema: ta::indicators::ExponentialMovingAverage,
-    long_ema: ta::indicators::ExponentialMovingAverage,
-    signal_ema: ta::indicators::ExponentialMovingAverage,
-    current: usize,
+    short_ema: Ema,
+    long_ema: Ema,
+    signal_ema: Ema,
 }
 
 impl Indicator {
-    fn new(args: Vec<usize>) -> Self {
-        Self {
-            long_period: args[1],
-            short_ema: ta::indicators::ExponentialMovingAverage::new(args[0]).unwrap(),
-            long_ema: ta::indicators::ExponentialMovingAverage::new(args[1]).unwrap(),
-            signal_ema: ta::indicators::ExponentialMovingAverage::new(args[2]).unwrap(),
-            current: 0,
-            args,
+    pub fn schema() -> Schema {
+        Schema::new(
+            "MACD",
+            "指数平滑异同移动平均线",
+            vec![
+                Param::new("SHORT", "短周期", 12, 2, 200),
+                Param::new("LONG", "长周期，大于短周期", 26, 2, 250),
+                Param::new("MID", "DEA 周期", 9, 1, 100),
+            ],
+        )
+    }
+
+    /// # Panics
+    ///
+    /// 参数不符合 [`schema`](Self::schema) 时 panic，参数来自外部输入时使用 [`try_new`](Self::try_new)
+    pub fn new(args: Vec<usize>) -> Self {
+        Self::try_new(args).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new(args: Vec<usize>) -> Result<Self, ParamError> {
+        let schema = Self::schema();
+        schema.validate(&args)?;
+        if args[0] >= args[1] {
+            return Err(schema.invalid(format!("SHORT {} must be less than LONG {}", args[0], args[1])));
         }
+        Ok(Self {
+            short_ema: Ema::new(args[0]),
+            long_ema: Ema::new(args[1]),
+            signal_ema: Ema::new(args[2]),
+            args,
+        })
     }
 }
 
 impl Default for Indicator {
     fn default() -> Self {
-        Indicator::new(vec![12, 26, 9])
+        Indicator::new(Indicator::schema().defaults())
     }
 }
 
@@ -54,16 +74,11 @@ impl crate::Indicator for Indicator {
     }
 
     fn next(&mut self, bar: &Bar) -> Vec<f64> {
-        self.current = self.current + 1;
-        let short = self.short_ema.next(bar.close);
-        let long = self.long_ema.next(bar.close);
-
-        if self.current < self.long_period {
-            return vec![0.0, 0.0, 0.0];
-        }
-
-        let diff = short - long;
+        // DIF:EMA(CLOSE,SHORT)-EMA(CLOSE,LONG);
+        let diff = self.short_ema.next(bar.close) - self.long_ema.next(bar.close);
+        // DEA:EMA(DIF,MID);
         let dea = self.signal_ema.next(diff);
+        // MACD:(DIF-DEA)*2;
         let macd = (diff - dea) * 2.0;
 
         vec![diff, dea, macd]
@@ -73,7 +88,6 @@ impl crate::Indicator for Indicator {
         self.long_ema.reset();
         self.short_ema.reset();
         self.signal_ema.reset();
-        self.current = 0;
     }
 }
 
@@ -81,8 +95,15 @@ impl crate::Indicator for Indicator {
 mod tests {
     use trading_data::Bar;
 
+    use crate::formula::tests::{assert_close, golden_bars};
     use crate::Indicator;
 
+    #[test]
+    #[should_panic(expected = "SHORT 26 must be less than LONG 12")]
+    fn invalid_args() {
+        super::Indicator::new(vec![26, 12, 9]);
+    }
+
     #[test]
     fn macd() {
         let mut avg = super::Indicator::default();
@@ -93,4 +114,15 @@ mod tests {
             println!("{}: {:?}", i, out);
         }
     }
+
+    #[test]
+    fn golden() {
+        let mut macd = super::Indicator::default();
+        let out: Vec<_> = golden_bars().iter().map(|v| macd.next(v)).collect();
+        assert_close(&out[0], &[0.0, 0.0, 0.0]);
+        let column = |i: usize| out[17..].iter().map(|v| v[i]).collect::<Vec<_>>();
+        assert_close(&column(0), &[0.68, 0.737, 0.814]);
+        assert_close(&column(1), &[0.596, 0.624, 0.662]);
+        assert_close(&column(2), &[0.167, 0.226, 0.304]);
+    }
 }
@@ -0,0 +1,91 @@
+//! 指标参数
+//!
+//! 每个指标通过 [`Schema`] 说明参数的含义、默认值和取值范围，界面和配置文件据此生成编辑框并校验参数，
+//! 指标的 `try_new` 在创建前校验，参数错误时返回 [`ParamError`]，`new` 则直接 panic。
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+pub enum ParamError {
+    #[error("{indicator} expects {expected} arguments, got {actual}")]
+    Count { indicator: &'static str, expected: String, actual: usize },
+    #[error("{indicator} argument {name} must be in {min}..={max}, got {value}")]
+    Range { indicator: &'static str, name: String, value: usize, min: usize, max: usize },
+    #[error("{indicator}: {message}")]
+    Invalid { indicator: &'static str, message: String },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Param {
+    pub name: String,
+    pub description: &'static str,
+    pub default: usize,
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Param {
+    pub fn new(name: impl Into<String>, description: &'static str, default: usize, min: usize, max: usize) -> Self {
+        Self { name: name.into(), description, default, min, max }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Schema {
+    pub indicator: &'static str,
+    pub description: &'static str,
+    pub params: Vec<Param>,
+    /// 参数个数不固定，例如 `MA(5,10,20)`，超出 `params` 的参数使用最后一个参数的取值范围
+    pub variadic: bool,
+}
+
+impl Schema {
+    pub fn new(indicator: &'static str, description: &'static str, params: Vec<Param>) -> Self {
+        Self { indicator, description, params, variadic: false }
+    }
+
+    pub fn variadic(mut self) -> Self {
+        self.variadic = true;
+        self
+    }
+
+    pub fn defaults(&self) -> Vec<usize> {
+        self.params.iter().map(|v| v.default).collect()
+    }
+
+    /// 校验参数个数和取值范围
+    pub fn validate(&self, args: &[usize]) -> Result<(), ParamError> {
+        let valid = if self.variadic { !args.is_empty() } else { args.len() == self.params.len() };
+        if !valid {
+            let expected = if self.variadic {
+                "at least 1".to_string()
+            } else {
+                self.params.len().to_string()
+            };
+            return Err(ParamError::Count { indicator: self.indicator, expected, actual: args.len() });
+        }
+        for (i, value) in args.iter().enumerate() {
+            let Some(param) = self.params.get(i).or(self.params.last()) else {
+                continue;
+            };
+            if *value < param.min || *value > param.max {
+                let name = match i < self.params.len() {
+                    true => param.name.clone(),
+                    false => format!("{}{}", param.name.trim_end_matches(|v: char| v.is_ascii_digit()), i + 1),
+                };
+                return Err(ParamError::Range {
+                    indicator: self.indicator,
+                    name,
+                    value: *value,
+                    min: param.min,
+                    max: param.max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn invalid(&self, message: impl Into<String>) -> ParamError {
+        ParamError::Invalid { indicator: self.indicator, message: message.into() }
+    }
+}
@@ -0,0 +1,202 @@
+//! 按名称创建指标
+//!
+//! 指标以 `名称(参数,...)` 的形式描述，例如 `KDJ(9,3,3)`、`MA(5,10,20)`，省略参数时使用默认参数。
+//! 配置文件、命令行和界面使用同一种写法，通过 [`Registry`] 创建对应的指标。
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+
+use crate::{average, kdj, macd, rsi, volume, Indicator, ParamError, Schema};
+
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+pub enum SpecError {
+    #[error("invalid indicator spec `{0}`")]
+    Invalid(String),
+    #[error("unknown indicator `{0}`")]
+    Unknown(String),
+    #[error(transparent)]
+    Param(#[from] ParamError),
+}
+
+/// 指标名称和参数，名称不区分大小写
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Spec {
+    pub name: String,
+    /// 为空时使用默认参数
+    pub args: Vec<usize>,
+}
+
+impl FromStr for Spec {
+    type Err = SpecError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SpecError::Invalid(s.to_string());
+        let s = s.trim();
+        let (name, args) = match s.split_once('(') {
+            Some((name, rest)) => (name, rest.strip_suffix(')').ok_or_else(invalid)?),
+            None => (s, ""),
+        };
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|v| v.is_ascii_alphanumeric() || v == '_') {
+            return Err(invalid());
+        }
+        let args = match args.trim() {
+            "" => vec![],
+            args => args
+                .split(',')
+                .map(|v| v.trim().parse::<usize>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?,
+        };
+        Ok(Self { name: name.to_uppercase(), args })
+    }
+}
+
+impl Display for Spec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.args.is_empty() {
+            return write!(f, "{}", self.name);
+        }
+        let args: Vec<_> = self.args.iter().map(ToString::to_string).collect();
+        write!(f, "{}({})", self.name, args.join(","))
+    }
+}
+
+pub type Factory = fn(Vec<usize>) -> Result<Box<dyn Indicator>, ParamError>;
+
+/// 注册的指标
+#[derive(Clone)]
+pub struct Entry {
+    pub schema: Schema,
+    factory: Factory,
+}
+
+impl Entry {
+    pub fn new(schema: Schema, factory: Factory) -> Self {
+        Self { schema, factory }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.schema.indicator
+    }
+
+    /// 参数为空时使用默认参数
+    pub fn create(&self, args: Vec<usize>) -> Result<Box<dyn Indicator>, SpecError> {
+        let args = if args.is_empty() { self.schema.defaults() } else { args };
+        Ok((self.factory)(args)?)
+    }
+
+    /// 默认参数的写法
+    pub fn spec(&self) -> Spec {
+        Spec { name: self.name().to_string(), args: self.schema.defaults() }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Registry {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Registry {
+    /// 包含所有内置指标
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        for entry in [
+            Entry::new(average::Indicator::schema(), |args| {
+                Ok(Box::new(average::Indicator::try_new(args)?))
+            }),
+            Entry::new(macd::Indicator::schema(), |args| Ok(Box::new(macd::Indicator::try_new(args)?))),
+            Entry::new(kdj::Indicator::schema(), |args| Ok(Box::new(kdj::Indicator::try_new(args)?))),
+            Entry::new(rsi::Indicator::schema(), |args| Ok(Box::new(rsi::Indicator::try_new(args)?))),
+            Entry::new(volume::Indicator::schema(), |args| {
+                Ok(Box::new(volume::Indicator::try_new(args)?))
+            }),
+        ] {
+            registry.register(entry);
+        }
+        registry
+    }
+
+    /// 同名的指标会被替换
+    pub fn register(&mut self, entry: Entry) {
+        self.entries.insert(entry.name().to_uppercase(), entry);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(&name.to_uppercase())
+    }
+
+    /// 按名称排序
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.values()
+    }
+
+    pub fn create(&self, spec: &Spec) -> Result<Box<dyn Indicator>, SpecError> {
+        let entry = self.get(&spec.name).ok_or_else(|| SpecError::Unknown(spec.name.clone()))?;
+        entry.create(spec.args.clone())
+    }
+
+    pub fn parse(&self, spec: &str) -> Result<Box<dyn Indicator>, SpecError> {
+        self.create(&spec.parse()?)
+    }
+}
+
+lazy_static! {
+    static ref BUILTIN: Registry = Registry::builtin();
+}
+
+/// 使用内置指标解析，例如 `KDJ(9,3,3)`
+pub fn parse(spec: &str) -> Result<Box<dyn Indicator>, SpecError> {
+    BUILTIN.parse(spec)
+}
+
+/// 内置指标的默认写法
+pub fn available() -> Vec<Spec> {
+    BUILTIN.entries().map(Entry::spec).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_spec() {
+        let spec: Spec = " kdj( 9, 3,3 )".parse().unwrap();
+        assert_eq!(spec, Spec { name: "KDJ".to_string(), args: vec![9, 3, 3] });
+        assert_eq!(spec.to_string(), "KDJ(9,3,3)");
+        assert_eq!("VOL".parse::<Spec>().unwrap().args, Vec::<usize>::new());
+        for invalid in ["", "KDJ(9,3", "KDJ(a)", "K D J", "(9)", "KDJ(9,,3)"] {
+            assert!(invalid.parse::<Spec>().is_err(), "{}", invalid);
+        }
+
+        let kdj = parse("KDJ(5,3,3)").unwrap();
+        assert_eq!((kdj.name(), kdj.arguments()), ("KDJ", &vec![5, 3, 3]));
+        assert_eq!(parse("macd").unwrap().arguments(), &vec![12, 26, 9]);
+        assert_eq!(parse("MA(5,10,20)").unwrap().index().len(), 3);
+        assert_eq!(parse("BOLL(20)").err(), Some(SpecError::Unknown("BOLL".to_string())));
+        assert!(matches!(
+            parse("MACD(12)"),
+            Err(SpecError::Param(ParamError::Count { actual: 1, .. }))
+        ));
+        assert!(matches!(
+            parse("MACD(26,12,9)"),
+            Err(SpecError::Param(ParamError::Invalid { .. }))
+        ));
+        assert!(matches!(
+            parse("VOL(0)"),
+            Err(SpecError::Param(ParamError::Range { value: 0, .. }))
+        ));
+        let err = parse("MA(5,10,20,30,40,50,60,70,80,90,2000)").err().unwrap();
+        assert_eq!(err.to_string(), "MA argument M11 must be in 1..=1000, got 2000");
+
+        let names: Vec<_> = available().iter().map(|v| v.name.clone()).collect();
+        assert_eq!(names, ["KDJ", "MA", "MACD", "RSI", "VOL"]);
+        for spec in available() {
+            let indicator = BUILTIN.create(&spec).unwrap();
+            assert_eq!(indicator.name(), spec.name);
+            assert_eq!(indicator.arguments(), &BUILTIN.get(&spec.name).unwrap().schema.defaults());
+        }
+    }
+}
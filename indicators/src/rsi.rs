@@ -1,31 +1,42 @@
-use ta::{Next, Reset};
-
 use trading_data::Bar;
 
-use crate::ValueIndex;
+use crate::formula::{div, Ref, Sma};
+use crate::{Param, ParamError, Schema, ValueIndex};
 
 pub struct Indicator {
-    rsi: Vec<ta::indicators::RelativeStrengthIndex>,
+    last: Ref,
+    /// 每个周期的 SMA(MAX(CLOSE-LC,0),N,1) 和 SMA(ABS(CLOSE-LC),N,1)
+    rsi: Vec<(Sma, Sma)>,
     args: Vec<usize>,
 }
 
 impl Indicator {
-    fn new(args: Vec<usize>) -> Self {
-        Self {
-            rsi: args
-                .clone()
-                .into_iter()
-                .map(ta::indicators::RelativeStrengthIndex::new)
-                .flatten()
-                .collect(),
-            args,
-        }
+    pub fn schema() -> Schema {
+        let params = [6, 12, 24]
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| Param::new(format!("N{}", i + 1), "周期", v, 1, 100))
+            .collect();
+        Schema::new("RSI", "相对强弱指标", params).variadic()
+    }
+
+    /// # Panics
+    ///
+    /// 参数不符合 [`schema`](Self::schema) 时 panic，参数来自外部输入时使用 [`try_new`](Self::try_new)
+    pub fn new(args: Vec<usize>) -> Self {
+        Self::try_new(args).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new(args: Vec<usize>) -> Result<Self, ParamError> {
+        Self::schema().validate(&args)?;
+        let rsi = args.iter().map(|v| (Sma::new(*v, 1), Sma::new(*v, 1))).collect();
+        Ok(Self { last: Ref::new(1), rsi, args })
     }
 }
 
 impl Default for Indicator {
     fn default() -> Self {
-        Indicator::new(vec![9, 9, 9])
+        Indicator::new(Indicator::schema().defaults())
     }
 }
 
@@ -47,12 +58,24 @@ impl crate::Indicator for Indicator {
     }
 
     fn next(&mut self, bar: &Bar) -> Vec<f64> {
-        self.rsi.iter_mut().map(|v| v.next(bar.close)).collect()
+        // LC:=REF(CLOSE,1);
+        let change = bar.close - self.last.next(bar.close);
+        // RSI:SMA(MAX(CLOSE-LC,0),N,1)/SMA(ABS(CLOSE-LC),N,1)*100;
+        self.rsi
+            .iter_mut()
+            .map(|(up, all)| {
+                let up = up.next(if change < 0.0 { 0.0 } else { change });
+                let all = all.next(change.abs());
+                div(up, all) * 100.0
+            })
+            .collect()
     }
 
     fn reset(&mut self) {
-        for rsi in self.rsi.iter_mut() {
-            rsi.reset()
+        self.last.reset();
+        for (up, all) in self.rsi.iter_mut() {
+            up.reset();
+            all.reset();
         }
     }
 }
@@ -63,6 +86,7 @@ mod tests {
 
     use trading_data::{ChartLoader, LocalLoader, Stock, TradingDay};
 
+    use crate::formula::tests::{assert_close, golden_bars};
     use crate::Indicator;
 
     #[tokio::test]
@@ -81,4 +105,23 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn golden() {
+        let mut rsi = super::Indicator::default();
+        assert_eq!(rsi.arguments(), &vec![6, 12, 24]);
+        let out: Vec<_> = golden_bars().iter().map(|v| rsi.next(v)).collect();
+        assert!(out[0].iter().all(|v| v.is_nan()));
+        assert_close(&out[1], &[100.0, 100.0, 100.0]);
+        let column = |i: usize| out[15..].iter().map(|v| v[i]).collect::<Vec<_>>();
+        assert_close(&column(0), &[62.28, 53.25, 64.16, 72.01, 76.43]);
+        assert_close(&column(1), &[74.28, 69.08, 72.73, 75.83, 77.81]);
+        assert_close(&column(2), &[84.45, 81.55, 82.64, 83.64, 84.32]);
+
+        let mut bar = trading_data::Bar::new("2023-07-01");
+        bar.close = 10.0;
+        rsi.reset();
+        rsi.next(&bar);
+        assert_eq!(rsi.next(&bar), vec![0.0, 0.0, 0.0]);
+    }
 }
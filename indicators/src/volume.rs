@@ -1,8 +1,9 @@
 use lazy_static::lazy_static;
 
 use trading_data::Bar;
+use trading_ext::RollingSum;
 
-use crate::ValueIndex;
+use crate::{Param, ParamError, Schema, ValueIndex};
 
 lazy_static! {
     pub static ref VOL: ValueIndex = ValueIndex::new(format!("VOL"), 0);
@@ -10,20 +11,31 @@ lazy_static! {
 }
 
 pub struct Indicator {
-    count: usize,
-    items: Vec<f64>,
+    sum: RollingSum,
     args: Vec<usize>,
 }
 
 impl Default for Indicator {
     fn default() -> Indicator {
-        Indicator::new(vec![100])
+        Indicator::new(Indicator::schema().defaults())
     }
 }
 
 impl Indicator {
+    pub fn schema() -> Schema {
+        Schema::new("VOL", "成交量", vec![Param::new("M1", "均量周期", 100, 1, 1000)])
+    }
+
+    /// # Panics
+    ///
+    /// 参数不符合 [`schema`](Self::schema) 时 panic，参数来自外部输入时使用 [`try_new`](Self::try_new)
     pub fn new(args: Vec<usize>) -> Self {
-        Self { count: args[0], items: vec![], args }
+        Self::try_new(args).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_new(args: Vec<usize>) -> Result<Self, ParamError> {
+        Self::schema().validate(&args)?;
+        Ok(Self { sum: RollingSum::new(args[0]), args })
     }
 }
 
@@ -41,17 +53,13 @@ impl crate::Indicator for Indicator {
     }
 
     fn next(&mut self, bar: &Bar) -> Vec<f64> {
-        self.items.push(bar.volume);
-        if self.items.len() > self.count {
-            self.items.remove(0);
-        }
+        self.sum.push(bar.volume);
 
         let volume = if bar.close >= bar.open { bar.volume } else { 0.0 - bar.volume };
-        let avg = self.items.iter().sum::<f64>() / self.items.len() as f64;
-        vec![volume, avg]
+        vec![volume, self.sum.mean()]
     }
 
     fn reset(&mut self) {
-        self.items.clear();
+        self.sum.clear();
     }
 }
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use hyper::body::HttpBody;
+use trading_data::loader::remote::{headers, SignContent, SignVersion, Verifier, VerifyError};
+use trading_data::Credential;
+
+use crate::AppState;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    MissingHeader(&'static str),
+    UnknownAccessKey,
+    Verify(VerifyError),
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingHeader(name) => write!(f, "missing header: {}", name),
+            AuthError::UnknownAccessKey => write!(f, "unknown access key"),
+            AuthError::Verify(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<VerifyError> for AuthError {
+    fn from(err: VerifyError) -> Self {
+        AuthError::Verify(err)
+    }
+}
+
+/// 请求签名校验，与 [`trading_data::RemoteLoader`] 的签名方式保持一致
+#[derive(Debug)]
+pub struct Authenticator {
+    secrets: HashMap<String, String>,
+    verifier: Verifier,
+    enabled: bool,
+    body_limit: usize,
+}
+
+impl Authenticator {
+    /// 校验签名前读取的请求体上限
+    pub const BODY_LIMIT: usize = 1024 * 1024;
+
+    /// 没有凭证时拒绝所有请求
+    pub fn new(credentials: Vec<Credential>, verifier: Verifier) -> Self {
+        let secrets = credentials.into_iter().map(|v| (v.access_key, v.secret_key)).collect();
+        Self { secrets, verifier, enabled: true, body_limit: Self::BODY_LIMIT }
+    }
+
+    /// 不校验签名，只用于本机调试
+    pub fn disabled() -> Self {
+        Self { enabled: false, ..Self::new(vec![], Verifier::default()) }
+    }
+
+    pub fn with_body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn verify(
+        &self,
+        headers: &HeaderMap,
+        method: &str,
+        path: &str,
+        query: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), AuthError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let header = |name: &'static str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .ok_or(AuthError::MissingHeader(name))
+        };
+
+        let version = SignVersion::from_header(header(headers::VERSION).unwrap_or_default());
+        let content = SignContent {
+            method,
+            path,
+            query,
+            body,
+            timestamp: header(headers::TIMESTAMP)?,
+            nonce: header(headers::NONCE).unwrap_or_default(),
+        };
+        let access_key = header(headers::AK)?;
+        let secret_key = self.secrets.get(access_key).ok_or(AuthError::UnknownAccessKey)?;
+        let sign = header(headers::SIGN)?;
+        Ok(self.verifier.verify(version, secret_key, &content, sign)?)
+    }
+}
+
+/// 读取请求体，超过 `limit` 时返回 413，避免未校验的请求占满内存
+async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>, Response> {
+    let too_large = || (StatusCode::PAYLOAD_TOO_LARGE, format!("request body exceeds {} bytes", limit)).into_response();
+    if body.size_hint().lower() > limit as u64 {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()).into_response())?;
+        if data.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+pub(crate) async fn authenticate(State(state): State<Arc<AppState>>, req: Request<Body>, next: Next<Body>) -> Response {
+    let (parts, body) = req.into_parts();
+    let body = match read_body(body, state.auth.body_limit).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
+    let path = parts.uri.path();
+    let method = parts.method.as_str();
+    if let Err(err) = state.auth.verify(&parts.headers, method, path, parts.uri.query(), &body) {
+        tracing::debug!("reject {}: {}", path, err);
+        return (StatusCode::UNAUTHORIZED, err.to_string()).into_response();
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+    use trading_data::loader::remote::signature;
+
+    use super::*;
+
+    fn signed(version: SignVersion, credential: &Credential, query: &str, timestamp: i64) -> HeaderMap {
+        let timestamp = timestamp.to_string();
+        let nonce = fastrand::u64(..).to_string();
+        let content = SignContent {
+            method: "GET",
+            path: "/chart/day/600444",
+            query: Some(query),
+            timestamp: &timestamp,
+            nonce: &nonce,
+            ..Default::default()
+        };
+        let sign = signature(version, &credential.secret_key, &content);
+        let mut map = HeaderMap::new();
+        map.insert(headers::VERSION, HeaderValue::from_static(version.header()));
+        map.insert(headers::TIMESTAMP, HeaderValue::from_str(&timestamp).unwrap());
+        map.insert(headers::NONCE, HeaderValue::from_str(&nonce).unwrap());
+        map.insert(headers::AK, HeaderValue::from_str(&credential.access_key).unwrap());
+        map.insert(headers::SIGN, HeaderValue::from_str(&sign).unwrap());
+        map
+    }
+
+    #[test]
+    fn verify() {
+        let credential = Credential { access_key: "ak".to_string(), secret_key: "sk".to_string() };
+        let auth = Authenticator::new(vec![credential.clone()], Verifier::default());
+        let now = chrono::Local::now().timestamp_millis();
+        let verify = |headers: &HeaderMap, path: &str, query: &str| auth.verify(headers, "GET", path, Some(query), b"");
+
+        for version in [SignVersion::Legacy, SignVersion::HmacSha256] {
+            let headers = signed(version, &credential, "limit=10", now);
+            assert_eq!(verify(&headers, "/chart/day/600444", "limit=10"), Ok(()));
+            assert_eq!(
+                verify(&headers, "/chart/day/600445", "limit=10"),
+                Err(AuthError::Verify(VerifyError::InvalidSign))
+            );
+            let headers = signed(version, &credential, "limit=10", now - 600_000);
+            assert_eq!(
+                verify(&headers, "/chart/day/600444", "limit=10"),
+                Err(AuthError::Verify(VerifyError::Expired))
+            );
+        }
+
+        let headers = signed(SignVersion::HmacSha256, &credential, "limit=10", now);
+        assert_eq!(
+            verify(&headers, "/chart/day/600444", "limit=1000"),
+            Err(AuthError::Verify(VerifyError::InvalidSign))
+        );
+        assert_eq!(verify(&headers, "/chart/day/600444", "limit=10"), Ok(()));
+        assert_eq!(
+            verify(&headers, "/chart/day/600444", "limit=10"),
+            Err(AuthError::Verify(VerifyError::Replayed))
+        );
+
+        assert_eq!(
+            verify(&HeaderMap::new(), "/stocks", ""),
+            Err(AuthError::MissingHeader(headers::TIMESTAMP))
+        );
+
+        let other = Credential { access_key: "other".to_string(), secret_key: "sk".to_string() };
+        let headers = signed(SignVersion::HmacSha256, &other, "", now);
+        assert_eq!(verify(&headers, "/stocks", ""), Err(AuthError::UnknownAccessKey));
+
+        assert!(Authenticator::disabled()
+            .verify(&HeaderMap::new(), "GET", "/stocks", None, b"")
+            .is_ok());
+        let empty = Authenticator::new(vec![], Verifier::default());
+        let headers = signed(SignVersion::HmacSha256, &credential, "", now);
+        assert_eq!(
+            empty.verify(&headers, "GET", "/stocks", Some(""), b""),
+            Err(AuthError::UnknownAccessKey)
+        );
+    }
+}
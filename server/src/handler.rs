@@ -0,0 +1,177 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use trading_data::csv::{Writer, RECORD_COLUMNS};
+use trading_data::{
+    Bar, ChartParamter, DataError, DataSource, FundamentalsLoader, LocalLoader, Period, SectorLoader, Stocks,
+};
+
+use crate::AppState;
+
+const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// 接口错误，按错误原因映射为 http 状态码
+pub(crate) struct ApiError(StatusCode, String);
+
+/// 休市返回 409，`RemoteLoader` 按同样的规则还原为 `DataError`
+impl From<DataError> for ApiError {
+    fn from(err: DataError) -> Self {
+        let status = match &err {
+            DataError::NotFound(_) => StatusCode::NOT_FOUND,
+            DataError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            DataError::MarketClosed => StatusCode::CONFLICT,
+            DataError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            DataError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            DataError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            DataError::Parse { .. } | DataError::Io(_) | DataError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, format!("{:#}", err))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        tracing::debug!("response error {}: {}", self.0, self.1);
+        (self.0, self.1).into_response()
+    }
+}
+
+type ApiResult = Result<Response, ApiError>;
+
+/// 客户端通过 `Accept: text/csv` 请求 csv 格式，默认返回 json
+fn accept_csv(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("text/csv"))
+        .unwrap_or(false)
+}
+
+fn csv(content: String) -> Response {
+    ([(header::CONTENT_TYPE, CSV_CONTENT_TYPE)], content).into_response()
+}
+
+fn stocks_csv(stocks: &Stocks) -> String {
+    let mut content = String::from("股票代码\t股票名称");
+    for stock in stocks.iter() {
+        content.push_str(&format!("\n{}\t{}", stock.symbol, stock.name));
+    }
+    content
+}
+
+pub(crate) async fn stocks(State(state): State<Arc<AppState>>, headers: HeaderMap) -> ApiResult {
+    let stocks = state.loader.stocks().await?;
+    if accept_csv(&headers) {
+        return Ok(csv(stocks_csv(&stocks)));
+    }
+    Ok(Json(stocks.value()).into_response())
+}
+
+pub(crate) async fn market(State(state): State<Arc<AppState>>, headers: HeaderMap) -> ApiResult {
+    let market = state.loader.market().await?;
+    if !accept_csv(&headers) {
+        return Ok(Json(market).into_response());
+    }
+    let mut records: Vec<(&String, &Bar)> = market.iter().collect();
+    records.sort_by(|a, b| a.0.cmp(b.0));
+    Ok(csv(Writer::new(RECORD_COLUMNS).write_records(records)))
+}
+
+/// 股票代码为 6 位数字，其他代码返回 400，避免异常代码进入本地路径
+fn check_symbol(symbol: &str) -> Result<(), ApiError> {
+    if symbol.len() == 6 && symbol.bytes().all(|v| v.is_ascii_digit()) {
+        return Ok(());
+    }
+    Err(ApiError(StatusCode::BAD_REQUEST, format!("invalid symbol {:?}", symbol)))
+}
+
+pub(crate) async fn current(
+    State(state): State<Arc<AppState>>,
+    Path(symbol): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult {
+    check_symbol(&symbol)?;
+    let bar = state.loader.current(&symbol).await?;
+    if accept_csv(&headers) {
+        return Ok(csv(Writer::new(RECORD_COLUMNS).write_records([(symbol, &bar)])));
+    }
+    Ok(Json(bar).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChartQuery {
+    limit: Option<usize>,
+    start: Option<String>,
+    end: Option<String>,
+}
+
+pub(crate) async fn chart(
+    State(state): State<Arc<AppState>>,
+    Path((period, symbol)): Path<(String, String)>,
+    Query(query): Query<ChartQuery>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let period = Period::from_str(&period).map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    check_symbol(&symbol)?;
+    let mut param = ChartParamter::new(symbol, period);
+    param.limit = query.limit;
+    param.start = query.start;
+    param.end = query.end;
+    param.validate().map_err(|e| ApiError(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let chart = state.loader.chart(param).await?;
+    if !accept_csv(&headers) {
+        return Ok(Json(chart.value()).into_response());
+    }
+    Ok(csv(Writer::default().write_bars(chart.iter())))
+}
+
+fn local<'a>(state: &'a AppState, name: &str) -> Result<&'a LocalLoader, ApiError> {
+    state
+        .local
+        .as_ref()
+        .ok_or_else(|| DataError::Unsupported(name.to_string()).into())
+}
+
+pub(crate) async fn fundamentals(
+    State(state): State<Arc<AppState>>,
+    Path(symbol): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult {
+    check_symbol(&symbol)?;
+    let loader = local(&state, "fundamentals")?;
+    let fundamentals = loader.fundamentals(&symbol).await?;
+    if accept_csv(&headers) {
+        return Ok(csv(fundamentals.to_csv()));
+    }
+    Ok(Json(fundamentals.value()).into_response())
+}
+
+pub(crate) async fn sectors(State(state): State<Arc<AppState>>, headers: HeaderMap) -> ApiResult {
+    let sectors = local(&state, "sectors")?.sectors().await?;
+    if accept_csv(&headers) {
+        return Ok(csv(sectors.to_csv()));
+    }
+    Ok(Json(sectors.value()).into_response())
+}
+
+pub(crate) async fn members(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> ApiResult {
+    let members = local(&state, "sectors")?.members(&code).await?;
+    if accept_csv(&headers) {
+        return Ok(csv(members.to_csv()));
+    }
+    Ok(Json(members.value()).into_response())
+}
+
+pub(crate) async fn fallback() -> impl IntoResponse {
+    (StatusCode::NOT_FOUND, "not found")
+}
@@ -0,0 +1,207 @@
+//! 本地数据服务，按照 [`trading_data::RemoteLoader`] 的协议提供 [`trading_data::DataSource`] 中的数据，
+//! 默认为 [`trading_data::LocalLoader`]
+use std::sync::Arc;
+
+use axum::routing::get;
+use axum::{middleware, Router};
+
+pub use auth::{AuthError, Authenticator};
+
+mod auth;
+mod handler;
+
+/// 接口挂载路径，与 `RemoteLoader` 默认的 host 保持一致
+pub const API_PREFIX: &str = "/api/data";
+
+pub struct AppState {
+    pub loader: Arc<dyn trading_data::DataSource>,
+    pub auth: Authenticator,
+    /// 只有本地数据提供的接口（`/fundamentals`、`/sectors`），为空时返回 501
+    pub local: Option<trading_data::LocalLoader>,
+}
+
+impl AppState {
+    pub fn new(loader: impl trading_data::DataSource + 'static, auth: Authenticator) -> Self {
+        Self { loader: Arc::new(loader), auth, local: None }
+    }
+
+    pub fn with_local(mut self, loader: trading_data::LocalLoader) -> Self {
+        self.local = Some(loader);
+        self
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    let state = Arc::new(state);
+    let api = Router::new()
+        .route("/stocks", get(handler::stocks))
+        .route("/market", get(handler::market))
+        .route("/current/:symbol", get(handler::current))
+        .route("/chart/:period/:symbol", get(handler::chart))
+        .route("/fundamentals/:symbol", get(handler::fundamentals))
+        .route("/sectors", get(handler::sectors))
+        .route("/sectors/:code", get(handler::members))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::authenticate))
+        .with_state(state);
+    Router::new().nest(API_PREFIX, api).fallback(handler::fallback)
+}
+
+pub async fn serve(listener: std::net::TcpListener, state: AppState) -> anyhow::Result<()> {
+    listener.set_nonblocking(true)?;
+    tracing::info!("listening on {}", listener.local_addr()?);
+    axum::Server::from_tcp(listener)?
+        .serve(router(state).into_make_service())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use reqwest::header;
+    use trading_data::{
+        headers, sign, BarLoader, ChartLoader, ChartParamter, Credential, DataError, FundamentalsLoader,
+        MarketCurrentLoader, Period, RemoteLoader, SectorKind, SectorLoader, SignVersion, Stock, StocksLoader,
+        Verifier,
+    };
+
+    use trading_data::LocalLoader;
+
+    use super::*;
+
+    fn credential() -> Credential {
+        Credential { access_key: "test".to_string(), secret_key: "secret".to_string() }
+    }
+
+    fn data_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("trading-server-{}", fastrand::u64(..)));
+        std::fs::create_dir_all(dir.join("stocks/day/60/04")).unwrap();
+        std::fs::write(dir.join("stocks.csv"), "股票代码\t股票名称\n600444\t国机通用\n600795\t国电电力").unwrap();
+        std::fs::write(
+            dir.join("stocks/day/60/04/600444.csv"),
+            "date,open,high,low,close,volume\n\
+             2023-07-10,10.0,10.5,9.8,10.2,1000\n\
+             2023-07-11,10.2,10.8,10.1,10.6,1200\n\
+             2023-07-12,10.6,10.9,10.3,10.4,900",
+        )
+        .unwrap();
+        std::fs::write(dir.join("sectors.csv"), "code\tname\tkind\n000300\t沪深300\tindex").unwrap();
+        std::fs::create_dir_all(dir.join("sectors")).unwrap();
+        std::fs::write(dir.join("sectors/000300.csv"), "symbol\tadded\tremoved\n600444\t2023-01-01\t").unwrap();
+        std::fs::create_dir_all(dir.join("fundamentals/60/04")).unwrap();
+        std::fs::write(
+            dir.join("fundamentals/60/04/600444.csv"),
+            "date,total_shares,float_shares,eps_ttm,bps\n2023-04-28,2000,1000,0.52,5.2",
+        )
+        .unwrap();
+        dir
+    }
+
+    async fn start() -> (String, PathBuf) {
+        let dir = data_dir();
+        let loader = LocalLoader::new(&dir).unwrap();
+        let state = AppState::new(loader.clone(), Authenticator::new(vec![credential()], Verifier::default()))
+            .with_local(loader);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}{}", listener.local_addr().unwrap(), API_PREFIX);
+        tokio::spawn(serve(listener, state));
+        (host, dir)
+    }
+
+    #[tokio::test]
+    async fn remote_loader() {
+        let (host, dir) = start().await;
+
+        let anonymous = RemoteLoader::default().with_host(&host);
+        let err = anonymous.stocks().await.unwrap_err();
+        assert!(matches!(err, DataError::Unauthorized(_)), "unsigned request must be rejected");
+
+        let loader = anonymous
+            .with_credential(credential())
+            .with_sign_version(SignVersion::HmacSha256);
+        let stocks = loader.stocks().await.unwrap();
+        assert_eq!(stocks.len(), 2);
+        assert_eq!(stocks[0], Stock::new("国机通用", "600444"));
+
+        let chart = loader.chart(ChartParamter::day("600444").limit(2)).await.unwrap();
+        assert_eq!(chart.len(), 2);
+        assert_eq!(chart[0].date, "2023-07-11");
+        assert_eq!(chart[1].close, 10.4);
+
+        let chart = loader.chart(ChartParamter::new("600444", Period::Week)).await.unwrap();
+        assert_eq!(chart.len(), 1);
+
+        assert!(loader.chart(ChartParamter::day("000001")).await.unwrap_err().is_not_found());
+        assert!(loader.current("000001").await.unwrap_err().is_not_found());
+
+        let market = loader.market().await.unwrap();
+        assert_eq!(market.len(), 1);
+        assert_eq!((market["600444"].date.as_str(), market["600444"].close), ("2023-07-12", 10.4));
+
+        let fundamentals = loader.fundamentals("600444").await.unwrap();
+        assert_eq!(fundamentals.len(), 1);
+        assert_eq!(fundamentals[0].bps, Some(5.2));
+        assert!(loader.fundamentals("000001").await.unwrap_err().is_not_found());
+
+        let sectors = loader.sectors().await.unwrap();
+        assert_eq!((sectors[0].code.as_str(), sectors[0].kind), ("000300", SectorKind::Index));
+        let members = loader.members("000300").await.unwrap();
+        assert_eq!(members.symbols(Some("2023-07-10")), ["600444"]);
+        assert!(loader.members("000905").await.unwrap_err().is_not_found());
+
+        let legacy = loader.with_sign_version(SignVersion::Legacy);
+        assert_eq!(legacy.stocks().await.unwrap().len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn csv_response() {
+        let (host, dir) = start().await;
+        let credential = credential();
+
+        let request = |path: &str, timestamp: i64| {
+            let uri = path.split('?').next().unwrap_or_default();
+            let sign = sign("0.1.0", &credential.secret_key, uri, &timestamp.to_string());
+            reqwest::Client::new()
+                .get(format!("{}{}", host, path))
+                .header(header::ACCEPT, "text/csv")
+                .header(headers::VERSION, "0.1.0")
+                .header(headers::TIMESTAMP, timestamp)
+                .header(headers::AK, credential.access_key.as_str())
+                .header(headers::SIGN, sign)
+        };
+
+        let now = chrono::Local::now().timestamp_millis();
+        let resp = request("/chart/day/600444", now).send().await.unwrap();
+        assert!(resp.status().is_success());
+        assert!(resp.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/csv"));
+        let content = resp.text().await.unwrap();
+        assert_eq!(content.lines().count(), 4);
+        assert_eq!(content.lines().nth(1), Some("2023-07-10,10,10.5,9.8,10.2,1000"));
+
+        let resp = request("/stocks", now).send().await.unwrap();
+        assert_eq!(resp.text().await.unwrap().lines().nth(1), Some("600444\t国机通用"));
+
+        for path in [
+            "/current/1",
+            "/chart/day/60044%C3%A9",
+            "/fundamentals/..%2F..%2F600444",
+            "/chart/5m/600444?end=2023",
+            "/chart/day/600444?start=2023-07",
+        ] {
+            let resp = request(path, now).send().await.unwrap();
+            assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST, "{}", path);
+        }
+
+        let resp = request("/stocks", now - 3_600_000).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let body = vec![b'0'; Authenticator::BODY_LIMIT + 1];
+        let resp = request("/stocks", now).body(body).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use clap::Parser;
+use trading_data::loader::layout::{self, MigrateOptions};
+use trading_data::{Config, Credential, CredentialProvider, LocalLoader, MultiCredentialProvider, Verifier};
+use trading_server::{AppState, Authenticator};
+
+/// 本地数据服务
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// 监听地址
+    #[arg(long, env = "TRADING_SERVER_LISTEN", default_value = "127.0.0.1:18686")]
+    listen: String,
+
+    /// 数据目录，默认使用配置文件中当前 profile 的数据目录
+    #[arg(long, env = "TRADING_SERVER_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+
+    /// 访问凭证文件，内容为 `Credential` 数组；未指定时使用本机默认凭证
+    #[arg(long, env = "TRADING_SERVER_CREDENTIALS")]
+    credentials: Option<PathBuf>,
+
+    /// 允许的客户端时间偏差（秒）
+    #[arg(long, default_value_t = 300)]
+    window: u64,
+
+    /// 拒绝旧版 md5 签名的请求
+    #[arg(long)]
+    no_legacy: bool,
+
+    /// 不校验请求签名，只用于本机调试；未指定时必须配置凭证
+    #[arg(long)]
+    no_auth: bool,
+
+    /// 启动前把数据目录升级到当前版本，修改的文件备份到 `backup/`
+    #[arg(long)]
+    migrate: bool,
+
+    /// 与 `--migrate` 一起使用，只输出需要修改的文件后退出
+    #[arg(long, requires = "migrate")]
+    dry_run: bool,
+}
+
+fn credentials(path: Option<PathBuf>) -> anyhow::Result<Vec<Credential>> {
+    match path {
+        Some(path) => {
+            let content = std::fs::read_to_string(&path).context(format!("read credentials: {:?}", path))?;
+            serde_json::from_str(&content).context("deserialize credentials file")
+        }
+        None => Ok(MultiCredentialProvider::default().credential()?.into_iter().collect()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
+
+    let args = Args::parse();
+    let data_dir = match args.data_dir {
+        Some(dir) => dir,
+        None => Config::load()?.active()?.data_dir()?,
+    };
+    if args.migrate {
+        let options = MigrateOptions { dry_run: args.dry_run, backup: true };
+        let report = layout::migrate(&data_dir, options)?;
+        for (from, description, changes) in &report.steps {
+            tracing::info!("layout {} -> {}: {}, {} files", from, from + 1, description, changes.len());
+        }
+        if args.dry_run {
+            return Ok(());
+        }
+    }
+    let auth = if args.no_auth {
+        tracing::warn!("--no-auth is set, requests will not be verified");
+        Authenticator::disabled()
+    } else {
+        let credentials = credentials(args.credentials)?;
+        if credentials.is_empty() {
+            anyhow::bail!("no credential configured, use --credentials or pass --no-auth to disable verification");
+        }
+        let verifier = Verifier::new(Duration::from_secs(args.window)).with_legacy(!args.no_legacy);
+        Authenticator::new(credentials, verifier)
+    };
+
+    let loader = LocalLoader::new(&data_dir)?;
+    tracing::info!("serving {:?}", data_dir);
+
+    let listener = std::net::TcpListener::bind(&args.listen).context(format!("bind {}", args.listen))?;
+    let state = AppState::new(loader.clone(), auth).with_local(loader);
+    trading_server::serve(listener, state).await
+}